@@ -0,0 +1,26 @@
+//! Decodes `SOLEND_PROGRAM_ID`, when set, so a malformed id fails the build rather
+//! than `Solend::id()` at runtime.
+
+use std::{env, fs, path::Path};
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=SOLEND_PROGRAM_ID");
+    let program_id = match env::var("SOLEND_PROGRAM_ID") {
+        Ok(program_id) => {
+            let bytes = bs58::decode(&program_id)
+                .into_vec()
+                .unwrap_or_else(|err| panic!("SOLEND_PROGRAM_ID is not base58: {}", err));
+            if bytes.len() != 32 {
+                panic!("SOLEND_PROGRAM_ID decodes to {} bytes, not 32", bytes.len());
+            }
+            format!("Some({:?})", bytes)
+        }
+        Err(_) => "None".to_string(),
+    };
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(
+        Path::new(&out_dir).join("solend_program_id.rs"),
+        format!("const SOLEND_PROGRAM_ID: Option<[u8; 32]> = {};\n", program_id),
+    )
+    .unwrap();
+}
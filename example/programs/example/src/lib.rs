@@ -1,22 +1,917 @@
-use anchor_lang::prelude::*;
+//! Reference integration of the solend-anchor wrappers: one instruction per wrapper,
+//! each forwarding its accounts to Solend through `CpiContext::new`, plus `pda_*`
+//! instructions where a PDA of this program owns the obligation and signs through
+//! `CpiContext::new_with_signer`.
 
-use solend_anchor::init_lending_market;
+use anchor_lang::prelude::*;
+use solend_anchor::{
+    obligation::{create_obligation_with_seed, CreateObligationAccounts},
+    *,
+};
+use solend_token_lending::state::{ReserveConfig, ReserveFees};
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
+pub const AUTHORITY_SEED: &[u8] = b"authority";
+
+/// PDA of this program that owns `user`'s obligation in the `pda_*` instructions
+pub fn authority_address(user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[AUTHORITY_SEED, user.as_ref()], &id())
+}
+
+/// `ReserveConfig` as an instruction argument
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct ReserveConfigArgs {
+    pub optimal_utilization_rate: u8,
+    pub loan_to_value_ratio: u8,
+    pub liquidation_bonus: u8,
+    pub liquidation_threshold: u8,
+    pub min_borrow_rate: u8,
+    pub optimal_borrow_rate: u8,
+    pub max_borrow_rate: u8,
+    pub borrow_fee_wad: u64,
+    pub flash_loan_fee_wad: u64,
+    pub host_fee_percentage: u8,
+    pub deposit_limit: u64,
+    pub borrow_limit: u64,
+}
+
+impl ReserveConfigArgs {
+    fn fees(&self) -> ReserveFees {
+        ReserveFees {
+            borrow_fee_wad: self.borrow_fee_wad,
+            flash_loan_fee_wad: self.flash_loan_fee_wad,
+            host_fee_percentage: self.host_fee_percentage,
+        }
+    }
+
+    fn config_data(&self) -> ReserveConfigData {
+        ReserveConfigData {
+            optimal_utilization_rate: self.optimal_utilization_rate,
+            loan_to_value_ratio: self.loan_to_value_ratio,
+            liquidation_bonus: self.liquidation_bonus,
+            liquidation_threshold: self.liquidation_threshold,
+            min_borrow_rate: self.min_borrow_rate,
+            optimal_borrow_rate: self.optimal_borrow_rate,
+            max_borrow_rate: self.max_borrow_rate,
+            fees: self.fees(),
+            deposit_limit: self.deposit_limit,
+            borrow_limit: self.borrow_limit,
+        }
+    }
+
+    fn config(&self, fee_receiver: Pubkey) -> ReserveConfig {
+        ReserveConfig {
+            optimal_utilization_rate: self.optimal_utilization_rate,
+            loan_to_value_ratio: self.loan_to_value_ratio,
+            liquidation_bonus: self.liquidation_bonus,
+            liquidation_threshold: self.liquidation_threshold,
+            min_borrow_rate: self.min_borrow_rate,
+            optimal_borrow_rate: self.optimal_borrow_rate,
+            max_borrow_rate: self.max_borrow_rate,
+            fees: self.fees(),
+            deposit_limit: self.deposit_limit,
+            borrow_limit: self.borrow_limit,
+            fee_receiver,
+        }
+    }
+}
+
 #[program]
 pub mod example {
-
     use super::*;
-    pub fn initialize(ctx: Context<Initialize>) -> ProgramResult {
-        
-        // let cpi_context = 
 
-        // init_lending_market(ctx, quote_currency);
-        
-        Ok(())
+    // Market admin
+
+    pub fn init_lending_market(
+        ctx: Context<InitLendingMarket>,
+        quote_currency: [u8; 32],
+    ) -> ProgramResult {
+        let accounts = InitLendingMarketAccounts {
+            owner: ctx.accounts.owner.to_account_info(),
+            lending_market_pubkey: ctx.accounts.lending_market.clone(),
+            oracle_program_id: ctx.accounts.oracle_program_id.clone(),
+            switchboard_oracle_program_id: ctx.accounts.switchboard_oracle_program_id.clone(),
+            rent: ctx.accounts.rent.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+        };
+        solend_anchor::init_lending_market(
+            CpiContext::new(ctx.accounts.solend_program.to_account_info(), accounts),
+            quote_currency,
+        )
+    }
+
+    pub fn set_lending_market_owner(ctx: Context<SetLendingMarketOwner>) -> ProgramResult {
+        let accounts = SetLendingMarketOwnerAccounts {
+            new_owner: ctx.accounts.new_owner.clone(),
+            lending_market_pubkey: ctx.accounts.lending_market.clone(),
+            lending_market_owner: ctx.accounts.lending_market_owner.to_account_info(),
+        };
+        solend_anchor::set_lending_market_owner(CpiContext::new(
+            ctx.accounts.solend_program.to_account_info(),
+            accounts,
+        ))
+    }
+
+    // Reserve lifecycle
+
+    pub fn init_reserve(
+        ctx: Context<InitReserve>,
+        liquidity_amount: u64,
+        config: ReserveConfigArgs,
+    ) -> ProgramResult {
+        let accounts = InitReserveAccounts {
+            source_liquidity_pubkey: ctx.accounts.source_liquidity.clone(),
+            destination_collateral_pubkey: ctx.accounts.destination_collateral.clone(),
+            reserve_pubkey: ctx.accounts.reserve.clone(),
+            reserve_liquidity_mint_pubkey: ctx.accounts.reserve_liquidity_mint.clone(),
+            reserve_liquidity_supply_pubkey: ctx.accounts.reserve_liquidity_supply.clone(),
+            reserve_collateral_mint_pubkey: ctx.accounts.reserve_collateral_mint.clone(),
+            reserve_collateral_supply_pubkey: ctx.accounts.reserve_collateral_supply.clone(),
+            pyth_product_pubkey: ctx.accounts.pyth_product.clone(),
+            pyth_price_pubkey: ctx.accounts.pyth_price.clone(),
+            switchboard_feed_pubkey: ctx.accounts.switchboard_feed.clone(),
+            lending_market_pubkey: ctx.accounts.lending_market.clone(),
+            lending_market_owner_pubkey: ctx.accounts.lending_market_owner.to_account_info(),
+            user_transfer_authority_pubkey: ctx
+                .accounts
+                .user_transfer_authority
+                .to_account_info(),
+            fee_receiver_pubkey: ctx.accounts.fee_receiver.clone(),
+            lending_market_authority_pubkey: ctx.accounts.lending_market_authority.clone(),
+            rent_sysvar: ctx.accounts.rent.clone(),
+            clock_sysvar: ctx.accounts.clock.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+        };
+        solend_anchor::init_reserve(
+            CpiContext::new(ctx.accounts.solend_program.to_account_info(), accounts),
+            liquidity_amount,
+            config.config_data(),
+        )
+    }
+
+    pub fn refresh_reserve(ctx: Context<RefreshReserve>) -> ProgramResult {
+        let accounts = RefreshReserveAccounts {
+            reserve_pubkey: ctx.accounts.reserve.clone(),
+            reserve_liquidity_pyth_oracle_pubkey: ctx.accounts.pyth_price.clone(),
+            reserve_liquidity_switchboard_oracle_pubkey: ctx.accounts.switchboard_feed.clone(),
+            clock_sysvar: ctx.accounts.clock.clone(),
+        };
+        solend_anchor::refresh_reserve(CpiContext::new(
+            ctx.accounts.solend_program.to_account_info(),
+            accounts,
+        ))
+    }
+
+    pub fn update_reserve_config(
+        ctx: Context<UpdateReserveConfig>,
+        config: ReserveConfigArgs,
+        fee_receiver: Pubkey,
+    ) -> ProgramResult {
+        let accounts = UpdateReserveConfigAccounts {
+            reserve_pubkey: ctx.accounts.reserve.clone(),
+            lending_market_pubkey: ctx.accounts.lending_market.clone(),
+            lending_market_authority_pubkey: ctx.accounts.lending_market_authority.clone(),
+            lending_market_owner_pubkey: ctx.accounts.lending_market_owner.to_account_info(),
+            pyth_product_pubkey: ctx.accounts.pyth_product.clone(),
+            pyth_price_pubkey: ctx.accounts.pyth_price.clone(),
+            switchboard_feed_pubkey: ctx.accounts.switchboard_feed.clone(),
+        };
+        solend_anchor::update_reserve_config(
+            CpiContext::new(ctx.accounts.solend_program.to_account_info(), accounts),
+            config.config(fee_receiver),
+        )
+    }
+
+    pub fn deposit_reserve_liquidity(
+        ctx: Context<DepositReserveLiquidity>,
+        liquidity_amount: u64,
+    ) -> ProgramResult {
+        let accounts = DepositReserveLiquidityAccounts {
+            source_liquidity_pubkey: ctx.accounts.source_liquidity.clone(),
+            destination_collateral_pubkey: ctx.accounts.destination_collateral.clone(),
+            reserve_pubkey: ctx.accounts.reserve.clone(),
+            reserve_liquidity_supply_pubkey: ctx.accounts.reserve_liquidity_supply.clone(),
+            reserve_collateral_mint_pubkey: ctx.accounts.reserve_collateral_mint.clone(),
+            lending_market_pubkey: ctx.accounts.lending_market.clone(),
+            lending_market_authority_pubkey: ctx.accounts.lending_market_authority.clone(),
+            user_transfer_authority_pubkey: ctx
+                .accounts
+                .user_transfer_authority
+                .to_account_info(),
+            clock_sysvar: ctx.accounts.clock.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+        };
+        solend_anchor::deposit_reserve_liquidity(
+            CpiContext::new(ctx.accounts.solend_program.to_account_info(), accounts),
+            liquidity_amount,
+        )
+    }
+
+    pub fn redeem_reserve_collateral(
+        ctx: Context<RedeemReserveCollateral>,
+        collateral_amount: u64,
+    ) -> ProgramResult {
+        let accounts = RedeemReserveCollateralAccounts {
+            source_collateral_pubkey: ctx.accounts.source_collateral.clone(),
+            destination_liquidity_pubkey: ctx.accounts.destination_liquidity.clone(),
+            reserve_pubkey: ctx.accounts.reserve.clone(),
+            reserve_collateral_mint_pubkey: ctx.accounts.reserve_collateral_mint.clone(),
+            reserve_liquidity_supply_pubkey: ctx.accounts.reserve_liquidity_supply.clone(),
+            lending_market_pubkey: ctx.accounts.lending_market.clone(),
+            lending_market_authority_pubkey: ctx.accounts.lending_market_authority.clone(),
+            user_transfer_authority_pubkey: ctx
+                .accounts
+                .user_transfer_authority
+                .to_account_info(),
+            clock_sysvar: ctx.accounts.clock.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+        };
+        solend_anchor::redeem_reserve_collateral(
+            CpiContext::new(ctx.accounts.solend_program.to_account_info(), accounts),
+            collateral_amount,
+        )
+    }
+
+    // Obligation lifecycle
+
+    pub fn init_obligation(ctx: Context<InitObligation>) -> ProgramResult {
+        let accounts = InitObligationAccounts {
+            obligation_pubkey: ctx.accounts.obligation.clone(),
+            lending_market_pubkey: ctx.accounts.lending_market.clone(),
+            obligation_owner_pubkey: ctx.accounts.obligation_owner.to_account_info(),
+            clock_sysvar: ctx.accounts.clock.clone(),
+            rent_sysvar: ctx.accounts.rent.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+        };
+        solend_anchor::init_obligation(CpiContext::new(
+            ctx.accounts.solend_program.to_account_info(),
+            accounts,
+        ))
+    }
+
+    /// The remaining accounts are the obligation's deposit reserves, then its borrow reserves
+    pub fn refresh_obligation(ctx: Context<RefreshObligation>) -> ProgramResult {
+        let accounts = RefreshObligationAccounts {
+            obligation_pubkey: ctx.accounts.obligation.clone(),
+            reserve_pubkeys: ctx.accounts.clock.to_account_info(),
+        };
+        solend_anchor::refresh_obligation(
+            CpiContext::new(ctx.accounts.solend_program.to_account_info(), accounts)
+                .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+        )
+    }
+
+    pub fn deposit_obligation_collateral(
+        ctx: Context<DepositObligationCollateral>,
+        collateral_amount: u64,
+    ) -> ProgramResult {
+        let accounts = DepositObligationCollateralAccounts {
+            source_collateral_pubkey: ctx.accounts.source_collateral.clone(),
+            destination_collateral_pubkey: ctx.accounts.destination_collateral.clone(),
+            deposit_reserve_pubkey: ctx.accounts.deposit_reserve.clone(),
+            obligation_pubkey: ctx.accounts.obligation.clone(),
+            lending_market_pubkey: ctx.accounts.lending_market.clone(),
+            obligation_owner_pubkey: ctx.accounts.obligation_owner.to_account_info(),
+            user_transfer_authority_pubkey: ctx
+                .accounts
+                .user_transfer_authority
+                .to_account_info(),
+            clock_sysvar: ctx.accounts.clock.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+        };
+        solend_anchor::deposit_obligation_collateral(
+            CpiContext::new(ctx.accounts.solend_program.to_account_info(), accounts),
+            collateral_amount,
+        )
+    }
+
+    pub fn deposit_reserve_liquidity_and_obligation_collateral(
+        ctx: Context<DepositReserveLiquidityAndObligationCollateral>,
+        liquidity_amount: u64,
+    ) -> ProgramResult {
+        let user = ctx.accounts.user.to_account_info();
+        let accounts =
+            deposit_and_collateralize_accounts(&ctx.accounts.deposit, user.clone(), user);
+        solend_anchor::deposit_reserve_liquidity_and_obligation_collateral(
+            CpiContext::new(ctx.accounts.deposit.solend_program.to_account_info(), accounts),
+            liquidity_amount,
+        )
+    }
+
+    pub fn withdraw_obligation_collateral(
+        ctx: Context<WithdrawObligationCollateral>,
+        collateral_amount: u64,
+    ) -> ProgramResult {
+        let accounts =
+            withdraw_accounts(&ctx.accounts.withdraw, ctx.accounts.user.to_account_info());
+        solend_anchor::withdraw_obligation_collateral(
+            CpiContext::new(ctx.accounts.withdraw.solend_program.to_account_info(), accounts),
+            collateral_amount,
+        )
+    }
+
+    /// A host fee receiver may be passed as the only remaining account
+    pub fn borrow_obligation_liquidity(
+        ctx: Context<BorrowObligationLiquidity>,
+        liquidity_amount: u64,
+    ) -> ProgramResult {
+        let accounts = borrow_accounts(&ctx.accounts.borrow, ctx.accounts.user.to_account_info());
+        solend_anchor::borrow_obligation_liquidity(
+            CpiContext::new(ctx.accounts.borrow.solend_program.to_account_info(), accounts)
+                .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            liquidity_amount,
+        )
+    }
+
+    pub fn repay_obligation_liquidity(
+        ctx: Context<RepayObligationLiquidity>,
+        liquidity_amount: u64,
+    ) -> ProgramResult {
+        let accounts = repay_accounts(&ctx.accounts.repay, ctx.accounts.user.to_account_info());
+        solend_anchor::repay_obligation_liquidity(
+            CpiContext::new(ctx.accounts.repay.solend_program.to_account_info(), accounts),
+            liquidity_amount,
+        )
+    }
+
+    // Liquidation
+
+    pub fn liquidate_obligation(
+        ctx: Context<LiquidateObligation>,
+        liquidity_amount: u64,
+    ) -> ProgramResult {
+        let accounts = LiquidateObligationAccounts {
+            source_liquidity_pubkey: ctx.accounts.source_liquidity.clone(),
+            destination_collateral_pubkey: ctx.accounts.destination_collateral.clone(),
+            repay_reserve_pubkey: ctx.accounts.repay_reserve.clone(),
+            repay_reserve_liquidity_supply_pubkey: ctx
+                .accounts
+                .repay_reserve_liquidity_supply
+                .clone(),
+            withdraw_reserve_pubkey: ctx.accounts.withdraw_reserve.clone(),
+            withdraw_reserve_collateral_supply_pubkey: ctx
+                .accounts
+                .withdraw_reserve_collateral_supply
+                .clone(),
+            obligation_pubkey: ctx.accounts.obligation.clone(),
+            lending_market_pubkey: ctx.accounts.lending_market.clone(),
+            lending_market_authority_pubkey: ctx.accounts.lending_market_authority.clone(),
+            user_transfer_authority_pubkey: ctx
+                .accounts
+                .user_transfer_authority
+                .to_account_info(),
+            clock_sysvar: ctx.accounts.clock.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+        };
+        solend_anchor::liquidate_obligation(
+            CpiContext::new(ctx.accounts.solend_program.to_account_info(), accounts),
+            liquidity_amount,
+        )
+    }
+
+    // Flash loan
+
+    /// The remaining accounts are passed on to the flash loan receiver program
+    pub fn flash_loan(ctx: Context<FlashLoan>, amount: u64) -> ProgramResult {
+        let accounts = FlashLoanAccounts {
+            source_liquidity_pubkey: ctx.accounts.source_liquidity.clone(),
+            destination_liquidity_pubkey: ctx.accounts.destination_liquidity.clone(),
+            reserve_pubkey: ctx.accounts.reserve.clone(),
+            reserve_liquidity_fee_receiver_pubkey: ctx
+                .accounts
+                .reserve_liquidity_fee_receiver
+                .clone(),
+            host_fee_receiver_pubkey: ctx.accounts.host_fee_receiver.clone(),
+            lending_market_pubkey: ctx.accounts.lending_market.clone(),
+            lending_market_authority_pubkey: ctx.accounts.lending_market_authority.clone(),
+            flash_loan_receiver_program_id: ctx.accounts.flash_loan_receiver_program.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+        };
+        solend_anchor::flash_loan(
+            CpiContext::new(ctx.accounts.solend_program.to_account_info(), accounts)
+                .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            amount,
+        )
+    }
+
+    // PDA-owned obligation
+
+    /// Creates the obligation of `user`'s authority PDA at its seed-derived address
+    pub fn pda_create_obligation(ctx: Context<PdaCreateObligation>) -> ProgramResult {
+        let user = ctx.accounts.user.key();
+        let (_, bump) = authority_address(&user);
+        let accounts = CreateObligationAccounts {
+            payer: ctx.accounts.user.to_account_info(),
+            obligation_pubkey: ctx.accounts.obligation.clone(),
+            lending_market_pubkey: ctx.accounts.lending_market.clone(),
+            obligation_owner_pubkey: ctx.accounts.authority.clone(),
+            clock_sysvar: ctx.accounts.clock.clone(),
+            rent_sysvar: ctx.accounts.rent.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+        };
+        create_obligation_with_seed(CpiContext::new_with_signer(
+            ctx.accounts.solend_program.to_account_info(),
+            accounts,
+            &[&[AUTHORITY_SEED, user.as_ref(), &[bump]]],
+        ))
+    }
+
+    /// Deposits liquidity held by the authority PDA into its obligation
+    pub fn pda_deposit(
+        ctx: Context<PdaDepositReserveLiquidityAndObligationCollateral>,
+        liquidity_amount: u64,
+    ) -> ProgramResult {
+        let user = ctx.accounts.user.key();
+        let (_, bump) = authority_address(&user);
+        let accounts = deposit_and_collateralize_accounts(
+            &ctx.accounts.deposit,
+            ctx.accounts.authority.clone(),
+            ctx.accounts.authority.clone(),
+        );
+        solend_anchor::deposit_reserve_liquidity_and_obligation_collateral(
+            CpiContext::new_with_signer(
+                ctx.accounts.deposit.solend_program.to_account_info(),
+                accounts,
+                &[&[AUTHORITY_SEED, user.as_ref(), &[bump]]],
+            ),
+            liquidity_amount,
+        )
+    }
+
+    pub fn pda_withdraw(
+        ctx: Context<PdaWithdrawObligationCollateral>,
+        collateral_amount: u64,
+    ) -> ProgramResult {
+        let user = ctx.accounts.user.key();
+        let (_, bump) = authority_address(&user);
+        let accounts = withdraw_accounts(&ctx.accounts.withdraw, ctx.accounts.authority.clone());
+        solend_anchor::withdraw_obligation_collateral(
+            CpiContext::new_with_signer(
+                ctx.accounts.withdraw.solend_program.to_account_info(),
+                accounts,
+                &[&[AUTHORITY_SEED, user.as_ref(), &[bump]]],
+            ),
+            collateral_amount,
+        )
+    }
+
+    pub fn pda_borrow(
+        ctx: Context<PdaBorrowObligationLiquidity>,
+        liquidity_amount: u64,
+    ) -> ProgramResult {
+        let user = ctx.accounts.user.key();
+        let (_, bump) = authority_address(&user);
+        let accounts = borrow_accounts(&ctx.accounts.borrow, ctx.accounts.authority.clone());
+        solend_anchor::borrow_obligation_liquidity(
+            CpiContext::new_with_signer(
+                ctx.accounts.borrow.solend_program.to_account_info(),
+                accounts,
+                &[&[AUTHORITY_SEED, user.as_ref(), &[bump]]],
+            ),
+            liquidity_amount,
+        )
+    }
+
+    pub fn pda_repay(
+        ctx: Context<PdaRepayObligationLiquidity>,
+        liquidity_amount: u64,
+    ) -> ProgramResult {
+        let user = ctx.accounts.user.key();
+        let (_, bump) = authority_address(&user);
+        let accounts = repay_accounts(&ctx.accounts.repay, ctx.accounts.authority.clone());
+        solend_anchor::repay_obligation_liquidity(
+            CpiContext::new_with_signer(
+                ctx.accounts.repay.solend_program.to_account_info(),
+                accounts,
+                &[&[AUTHORITY_SEED, user.as_ref(), &[bump]]],
+            ),
+            liquidity_amount,
+        )
+    }
+}
+
+// The user and PDA variants of an instruction share these accounts. The obligation
+// owner and transfer authority are the signing user or the authority PDA.
+
+fn deposit_and_collateralize_accounts<'info>(
+    accounts: &ObligationDeposit<'info>,
+    obligation_owner: AccountInfo<'info>,
+    user_transfer_authority: AccountInfo<'info>,
+) -> DepositReserveLiquidityAndObligationCollateralAccounts<'info> {
+    DepositReserveLiquidityAndObligationCollateralAccounts {
+        source_liquidity_pubkey: accounts.source_liquidity.clone(),
+        user_collateral_pubkey: accounts.user_collateral.clone(),
+        reserve_pubkey: accounts.reserve.clone(),
+        reserve_liquidity_supply_pubkey: accounts.reserve_liquidity_supply.clone(),
+        reserve_collateral_mint_pubkey: accounts.reserve_collateral_mint.clone(),
+        lending_market_pubkey: accounts.lending_market.clone(),
+        lending_market_authority_pubkey: accounts.lending_market_authority.clone(),
+        destination_deposit_collateral_pubkey: accounts.reserve_collateral_supply.clone(),
+        obligation_pubkey: accounts.obligation.clone(),
+        obligation_owner_pubkey: obligation_owner,
+        reserve_liquidity_pyth_oracle_pubkey: accounts.pyth_price.clone(),
+        reserve_liquidity_switchboard_oracle_pubkey: accounts.switchboard_feed.clone(),
+        user_transfer_authority_pubkey: user_transfer_authority,
+        clock_sysvar: accounts.clock.clone(),
+        token_program: accounts.token_program.clone(),
+    }
+}
+
+fn withdraw_accounts<'info>(
+    accounts: &ObligationWithdraw<'info>,
+    obligation_owner: AccountInfo<'info>,
+) -> WithdrawObligationCollateralAccounts<'info> {
+    WithdrawObligationCollateralAccounts {
+        source_collateral_pubkey: accounts.source_collateral.clone(),
+        destination_collateral_pubkey: accounts.destination_collateral.clone(),
+        withdraw_reserve_pubkey: accounts.withdraw_reserve.clone(),
+        obligation_pubkey: accounts.obligation.clone(),
+        lending_market_pubkey: accounts.lending_market.clone(),
+        lending_market_authority_pubkey: accounts.lending_market_authority.clone(),
+        obligation_owner_pubkey: obligation_owner,
+        clock_sysvar: accounts.clock.clone(),
+        token_program: accounts.token_program.clone(),
+    }
+}
+
+fn borrow_accounts<'info>(
+    accounts: &ObligationBorrow<'info>,
+    obligation_owner: AccountInfo<'info>,
+) -> BorrowObligationLiquidityAccounts<'info> {
+    BorrowObligationLiquidityAccounts {
+        source_liquidity_pubkey: accounts.source_liquidity.clone(),
+        destination_liquidity_pubkey: accounts.destination_liquidity.clone(),
+        borrow_reserve_pubkey: accounts.borrow_reserve.clone(),
+        borrow_reserve_liquidity_fee_receiver_pubkey: accounts
+            .borrow_reserve_liquidity_fee_receiver
+            .clone(),
+        obligation_pubkey: accounts.obligation.clone(),
+        lending_market_pubkey: accounts.lending_market.clone(),
+        lending_market_authority_pubkey: accounts.lending_market_authority.clone(),
+        obligation_owner_pubkey: obligation_owner,
+        clock_sysvar: accounts.clock.clone(),
+        token_program: accounts.token_program.clone(),
+    }
+}
+
+fn repay_accounts<'info>(
+    accounts: &ObligationRepay<'info>,
+    user_transfer_authority: AccountInfo<'info>,
+) -> RepayObligationLiquidityAccounts<'info> {
+    RepayObligationLiquidityAccounts {
+        source_liquidity_pubkey: accounts.source_liquidity.clone(),
+        destination_liquidity_pubkey: accounts.destination_liquidity.clone(),
+        repay_reserve_pubkey: accounts.repay_reserve.clone(),
+        obligation_pubkey: accounts.obligation.clone(),
+        lending_market_pubkey: accounts.lending_market.clone(),
+        user_transfer_authority_pubkey: user_transfer_authority,
+        clock_sysvar: accounts.clock.clone(),
+        token_program: accounts.token_program.clone(),
     }
 }
 
+// Solend validates every account it is handed, so the accounts below are only
+// forwarded. Safety checks are turned off in Anchor.toml accordingly.
+
+#[derive(Accounts)]
+pub struct InitLendingMarket<'info> {
+    pub solend_program: Program<'info, Solend>,
+    pub owner: Signer<'info>,
+    #[account(mut)]
+    pub lending_market: AccountInfo<'info>,
+    pub oracle_program_id: AccountInfo<'info>,
+    pub switchboard_oracle_program_id: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+#[derive(Accounts)]
+pub struct SetLendingMarketOwner<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_owner: Signer<'info>,
+    pub new_owner: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitReserve<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub source_liquidity: AccountInfo<'info>,
+    #[account(mut)]
+    pub destination_collateral: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve: AccountInfo<'info>,
+    pub reserve_liquidity_mint: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve_liquidity_supply: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve_collateral_mint: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve_collateral_supply: AccountInfo<'info>,
+    #[account(mut)]
+    pub fee_receiver: AccountInfo<'info>,
+    pub pyth_product: AccountInfo<'info>,
+    pub pyth_price: AccountInfo<'info>,
+    pub switchboard_feed: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub lending_market_owner: Signer<'info>,
+    pub user_transfer_authority: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshReserve<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub reserve: AccountInfo<'info>,
+    pub pyth_price: AccountInfo<'info>,
+    pub switchboard_feed: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateReserveConfig<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub reserve: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub lending_market_owner: Signer<'info>,
+    pub pyth_product: AccountInfo<'info>,
+    pub pyth_price: AccountInfo<'info>,
+    pub switchboard_feed: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DepositReserveLiquidity<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub source_liquidity: AccountInfo<'info>,
+    #[account(mut)]
+    pub destination_collateral: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve_liquidity_supply: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve_collateral_mint: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub user_transfer_authority: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemReserveCollateral<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub source_collateral: AccountInfo<'info>,
+    #[account(mut)]
+    pub destination_liquidity: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve_collateral_mint: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve_liquidity_supply: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub user_transfer_authority: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+#[derive(Accounts)]
+pub struct InitObligation<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub obligation: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub obligation_owner: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshObligation<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub obligation: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct DepositObligationCollateral<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub source_collateral: AccountInfo<'info>,
+    #[account(mut)]
+    pub destination_collateral: AccountInfo<'info>,
+    pub deposit_reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub obligation: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub obligation_owner: Signer<'info>,
+    pub user_transfer_authority: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+#[derive(Accounts)]
+pub struct ObligationDeposit<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub source_liquidity: AccountInfo<'info>,
+    #[account(mut)]
+    pub user_collateral: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve_liquidity_supply: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve_collateral_mint: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve_collateral_supply: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub obligation: AccountInfo<'info>,
+    pub pyth_price: AccountInfo<'info>,
+    pub switchboard_feed: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+#[derive(Accounts)]
+pub struct ObligationWithdraw<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub source_collateral: AccountInfo<'info>,
+    #[account(mut)]
+    pub destination_collateral: AccountInfo<'info>,
+    pub withdraw_reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub obligation: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
 #[derive(Accounts)]
-pub struct Initialize {}
+pub struct ObligationBorrow<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub source_liquidity: AccountInfo<'info>,
+    #[account(mut)]
+    pub destination_liquidity: AccountInfo<'info>,
+    #[account(mut)]
+    pub borrow_reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub borrow_reserve_liquidity_fee_receiver: AccountInfo<'info>,
+    #[account(mut)]
+    pub obligation: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+#[derive(Accounts)]
+pub struct ObligationRepay<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub source_liquidity: AccountInfo<'info>,
+    #[account(mut)]
+    pub destination_liquidity: AccountInfo<'info>,
+    #[account(mut)]
+    pub repay_reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub obligation: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+#[derive(Accounts)]
+pub struct DepositReserveLiquidityAndObligationCollateral<'info> {
+    pub user: Signer<'info>,
+    pub deposit: ObligationDeposit<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawObligationCollateral<'info> {
+    pub user: Signer<'info>,
+    pub withdraw: ObligationWithdraw<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BorrowObligationLiquidity<'info> {
+    pub user: Signer<'info>,
+    pub borrow: ObligationBorrow<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RepayObligationLiquidity<'info> {
+    pub user: Signer<'info>,
+    pub repay: ObligationRepay<'info>,
+}
+
+#[derive(Accounts)]
+pub struct LiquidateObligation<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub source_liquidity: AccountInfo<'info>,
+    #[account(mut)]
+    pub destination_collateral: AccountInfo<'info>,
+    #[account(mut)]
+    pub repay_reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub repay_reserve_liquidity_supply: AccountInfo<'info>,
+    pub withdraw_reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub withdraw_reserve_collateral_supply: AccountInfo<'info>,
+    #[account(mut)]
+    pub obligation: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub user_transfer_authority: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+#[derive(Accounts)]
+pub struct FlashLoan<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub source_liquidity: AccountInfo<'info>,
+    #[account(mut)]
+    pub destination_liquidity: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub reserve_liquidity_fee_receiver: AccountInfo<'info>,
+    #[account(mut)]
+    pub host_fee_receiver: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub lending_market_authority: AccountInfo<'info>,
+    pub flash_loan_receiver_program: AccountInfo<'info>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+#[derive(Accounts)]
+pub struct PdaCreateObligation<'info> {
+    pub solend_program: Program<'info, Solend>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [AUTHORITY_SEED, user.key().as_ref()], bump)]
+    pub authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub obligation: AccountInfo<'info>,
+    pub lending_market: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+#[derive(Accounts)]
+pub struct PdaDepositReserveLiquidityAndObligationCollateral<'info> {
+    pub user: Signer<'info>,
+    #[account(seeds = [AUTHORITY_SEED, user.key().as_ref()], bump)]
+    pub authority: AccountInfo<'info>,
+    pub deposit: ObligationDeposit<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PdaWithdrawObligationCollateral<'info> {
+    pub user: Signer<'info>,
+    #[account(seeds = [AUTHORITY_SEED, user.key().as_ref()], bump)]
+    pub authority: AccountInfo<'info>,
+    pub withdraw: ObligationWithdraw<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PdaBorrowObligationLiquidity<'info> {
+    pub user: Signer<'info>,
+    #[account(seeds = [AUTHORITY_SEED, user.key().as_ref()], bump)]
+    pub authority: AccountInfo<'info>,
+    pub borrow: ObligationBorrow<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PdaRepayObligationLiquidity<'info> {
+    pub user: Signer<'info>,
+    #[account(seeds = [AUTHORITY_SEED, user.key().as_ref()], bump)]
+    pub authority: AccountInfo<'info>,
+    pub repay: ObligationRepay<'info>,
+}
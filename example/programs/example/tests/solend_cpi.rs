@@ -0,0 +1,946 @@
+//! Runs every example instruction against the Solend program, with mock Pyth accounts
+//! standing in for the oracle and a minimal flash loan receiver.
+
+use std::convert::TryInto;
+
+use anchor_lang::{Id, InstructionData, ToAccountMetas};
+use bytemuck::Zeroable;
+use example::{authority_address, ReserveConfigArgs};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke,
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+    transaction::Transaction,
+};
+use solend_anchor::{obligation::obligation_address, Solend};
+use solend_token_lending::{
+    math::Decimal,
+    pyth,
+    state::{LendingMarket, Obligation, Reserve},
+    NULL_PUBKEY,
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+/// One whole token at the 6 decimals every test mint uses
+const ONE: u64 = 1_000_000;
+const FLASH_LOAN_FEE_WAD: u64 = 3_000_000_000_000_000; // 0.3%
+
+fn process_example(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // Anchor's entrypoint ties the slice lifetime to the account lifetimes
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    example::entry(program_id, accounts, data)
+}
+
+/// Receives `[destination, reserve supply, token program, owner]` and pays back the
+/// amount Solend asks for from the destination account
+fn process_flash_loan_receiver(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let (tag, amount) = data.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+    if *tag != 0 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let amount = u64::from_le_bytes(
+        amount
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?,
+    );
+    let (destination, supply, token_program, owner) = match accounts {
+        [destination, supply, token_program, owner, ..] => {
+            (destination, supply, token_program, owner)
+        }
+        _ => return Err(ProgramError::NotEnoughAccountKeys),
+    };
+    let ix = spl_token::instruction::transfer(
+        &spl_token::id(),
+        destination.key,
+        supply.key,
+        owner.key,
+        &[],
+        amount,
+    )?;
+    invoke(
+        &ix,
+        &[destination.clone(), supply.clone(), owner.clone(), token_program.clone()],
+    )
+}
+
+fn config() -> ReserveConfigArgs {
+    ReserveConfigArgs {
+        optimal_utilization_rate: 80,
+        loan_to_value_ratio: 50,
+        liquidation_bonus: 5,
+        liquidation_threshold: 55,
+        min_borrow_rate: 0,
+        optimal_borrow_rate: 0,
+        max_borrow_rate: 0,
+        borrow_fee_wad: 0,
+        flash_loan_fee_wad: FLASH_LOAN_FEE_WAD,
+        host_fee_percentage: 0,
+        deposit_limit: u64::MAX,
+        borrow_limit: u64::MAX,
+    }
+}
+
+fn pyth_product(price: &Pubkey) -> Vec<u8> {
+    let mut product: pyth::Product = Zeroable::zeroed();
+    product.magic = pyth::MAGIC;
+    product.ver = pyth::VERSION_2;
+    product.atype = pyth::AccountType::Product as u32;
+    product.px_acc = pyth::AccKey {
+        val: price.to_bytes(),
+    };
+    let attr = b"\x0equote_currency\x03USD";
+    product.attr[..attr.len()].copy_from_slice(attr);
+    bytemuck::bytes_of(&product).to_vec()
+}
+
+/// `usd` per whole token, published in `slot`
+fn pyth_price(usd: i64, slot: u64) -> Vec<u8> {
+    let mut price: pyth::Price = Zeroable::zeroed();
+    price.magic = pyth::MAGIC;
+    price.ver = pyth::VERSION_2;
+    price.atype = pyth::AccountType::Price as u32;
+    price.ptype = pyth::PriceType::Price;
+    price.expo = -8;
+    price.valid_slot = slot;
+    price.agg.price = usd * 100_000_000;
+    price.agg.conf = 1_000;
+    price.agg.status = pyth::PriceStatus::Trading;
+    bytemuck::bytes_of(&price).to_vec()
+}
+
+fn rent_exempt(len: usize) -> u64 {
+    Rent::default().minimum_balance(len)
+}
+
+fn ix<A: ToAccountMetas, D: InstructionData>(accounts: A, data: D) -> Instruction {
+    Instruction {
+        program_id: example::id(),
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+fn with_remaining(mut ix: Instruction, remaining: Vec<AccountMeta>) -> Instruction {
+    ix.accounts.extend(remaining);
+    ix
+}
+
+struct TestReserve {
+    reserve: Pubkey,
+    liquidity_mint: Pubkey,
+    liquidity_supply: Pubkey,
+    collateral_mint: Pubkey,
+    collateral_supply: Pubkey,
+    fee_receiver: Pubkey,
+    pyth_product: Pubkey,
+    pyth_price: Pubkey,
+    /// Funded liquidity account of the test user
+    user_liquidity: Pubkey,
+    /// Initialized by `init_reserve` for the test user
+    user_collateral: Pubkey,
+}
+
+struct Setup {
+    test: ProgramTest,
+    user: Keypair,
+    market: Pubkey,
+    pyth_program: Pubkey,
+    flash_loan_receiver: Pubkey,
+}
+
+impl Setup {
+    fn new() -> Self {
+        let mut test = ProgramTest::new("example", example::id(), processor!(process_example));
+        test.add_program(
+            "spl_token_lending",
+            Solend::id(),
+            processor!(solend_token_lending::processor::process_instruction),
+        );
+        let flash_loan_receiver = Pubkey::new_unique();
+        test.add_program(
+            "flash_loan_receiver",
+            flash_loan_receiver,
+            processor!(process_flash_loan_receiver),
+        );
+
+        let user = Keypair::new();
+        test.add_account(
+            user.pubkey(),
+            Account::new(100_000_000_000, 0, &system_program::id()),
+        );
+
+        let market = Pubkey::new_unique();
+        Self::add_empty(&mut test, market, LendingMarket::LEN, Solend::id());
+
+        Self {
+            test,
+            user,
+            market,
+            pyth_program: Pubkey::new_unique(),
+            flash_loan_receiver,
+        }
+    }
+
+    fn add_empty(test: &mut ProgramTest, pubkey: Pubkey, len: usize, owner: Pubkey) {
+        test.add_account(
+            pubkey,
+            Account {
+                lamports: rent_exempt(len),
+                data: vec![0; len],
+                owner,
+                ..Account::default()
+            },
+        );
+    }
+
+    fn add_token_account(&mut self, mint: Pubkey, owner: Pubkey, amount: u64) -> Pubkey {
+        let pubkey = Pubkey::new_unique();
+        self.test.add_packable_account(
+            pubkey,
+            rent_exempt(TokenAccount::LEN),
+            &TokenAccount {
+                mint,
+                owner,
+                amount,
+                state: AccountState::Initialized,
+                ..TokenAccount::default()
+            },
+            &spl_token::id(),
+        );
+        pubkey
+    }
+
+    fn add_obligation_account(&mut self) -> Pubkey {
+        let pubkey = Pubkey::new_unique();
+        Self::add_empty(&mut self.test, pubkey, Obligation::LEN, Solend::id());
+        pubkey
+    }
+
+    /// Allocates a reserve's accounts, giving the user `user_amount` of its liquidity
+    fn add_reserve(&mut self, usd: i64, user_amount: u64) -> TestReserve {
+        let liquidity_mint = Pubkey::new_unique();
+        self.test.add_packable_account(
+            liquidity_mint,
+            rent_exempt(Mint::LEN),
+            &Mint {
+                mint_authority: COption::Some(self.user.pubkey()),
+                supply: user_amount,
+                decimals: 6,
+                is_initialized: true,
+                freeze_authority: COption::None,
+            },
+            &spl_token::id(),
+        );
+        let user_liquidity =
+            self.add_token_account(liquidity_mint, self.user.pubkey(), user_amount);
+
+        let reserve = TestReserve {
+            reserve: Pubkey::new_unique(),
+            liquidity_mint,
+            liquidity_supply: Pubkey::new_unique(),
+            collateral_mint: Pubkey::new_unique(),
+            collateral_supply: Pubkey::new_unique(),
+            fee_receiver: Pubkey::new_unique(),
+            pyth_product: Pubkey::new_unique(),
+            pyth_price: Pubkey::new_unique(),
+            user_liquidity,
+            user_collateral: Pubkey::new_unique(),
+        };
+        let test = &mut self.test;
+        Self::add_empty(test, reserve.reserve, Reserve::LEN, Solend::id());
+        Self::add_empty(test, reserve.collateral_mint, Mint::LEN, spl_token::id());
+        for account in [
+            reserve.liquidity_supply,
+            reserve.collateral_supply,
+            reserve.fee_receiver,
+            reserve.user_collateral,
+        ] {
+            Self::add_empty(test, account, TokenAccount::LEN, spl_token::id());
+        }
+
+        let product = pyth_product(&reserve.pyth_price);
+        let price = pyth_price(usd, 0);
+        for (pubkey, data) in [(reserve.pyth_product, product), (reserve.pyth_price, price)] {
+            test.add_account(
+                pubkey,
+                Account {
+                    lamports: rent_exempt(data.len()),
+                    data,
+                    owner: self.pyth_program,
+                    ..Account::default()
+                },
+            );
+        }
+        reserve
+    }
+
+    async fn start(self) -> Env {
+        let context = self.test.start_with_context().await;
+        let (market_authority, _) =
+            Pubkey::find_program_address(&[self.market.as_ref()], &Solend::id());
+        let mut env = Env {
+            context,
+            user: self.user,
+            market: self.market,
+            market_authority,
+            pyth_program: self.pyth_program,
+            flash_loan_receiver: self.flash_loan_receiver,
+        };
+        env.init_lending_market().await;
+        env
+    }
+}
+
+struct Env {
+    context: ProgramTestContext,
+    user: Keypair,
+    market: Pubkey,
+    market_authority: Pubkey,
+    pyth_program: Pubkey,
+    flash_loan_receiver: Pubkey,
+}
+
+impl Env {
+    /// Sends `ixs` signed by the fee payer and the test user
+    async fn process(&mut self, ixs: &[Instruction]) {
+        let blockhash = self.context.banks_client.get_latest_blockhash().await.unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            ixs,
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.user],
+            blockhash,
+        );
+        self.context.banks_client.process_transaction(tx).await.unwrap();
+    }
+
+    async fn data(&mut self, pubkey: Pubkey) -> Vec<u8> {
+        self.context
+            .banks_client
+            .get_account(pubkey)
+            .await
+            .unwrap()
+            .unwrap()
+            .data
+    }
+
+    async fn balance(&mut self, token_account: Pubkey) -> u64 {
+        TokenAccount::unpack(&self.data(token_account).await).unwrap().amount
+    }
+
+    async fn obligation(&mut self, obligation: Pubkey) -> Obligation {
+        Obligation::unpack(&self.data(obligation).await).unwrap()
+    }
+
+    fn set_price(&mut self, reserve: &TestReserve, usd: i64, slot: u64) {
+        let data = pyth_price(usd, slot);
+        let mut account = Account::new(rent_exempt(data.len()), data.len(), &self.pyth_program);
+        account.data = data;
+        self.context.set_account(&reserve.pyth_price, &AccountSharedData::from(account));
+    }
+
+    async fn init_lending_market(&mut self) {
+        let mut quote_currency = [0u8; 32];
+        quote_currency[..3].copy_from_slice(b"USD");
+        let ix = ix(
+            example::accounts::InitLendingMarket {
+                solend_program: Solend::id(),
+                owner: self.user.pubkey(),
+                lending_market: self.market,
+                oracle_program_id: self.pyth_program,
+                switchboard_oracle_program_id: Pubkey::new_unique(),
+                rent: sysvar::rent::id(),
+                token_program: spl_token::id(),
+            },
+            example::instruction::InitLendingMarket { quote_currency },
+        );
+        self.process(&[ix]).await;
+    }
+
+    async fn init_reserve(&mut self, reserve: &TestReserve, liquidity_amount: u64) {
+        let ix = ix(
+            example::accounts::InitReserve {
+                solend_program: Solend::id(),
+                source_liquidity: reserve.user_liquidity,
+                destination_collateral: reserve.user_collateral,
+                reserve: reserve.reserve,
+                reserve_liquidity_mint: reserve.liquidity_mint,
+                reserve_liquidity_supply: reserve.liquidity_supply,
+                reserve_collateral_mint: reserve.collateral_mint,
+                reserve_collateral_supply: reserve.collateral_supply,
+                fee_receiver: reserve.fee_receiver,
+                pyth_product: reserve.pyth_product,
+                pyth_price: reserve.pyth_price,
+                switchboard_feed: NULL_PUBKEY,
+                lending_market: self.market,
+                lending_market_authority: self.market_authority,
+                lending_market_owner: self.user.pubkey(),
+                user_transfer_authority: self.user.pubkey(),
+                clock: sysvar::clock::id(),
+                rent: sysvar::rent::id(),
+                token_program: spl_token::id(),
+            },
+            example::instruction::InitReserve {
+                liquidity_amount,
+                config: config(),
+            },
+        );
+        self.process(&[ix]).await;
+    }
+
+    fn refresh_reserve(&self, reserve: &TestReserve) -> Instruction {
+        ix(
+            example::accounts::RefreshReserve {
+                solend_program: Solend::id(),
+                reserve: reserve.reserve,
+                pyth_price: reserve.pyth_price,
+                switchboard_feed: NULL_PUBKEY,
+                clock: sysvar::clock::id(),
+            },
+            example::instruction::RefreshReserve {},
+        )
+    }
+
+    /// `reserves` are the obligation's deposit reserves followed by its borrow reserves
+    fn refresh_obligation(&self, obligation: Pubkey, reserves: &[&TestReserve]) -> Instruction {
+        with_remaining(
+            ix(
+                example::accounts::RefreshObligation {
+                    solend_program: Solend::id(),
+                    obligation,
+                    clock: sysvar::clock::id(),
+                },
+                example::instruction::RefreshObligation {},
+            ),
+            reserves
+                .iter()
+                .map(|reserve| AccountMeta::new_readonly(reserve.reserve, false))
+                .collect(),
+        )
+    }
+
+    fn obligation_deposit(
+        &self,
+        reserve: &TestReserve,
+        obligation: Pubkey,
+        source_liquidity: Pubkey,
+        user_collateral: Pubkey,
+    ) -> example::accounts::ObligationDeposit {
+        example::accounts::ObligationDeposit {
+            solend_program: Solend::id(),
+            source_liquidity,
+            user_collateral,
+            reserve: reserve.reserve,
+            reserve_liquidity_supply: reserve.liquidity_supply,
+            reserve_collateral_mint: reserve.collateral_mint,
+            reserve_collateral_supply: reserve.collateral_supply,
+            lending_market: self.market,
+            lending_market_authority: self.market_authority,
+            obligation,
+            pyth_price: reserve.pyth_price,
+            switchboard_feed: NULL_PUBKEY,
+            clock: sysvar::clock::id(),
+            token_program: spl_token::id(),
+        }
+    }
+
+    fn obligation_withdraw(
+        &self,
+        reserve: &TestReserve,
+        obligation: Pubkey,
+        destination_collateral: Pubkey,
+    ) -> example::accounts::ObligationWithdraw {
+        example::accounts::ObligationWithdraw {
+            solend_program: Solend::id(),
+            source_collateral: reserve.collateral_supply,
+            destination_collateral,
+            withdraw_reserve: reserve.reserve,
+            obligation,
+            lending_market: self.market,
+            lending_market_authority: self.market_authority,
+            clock: sysvar::clock::id(),
+            token_program: spl_token::id(),
+        }
+    }
+
+    fn obligation_borrow(
+        &self,
+        reserve: &TestReserve,
+        obligation: Pubkey,
+        destination_liquidity: Pubkey,
+    ) -> example::accounts::ObligationBorrow {
+        example::accounts::ObligationBorrow {
+            solend_program: Solend::id(),
+            source_liquidity: reserve.liquidity_supply,
+            destination_liquidity,
+            borrow_reserve: reserve.reserve,
+            borrow_reserve_liquidity_fee_receiver: reserve.fee_receiver,
+            obligation,
+            lending_market: self.market,
+            lending_market_authority: self.market_authority,
+            clock: sysvar::clock::id(),
+            token_program: spl_token::id(),
+        }
+    }
+
+    fn obligation_repay(
+        &self,
+        reserve: &TestReserve,
+        obligation: Pubkey,
+        source_liquidity: Pubkey,
+    ) -> example::accounts::ObligationRepay {
+        example::accounts::ObligationRepay {
+            solend_program: Solend::id(),
+            source_liquidity,
+            destination_liquidity: reserve.liquidity_supply,
+            repay_reserve: reserve.reserve,
+            obligation,
+            lending_market: self.market,
+            clock: sysvar::clock::id(),
+            token_program: spl_token::id(),
+        }
+    }
+
+    async fn init_obligation(&mut self, obligation: Pubkey) {
+        let ix = ix(
+            example::accounts::InitObligation {
+                solend_program: Solend::id(),
+                obligation,
+                lending_market: self.market,
+                obligation_owner: self.user.pubkey(),
+                clock: sysvar::clock::id(),
+                rent: sysvar::rent::id(),
+                token_program: spl_token::id(),
+            },
+            example::instruction::InitObligation {},
+        );
+        self.process(&[ix]).await;
+    }
+
+    /// Deposits `amount` of `reserve`'s liquidity into `obligation` and borrows
+    /// `borrow_amount` of `borrow_reserve` against it
+    async fn deposit_and_borrow(
+        &mut self,
+        obligation: Pubkey,
+        reserve: &TestReserve,
+        amount: u64,
+        borrow_reserve: &TestReserve,
+        borrow_amount: u64,
+    ) {
+        let deposit = ix(
+            example::accounts::DepositReserveLiquidityAndObligationCollateral {
+                user: self.user.pubkey(),
+                deposit: self.obligation_deposit(
+                    reserve,
+                    obligation,
+                    reserve.user_liquidity,
+                    reserve.user_collateral,
+                ),
+            },
+            example::instruction::DepositReserveLiquidityAndObligationCollateral {
+                liquidity_amount: amount,
+            },
+        );
+        self.process(&[deposit]).await;
+
+        let borrow = ix(
+            example::accounts::BorrowObligationLiquidity {
+                user: self.user.pubkey(),
+                borrow: self.obligation_borrow(
+                    borrow_reserve,
+                    obligation,
+                    borrow_reserve.user_liquidity,
+                ),
+            },
+            example::instruction::BorrowObligationLiquidity {
+                liquidity_amount: borrow_amount,
+            },
+        );
+        self.process(&[
+            self.refresh_reserve(reserve),
+            self.refresh_reserve(borrow_reserve),
+            self.refresh_obligation(obligation, &[reserve]),
+            borrow,
+        ])
+        .await;
+    }
+}
+
+#[tokio::test]
+async fn market_and_reserve_lifecycle() {
+    let mut setup = Setup::new();
+    let usdc = setup.add_reserve(1, 1_000 * ONE);
+    let mut env = setup.start().await;
+
+    env.init_reserve(&usdc, 100 * ONE).await;
+    assert_eq!(env.balance(usdc.liquidity_supply).await, 100 * ONE);
+    assert_eq!(env.balance(usdc.user_collateral).await, 100 * ONE);
+
+    let mut new_config = config();
+    new_config.loan_to_value_ratio = 40;
+    let update = ix(
+        example::accounts::UpdateReserveConfig {
+            solend_program: Solend::id(),
+            reserve: usdc.reserve,
+            lending_market: env.market,
+            lending_market_authority: env.market_authority,
+            lending_market_owner: env.user.pubkey(),
+            pyth_product: usdc.pyth_product,
+            pyth_price: usdc.pyth_price,
+            switchboard_feed: NULL_PUBKEY,
+        },
+        example::instruction::UpdateReserveConfig {
+            config: new_config,
+            fee_receiver: usdc.fee_receiver,
+        },
+    );
+    env.process(&[update]).await;
+    let reserve = Reserve::unpack(&env.data(usdc.reserve).await).unwrap();
+    assert_eq!(reserve.config.loan_to_value_ratio, 40);
+
+    let deposit = ix(
+        example::accounts::DepositReserveLiquidity {
+            solend_program: Solend::id(),
+            source_liquidity: usdc.user_liquidity,
+            destination_collateral: usdc.user_collateral,
+            reserve: usdc.reserve,
+            reserve_liquidity_supply: usdc.liquidity_supply,
+            reserve_collateral_mint: usdc.collateral_mint,
+            lending_market: env.market,
+            lending_market_authority: env.market_authority,
+            user_transfer_authority: env.user.pubkey(),
+            clock: sysvar::clock::id(),
+            token_program: spl_token::id(),
+        },
+        example::instruction::DepositReserveLiquidity {
+            liquidity_amount: 50 * ONE,
+        },
+    );
+    let redeem = ix(
+        example::accounts::RedeemReserveCollateral {
+            solend_program: Solend::id(),
+            source_collateral: usdc.user_collateral,
+            destination_liquidity: usdc.user_liquidity,
+            reserve: usdc.reserve,
+            reserve_collateral_mint: usdc.collateral_mint,
+            reserve_liquidity_supply: usdc.liquidity_supply,
+            lending_market: env.market,
+            lending_market_authority: env.market_authority,
+            user_transfer_authority: env.user.pubkey(),
+            clock: sysvar::clock::id(),
+            token_program: spl_token::id(),
+        },
+        example::instruction::RedeemReserveCollateral {
+            collateral_amount: 20 * ONE,
+        },
+    );
+    env.process(&[env.refresh_reserve(&usdc), deposit, redeem]).await;
+    assert_eq!(env.balance(usdc.user_liquidity).await, 870 * ONE);
+    assert_eq!(env.balance(usdc.user_collateral).await, 130 * ONE);
+    assert_eq!(env.balance(usdc.liquidity_supply).await, 130 * ONE);
+
+    let new_owner = Pubkey::new_unique();
+    let set_owner = ix(
+        example::accounts::SetLendingMarketOwner {
+            solend_program: Solend::id(),
+            lending_market: env.market,
+            lending_market_owner: env.user.pubkey(),
+            new_owner,
+        },
+        example::instruction::SetLendingMarketOwner {},
+    );
+    env.process(&[set_owner]).await;
+    let market = LendingMarket::unpack(&env.data(env.market).await).unwrap();
+    assert_eq!(market.owner, new_owner);
+}
+
+#[tokio::test]
+async fn obligation_lifecycle() {
+    let mut setup = Setup::new();
+    let sol = setup.add_reserve(10, 1_000 * ONE);
+    let usdc = setup.add_reserve(1, 10_000 * ONE);
+    let obligation = setup.add_obligation_account();
+    let mut env = setup.start().await;
+    env.init_reserve(&sol, 10 * ONE).await;
+    env.init_reserve(&usdc, 5_000 * ONE).await;
+    env.init_obligation(obligation).await;
+
+    // Mint collateral first, then move it into the obligation
+    let deposit = ix(
+        example::accounts::DepositReserveLiquidity {
+            solend_program: Solend::id(),
+            source_liquidity: sol.user_liquidity,
+            destination_collateral: sol.user_collateral,
+            reserve: sol.reserve,
+            reserve_liquidity_supply: sol.liquidity_supply,
+            reserve_collateral_mint: sol.collateral_mint,
+            lending_market: env.market,
+            lending_market_authority: env.market_authority,
+            user_transfer_authority: env.user.pubkey(),
+            clock: sysvar::clock::id(),
+            token_program: spl_token::id(),
+        },
+        example::instruction::DepositReserveLiquidity {
+            liquidity_amount: 100 * ONE,
+        },
+    );
+    let collateralize = ix(
+        example::accounts::DepositObligationCollateral {
+            solend_program: Solend::id(),
+            source_collateral: sol.user_collateral,
+            destination_collateral: sol.collateral_supply,
+            deposit_reserve: sol.reserve,
+            obligation,
+            lending_market: env.market,
+            obligation_owner: env.user.pubkey(),
+            user_transfer_authority: env.user.pubkey(),
+            clock: sysvar::clock::id(),
+            token_program: spl_token::id(),
+        },
+        example::instruction::DepositObligationCollateral {
+            collateral_amount: 100 * ONE,
+        },
+    );
+    env.process(&[env.refresh_reserve(&sol), deposit, collateralize]).await;
+    assert_eq!(env.obligation(obligation).await.deposits[0].deposited_amount, 100 * ONE);
+
+    env.deposit_and_borrow(obligation, &sol, 50 * ONE, &usdc, 300 * ONE).await;
+    let state = env.obligation(obligation).await;
+    assert_eq!(state.deposits[0].deposited_amount, 150 * ONE);
+    assert_eq!(state.borrows[0].borrowed_amount_wads, Decimal::from(300 * ONE));
+    assert_eq!(env.balance(usdc.user_liquidity).await, 5_300 * ONE);
+
+    let repay = ix(
+        example::accounts::RepayObligationLiquidity {
+            user: env.user.pubkey(),
+            repay: env.obligation_repay(&usdc, obligation, usdc.user_liquidity),
+        },
+        example::instruction::RepayObligationLiquidity {
+            liquidity_amount: u64::MAX,
+        },
+    );
+    env.process(&[env.refresh_reserve(&usdc), repay]).await;
+    assert!(env.obligation(obligation).await.borrows.is_empty());
+    assert_eq!(env.balance(usdc.user_liquidity).await, 5_000 * ONE);
+
+    let withdraw = ix(
+        example::accounts::WithdrawObligationCollateral {
+            user: env.user.pubkey(),
+            withdraw: env.obligation_withdraw(&sol, obligation, sol.user_collateral),
+        },
+        example::instruction::WithdrawObligationCollateral {
+            collateral_amount: 150 * ONE,
+        },
+    );
+    env.process(&[
+        env.refresh_reserve(&sol),
+        env.refresh_obligation(obligation, &[&sol]),
+        withdraw,
+    ])
+    .await;
+    assert!(env.obligation(obligation).await.deposits.is_empty());
+    assert_eq!(env.balance(sol.user_collateral).await, 160 * ONE);
+}
+
+#[tokio::test]
+async fn pda_owned_obligation() {
+    let mut setup = Setup::new();
+    let sol = setup.add_reserve(10, 1_000 * ONE);
+    let usdc = setup.add_reserve(1, 10_000 * ONE);
+    let user = setup.user.pubkey();
+    let (authority, _) = authority_address(&user);
+    let pda_sol = setup.add_token_account(sol.liquidity_mint, authority, 100 * ONE);
+    let pda_collateral = setup.add_token_account(sol.collateral_mint, authority, 0);
+    let pda_usdc = setup.add_token_account(usdc.liquidity_mint, authority, 0);
+    let mut env = setup.start().await;
+    env.init_reserve(&sol, 10 * ONE).await;
+    env.init_reserve(&usdc, 5_000 * ONE).await;
+
+    let obligation = obligation_address(&authority, &env.market);
+    let create = ix(
+        example::accounts::PdaCreateObligation {
+            solend_program: Solend::id(),
+            user,
+            authority,
+            obligation,
+            lending_market: env.market,
+            clock: sysvar::clock::id(),
+            rent: sysvar::rent::id(),
+            system_program: system_program::id(),
+            token_program: spl_token::id(),
+        },
+        example::instruction::PdaCreateObligation {},
+    );
+    env.process(&[create]).await;
+    assert_eq!(env.obligation(obligation).await.owner, authority);
+
+    let deposit = ix(
+        example::accounts::PdaDepositReserveLiquidityAndObligationCollateral {
+            user,
+            authority,
+            deposit: env.obligation_deposit(&sol, obligation, pda_sol, pda_collateral),
+        },
+        example::instruction::PdaDeposit {
+            liquidity_amount: 100 * ONE,
+        },
+    );
+    env.process(&[deposit]).await;
+    assert_eq!(env.obligation(obligation).await.deposits[0].deposited_amount, 100 * ONE);
+
+    let borrow = ix(
+        example::accounts::PdaBorrowObligationLiquidity {
+            user,
+            authority,
+            borrow: env.obligation_borrow(&usdc, obligation, pda_usdc),
+        },
+        example::instruction::PdaBorrow {
+            liquidity_amount: 200 * ONE,
+        },
+    );
+    env.process(&[
+        env.refresh_reserve(&sol),
+        env.refresh_reserve(&usdc),
+        env.refresh_obligation(obligation, &[&sol]),
+        borrow,
+    ])
+    .await;
+    assert_eq!(env.balance(pda_usdc).await, 200 * ONE);
+
+    let repay = ix(
+        example::accounts::PdaRepayObligationLiquidity {
+            user,
+            authority,
+            repay: env.obligation_repay(&usdc, obligation, pda_usdc),
+        },
+        example::instruction::PdaRepay {
+            liquidity_amount: u64::MAX,
+        },
+    );
+    env.process(&[env.refresh_reserve(&usdc), repay]).await;
+    assert_eq!(env.balance(pda_usdc).await, 0);
+
+    let withdraw = ix(
+        example::accounts::PdaWithdrawObligationCollateral {
+            user,
+            authority,
+            withdraw: env.obligation_withdraw(&sol, obligation, pda_collateral),
+        },
+        example::instruction::PdaWithdraw {
+            collateral_amount: 100 * ONE,
+        },
+    );
+    env.process(&[
+        env.refresh_reserve(&sol),
+        env.refresh_obligation(obligation, &[&sol]),
+        withdraw,
+    ])
+    .await;
+    assert_eq!(env.balance(pda_collateral).await, 100 * ONE);
+}
+
+#[tokio::test]
+async fn liquidation() {
+    let mut setup = Setup::new();
+    let sol = setup.add_reserve(10, 1_000 * ONE);
+    let usdc = setup.add_reserve(1, 10_000 * ONE);
+    let obligation = setup.add_obligation_account();
+    let mut env = setup.start().await;
+    env.init_reserve(&sol, 10 * ONE).await;
+    env.init_reserve(&usdc, 5_000 * ONE).await;
+    env.init_obligation(obligation).await;
+
+    // $1,000 of collateral against $400 of debt, until SOL halves
+    env.deposit_and_borrow(obligation, &sol, 100 * ONE, &usdc, 400 * ONE).await;
+    env.context.warp_to_slot(100).unwrap();
+    env.set_price(&sol, 5, 100);
+
+    let collateral_before = env.balance(sol.user_collateral).await;
+    let liquidity_before = env.balance(usdc.user_liquidity).await;
+    let liquidate = ix(
+        example::accounts::LiquidateObligation {
+            solend_program: Solend::id(),
+            source_liquidity: usdc.user_liquidity,
+            destination_collateral: sol.user_collateral,
+            repay_reserve: usdc.reserve,
+            repay_reserve_liquidity_supply: usdc.liquidity_supply,
+            withdraw_reserve: sol.reserve,
+            withdraw_reserve_collateral_supply: sol.collateral_supply,
+            obligation,
+            lending_market: env.market,
+            lending_market_authority: env.market_authority,
+            user_transfer_authority: env.user.pubkey(),
+            clock: sysvar::clock::id(),
+            token_program: spl_token::id(),
+        },
+        example::instruction::LiquidateObligation {
+            liquidity_amount: 100 * ONE,
+        },
+    );
+    env.process(&[
+        env.refresh_reserve(&sol),
+        env.refresh_reserve(&usdc),
+        env.refresh_obligation(obligation, &[&sol, &usdc]),
+        liquidate,
+    ])
+    .await;
+
+    let repaid = liquidity_before - env.balance(usdc.user_liquidity).await;
+    let seized = env.balance(sol.user_collateral).await - collateral_before;
+    assert!(repaid > 0 && repaid <= 100 * ONE);
+    assert!(seized > 0);
+    let state = env.obligation(obligation).await;
+    assert_eq!(state.deposits[0].deposited_amount, 100 * ONE - seized);
+    assert_eq!(state.borrows[0].borrowed_amount_wads, Decimal::from(400 * ONE - repaid));
+}
+
+#[tokio::test]
+async fn flash_loan() {
+    let mut setup = Setup::new();
+    let usdc = setup.add_reserve(1, 10_000 * ONE);
+    let mut env = setup.start().await;
+    env.init_reserve(&usdc, 5_000 * ONE).await;
+
+    let flash_loan = with_remaining(
+        ix(
+            example::accounts::FlashLoan {
+                solend_program: Solend::id(),
+                source_liquidity: usdc.liquidity_supply,
+                destination_liquidity: usdc.user_liquidity,
+                reserve: usdc.reserve,
+                reserve_liquidity_fee_receiver: usdc.fee_receiver,
+                host_fee_receiver: usdc.user_liquidity,
+                lending_market: env.market,
+                lending_market_authority: env.market_authority,
+                flash_loan_receiver_program: env.flash_loan_receiver,
+                token_program: spl_token::id(),
+            },
+            example::instruction::FlashLoan {
+                amount: 1_000 * ONE,
+            },
+        ),
+        vec![
+            AccountMeta::new(usdc.liquidity_supply, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(env.user.pubkey(), true),
+        ],
+    );
+    env.process(&[env.refresh_reserve(&usdc), flash_loan]).await;
+
+    let fee = 3 * ONE; // 0.3% of the 1,000 borrowed
+    assert_eq!(env.balance(usdc.user_liquidity).await, 5_000 * ONE - fee);
+    assert_eq!(env.balance(usdc.fee_receiver).await, fee);
+    assert_eq!(env.balance(usdc.liquidity_supply).await, 5_000 * ONE);
+}
@@ -0,0 +1,133 @@
+use std::{collections::HashMap, error::Error, fs, path::PathBuf, str::FromStr};
+
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::{program_error::ProgramError, program_pack::Pack},
+};
+use solana_client::rpc_client::RpcClient;
+use solend_token_lending::state::{LendingMarket, Obligation, Reserve};
+
+pub type SourceResult<T> = Result<T, Box<dyn Error>>;
+
+/// Where the off-chain tools read Solend accounts from
+pub trait AccountSource {
+    /// Every account owned by `program_id`, with its data
+    fn program_accounts(&self, program_id: &Pubkey) -> SourceResult<Vec<(Pubkey, Vec<u8>)>>;
+}
+
+pub struct RpcAccountSource {
+    client: RpcClient,
+}
+
+impl RpcAccountSource {
+    pub fn new(url: impl ToString) -> Self {
+        Self {
+            client: RpcClient::new(url.to_string()),
+        }
+    }
+
+    pub fn client(&self) -> &RpcClient {
+        &self.client
+    }
+}
+
+impl AccountSource for RpcAccountSource {
+    fn program_accounts(&self, program_id: &Pubkey) -> SourceResult<Vec<(Pubkey, Vec<u8>)>> {
+        Ok(self
+            .client
+            .get_program_accounts(program_id)?
+            .into_iter()
+            .map(|(pubkey, account)| (pubkey, account.data))
+            .collect())
+    }
+}
+
+/// Reads a saved `getProgramAccounts` response (base64 encoding), either the full
+/// JSON-RPC response or just its `result` array. The file is re-read on every call
+/// so a snapshot can be swapped while a tool is running.
+pub struct FileAccountSource {
+    path: PathBuf,
+}
+
+impl FileAccountSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl AccountSource for FileAccountSource {
+    fn program_accounts(&self, _program_id: &Pubkey) -> SourceResult<Vec<(Pubkey, Vec<u8>)>> {
+        let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(&self.path)?)?;
+        let entries = value
+            .get("result")
+            .unwrap_or(&value)
+            .as_array()
+            .ok_or("snapshot is not a getProgramAccounts result")?;
+
+        entries
+            .iter()
+            .map(|entry| -> SourceResult<(Pubkey, Vec<u8>)> {
+                let pubkey = entry["pubkey"].as_str().ok_or("account without pubkey")?;
+                Ok((
+                    Pubkey::from_str(pubkey)?,
+                    decode_account_data(&entry["account"]["data"])?,
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Decodes the `data` field of an RPC account, `["<base64>", "base64"]`
+pub fn decode_account_data(data: &serde_json::Value) -> SourceResult<Vec<u8>> {
+    let encoded = data
+        .get(0)
+        .and_then(|encoded| encoded.as_str())
+        .ok_or("account data is not base64 encoded")?;
+    Ok(base64::decode(encoded)?)
+}
+
+/// Decoded accounts of a single lending market
+#[derive(Default)]
+pub struct MarketAccounts {
+    pub lending_market: Option<LendingMarket>,
+    pub reserves: HashMap<Pubkey, Reserve>,
+    pub obligations: Vec<(Pubkey, Obligation)>,
+    /// Reserve and obligation sized accounts that failed to unpack
+    pub unpack_failures: Vec<(Pubkey, ProgramError)>,
+}
+
+impl MarketAccounts {
+    /// Loads every reserve and obligation of `lending_market`, telling account types
+    /// apart by their packed length. Accounts that fail to unpack are collected in
+    /// `unpack_failures`.
+    pub fn load(
+        source: &dyn AccountSource,
+        program_id: &Pubkey,
+        lending_market: &Pubkey,
+    ) -> SourceResult<Self> {
+        let mut market = MarketAccounts::default();
+        for (pubkey, data) in source.program_accounts(program_id)? {
+            match data.len() {
+                Reserve::LEN => match Reserve::unpack(&data) {
+                    Ok(reserve) if reserve.lending_market == *lending_market => {
+                        market.reserves.insert(pubkey, reserve);
+                    }
+                    Ok(_) => {}
+                    Err(err) => market.unpack_failures.push((pubkey, err)),
+                },
+                Obligation::LEN => match Obligation::unpack(&data) {
+                    Ok(obligation) if obligation.lending_market == *lending_market => {
+                        market.obligations.push((pubkey, obligation));
+                    }
+                    Ok(_) => {}
+                    Err(err) => market.unpack_failures.push((pubkey, err)),
+                },
+                LendingMarket::LEN if pubkey == *lending_market => {
+                    market.lending_market = LendingMarket::unpack(&data).ok();
+                }
+                _ => {}
+            }
+        }
+        Ok(market)
+    }
+}
@@ -0,0 +1,242 @@
+use anchor_lang::{
+    error,
+    prelude::{CpiContext, Pubkey},
+    solana_program::{entrypoint::ProgramResult, program_error::ProgramError, program_pack::Pack},
+    Key,
+};
+use solend_token_lending::state::{Obligation, Reserve};
+
+use crate::{
+    error::SolendAnchorError, redeem_reserve_collateral, repay_obligation_liquidity,
+    token::token_balance, withdraw_obligation_collateral, RedeemReserveCollateralAccounts,
+    RepayObligationLiquidityAccounts, WithdrawObligationCollateralAccounts,
+};
+
+/// An amount argument where `All` stands for Solend's `u64::MAX` sentinel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Amount {
+    Exact(u64),
+    All,
+}
+
+impl From<Amount> for u64 {
+    fn from(amount: Amount) -> u64 {
+        match amount {
+            Amount::Exact(amount) => amount,
+            Amount::All => u64::MAX,
+        }
+    }
+}
+
+impl From<u64> for Amount {
+    fn from(amount: u64) -> Amount {
+        match amount {
+            u64::MAX => Amount::All,
+            amount => Amount::Exact(amount),
+        }
+    }
+}
+
+fn has_debt(obligation: &Obligation, repay_reserve: &Pubkey) -> bool {
+    obligation
+        .borrows
+        .iter()
+        .any(|liquidity| liquidity.borrow_reserve == *repay_reserve)
+}
+
+/// Whether `Amount::All` can withdraw anything from `withdraw_reserve` as of the last
+/// refresh: the obligation holds collateral there and, with borrows, is still under
+/// its allowed borrow value
+fn has_withdrawable_collateral(obligation: &Obligation, withdraw_reserve: &Pubkey) -> bool {
+    let has_collateral = obligation.deposits.iter().any(|collateral| {
+        collateral.deposit_reserve == *withdraw_reserve && collateral.deposited_amount > 0
+    });
+    let at_borrow_limit = !obligation.borrows.is_empty()
+        && obligation.borrowed_value >= obligation.allowed_borrow_value;
+    has_collateral && !at_borrow_limit
+}
+
+/// Collateral `redeem` spends out of a source holding `balance`, zero when
+/// `Amount::All` would not redeem for any liquidity
+fn redeem_amount(reserve: &Reserve, balance: u64, amount: Amount) -> Result<u64, ProgramError> {
+    match amount {
+        Amount::Exact(collateral_amount) => Ok(collateral_amount),
+        Amount::All => {
+            if reserve
+                .collateral_exchange_rate()?
+                .collateral_to_liquidity(balance)?
+                == 0
+            {
+                return Ok(0);
+            }
+            Ok(balance)
+        }
+    }
+}
+
+/// `Amount::All` repays the whole debt, interest accrued this slot included, so no
+/// borrowed wads are left behind. It fails with `NothingToRepay` when the obligation
+/// has no debt in the reserve.
+pub fn repay<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, RepayObligationLiquidityAccounts<'info>>,
+    amount: Amount,
+) -> ProgramResult {
+    if amount == Amount::All {
+        let obligation = Obligation::unpack(&ctx.accounts.obligation_pubkey.try_borrow_data()?)?;
+        if !has_debt(&obligation, &ctx.accounts.repay_reserve_pubkey.key()) {
+            return Err(error!(SolendAnchorError::NothingToRepay).into());
+        }
+    }
+    repay_obligation_liquidity(ctx, amount.into())
+}
+
+pub fn repay_all<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, RepayObligationLiquidityAccounts<'info>>,
+) -> ProgramResult {
+    repay(ctx, Amount::All)
+}
+
+/// `Amount::All` withdraws every deposited collateral token when the obligation has
+/// no borrows, and as much as stays healthy otherwise. Returns the collateral
+/// withdrawn, and 0 without a CPI when `Amount::All` finds nothing withdrawable: no
+/// collateral in the reserve, or borrows already at the allowed borrow value as of
+/// the last refresh.
+pub fn withdraw<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, WithdrawObligationCollateralAccounts<'info>>,
+    amount: Amount,
+) -> Result<u64, ProgramError> {
+    if amount == Amount::All {
+        let obligation = Obligation::unpack(&ctx.accounts.obligation_pubkey.try_borrow_data()?)?;
+        if !has_withdrawable_collateral(&obligation, &ctx.accounts.withdraw_reserve_pubkey.key()) {
+            return Ok(0);
+        }
+    }
+    let destination = ctx.accounts.destination_collateral_pubkey.clone();
+    let balance_before = token_balance(&destination)?;
+    withdraw_obligation_collateral(ctx, amount.into())?;
+    Ok(token_balance(&destination)?.saturating_sub(balance_before))
+}
+
+pub fn withdraw_all<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, WithdrawObligationCollateralAccounts<'info>>,
+) -> Result<u64, ProgramError> {
+    withdraw(ctx, Amount::All)
+}
+
+/// Solend has no sentinel for redeeming, so `Amount::All` redeems the source
+/// collateral account's balance. Returns the collateral redeemed, and 0 without a
+/// CPI when the balance is too small to redeem for any liquidity.
+pub fn redeem<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, RedeemReserveCollateralAccounts<'info>>,
+    amount: Amount,
+) -> Result<u64, ProgramError> {
+    let collateral_amount = match amount {
+        Amount::Exact(collateral_amount) => collateral_amount,
+        Amount::All => {
+            let balance = token_balance(&ctx.accounts.source_collateral_pubkey)?;
+            let reserve = Reserve::unpack(&ctx.accounts.reserve_pubkey.try_borrow_data()?)?;
+            redeem_amount(&reserve, balance, amount)?
+        }
+    };
+    if collateral_amount == 0 {
+        return Ok(0);
+    }
+    redeem_reserve_collateral(ctx, collateral_amount)?;
+    Ok(collateral_amount)
+}
+
+pub fn redeem_all<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, RedeemReserveCollateralAccounts<'info>>,
+) -> Result<u64, ProgramError> {
+    redeem(ctx, Amount::All)
+}
+
+#[cfg(test)]
+mod tests {
+    use solend_token_lending::{
+        math::Decimal,
+        state::{ObligationCollateral, ObligationLiquidity},
+    };
+
+    use super::*;
+
+    #[test]
+    fn all_is_the_solend_sentinel() {
+        assert_eq!(u64::from(Amount::All), u64::MAX);
+        assert_eq!(Amount::from(u64::MAX), Amount::All);
+        assert_eq!(u64::from(Amount::Exact(42)), 42);
+        assert_eq!(Amount::from(42), Amount::Exact(42));
+    }
+
+    /// Obligation with `deposited` collateral in one reserve and, when `borrowed_value`
+    /// is set, a borrow in another against an allowed borrow value of 500
+    fn position(deposited: u64, borrowed_value: Option<u64>) -> (Pubkey, Pubkey, Obligation) {
+        let (deposit_reserve, borrow_reserve) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut collateral = ObligationCollateral::new(deposit_reserve);
+        collateral.deposited_amount = deposited;
+        let borrows = match borrowed_value {
+            Some(_) => vec![ObligationLiquidity::new(borrow_reserve)],
+            None => vec![],
+        };
+        let obligation = Obligation {
+            deposits: vec![collateral],
+            borrows,
+            borrowed_value: Decimal::from(borrowed_value.unwrap_or_default()),
+            allowed_borrow_value: Decimal::from(500u64),
+            ..Obligation::default()
+        };
+        (deposit_reserve, borrow_reserve, obligation)
+    }
+
+    #[test]
+    fn repay_all_needs_debt_in_the_reserve() {
+        let (deposit_reserve, borrow_reserve, obligation) = position(100, Some(400));
+        assert!(has_debt(&obligation, &borrow_reserve));
+        assert!(!has_debt(&obligation, &deposit_reserve));
+    }
+
+    #[test]
+    fn withdraw_all_takes_everything_without_borrows() {
+        let (deposit_reserve, _, obligation) = position(100, None);
+        assert!(has_withdrawable_collateral(&obligation, &deposit_reserve));
+    }
+
+    #[test]
+    fn withdraw_all_is_clamped_to_the_borrow_limit() {
+        let (deposit_reserve, _, obligation) = position(100, Some(499));
+        assert!(has_withdrawable_collateral(&obligation, &deposit_reserve));
+        let (deposit_reserve, _, obligation) = position(100, Some(500));
+        assert!(!has_withdrawable_collateral(&obligation, &deposit_reserve));
+    }
+
+    #[test]
+    fn withdraw_all_needs_collateral_in_the_reserve() {
+        let (deposit_reserve, borrow_reserve, obligation) = position(0, None);
+        assert!(!has_withdrawable_collateral(&obligation, &deposit_reserve));
+        assert!(!has_withdrawable_collateral(&obligation, &borrow_reserve));
+    }
+
+    /// Reserve where 1000 collateral tokens redeem for one liquidity token
+    fn reserve() -> Reserve {
+        let mut reserve = Reserve::default();
+        reserve.liquidity.available_amount = 1;
+        reserve.collateral.mint_total_supply = 1_000;
+        reserve
+    }
+
+    #[test]
+    fn redeem_exact_spends_the_given_amount() {
+        assert_eq!(redeem_amount(&reserve(), 5_000, Amount::Exact(1_500)), Ok(1_500));
+    }
+
+    #[test]
+    fn redeem_all_spends_the_balance() {
+        assert_eq!(redeem_amount(&reserve(), 5_000, Amount::All), Ok(5_000));
+    }
+
+    #[test]
+    fn redeem_all_skips_balances_worth_no_liquidity() {
+        assert_eq!(redeem_amount(&reserve(), 999, Amount::All), Ok(0));
+        assert_eq!(redeem_amount(&reserve(), 1_000, Amount::All), Ok(1_000));
+    }
+}
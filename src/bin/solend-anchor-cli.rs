@@ -0,0 +1,527 @@
+use std::{collections::HashMap, convert::TryFrom, env, fs, process, str::FromStr};
+
+use anchor_lang::solana_program::program_pack::Pack;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    transaction::Transaction,
+};
+use solend_anchor::{
+    account_source::{decode_account_data, FileAccountSource, MarketAccounts, SourceResult},
+    bootstrap::quote_currency,
+    builder::{refresh_obligation_instructions, refresh_reserve_instruction},
+};
+use solend_token_lending::{
+    instruction as solend_ix,
+    state::{LendingMarket, Obligation, Reserve, ReserveConfig, ReserveFees},
+};
+
+const USAGE: &str = "usage: solend-anchor-cli <command> [--flag value]...
+
+commands:
+  decode <reserve|obligation|market> <file>
+  init-market    --owner --market [--quote USD] [--oracle-program] [--switchboard-program]
+  init-reserve   --config <json> --amount --source-liquidity --destination-collateral --reserve
+                 --liquidity-mint --liquidity-supply --collateral-mint --collateral-supply
+                 --pyth-product --pyth-price --switchboard-feed --market --owner
+                 [--transfer-authority]
+  update-config  --config <json> --snapshot --reserve --market --owner --pyth-product
+                 --pyth-price --switchboard-feed
+  init-obligation --market --obligation --owner
+  refresh        --snapshot --market --payer (--reserve | --obligation)
+  deposit        --snapshot --market --reserve --obligation --owner --amount
+                 --source-liquidity --user-collateral [--transfer-authority]
+  withdraw       --snapshot --market --reserve --obligation --owner --amount
+                 --destination-collateral
+  redeem         --snapshot --market --reserve --amount --source-collateral
+                 --destination-liquidity --transfer-authority
+  borrow         --snapshot --market --reserve --obligation --owner --amount
+                 --destination-liquidity
+  repay          --snapshot --market --reserve --obligation --amount --source-liquidity
+                 --transfer-authority
+  liquidate      --snapshot --market --obligation --repay-reserve --withdraw-reserve --amount
+                 --source-liquidity --destination-collateral --transfer-authority
+  flash-loan     --snapshot --market --reserve --amount --destination-liquidity
+                 --receiver-program --payer [--receiver-accounts <a,b,...>]
+                 [--host-fee-receiver]
+
+init-reserve configs must set fee_receiver; update-config keeps the reserve's current one
+when it is left out.
+
+transaction commands also accept [--payer] [--program] [--encoding base64|base58]";
+
+struct Flags(HashMap<String, String>);
+
+impl Flags {
+    fn parse(args: &[String]) -> SourceResult<Self> {
+        let mut flags = HashMap::new();
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let name = flag
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument {}", flag))?;
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {}", flag))?;
+            flags.insert(name.to_string(), value.clone());
+        }
+        Ok(Flags(flags))
+    }
+
+    fn string(&self, name: &str) -> SourceResult<&str> {
+        Ok(self
+            .0
+            .get(name)
+            .ok_or_else(|| format!("--{} is required", name))?)
+    }
+
+    fn pubkey(&self, name: &str) -> SourceResult<Pubkey> {
+        Ok(Pubkey::from_str(self.string(name)?)?)
+    }
+
+    fn pubkey_or(&self, name: &str, default: Pubkey) -> SourceResult<Pubkey> {
+        match self.0.get(name) {
+            Some(value) => Ok(Pubkey::from_str(value)?),
+            None => Ok(default),
+        }
+    }
+
+    fn amount(&self) -> SourceResult<u64> {
+        Ok(self.string("amount")?.parse()?)
+    }
+}
+
+/// Reads an account dump: raw base64, a `getAccountInfo` response or its `value`
+fn read_account_dump(path: &str) -> SourceResult<Vec<u8>> {
+    let contents = fs::read_to_string(path)?;
+    match serde_json::from_str::<serde_json::Value>(&contents) {
+        Ok(value) => {
+            let value = value.get("result").unwrap_or(&value);
+            let value = value.get("value").unwrap_or(value);
+            decode_account_data(&value["data"])
+        }
+        Err(_) => Ok(base64::decode(contents.trim())?),
+    }
+}
+
+/// Reads a reserve config. `fee_receiver` must be a token account of the reserve's
+/// liquidity mint, so it is only defaulted when `current_fee_receiver` is known.
+fn read_reserve_config(
+    path: &str,
+    current_fee_receiver: Option<Pubkey>,
+) -> SourceResult<ReserveConfig> {
+    let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let u64_field = |value: &serde_json::Value, name: &str| -> SourceResult<u64> {
+        Ok(value[name]
+            .as_u64()
+            .ok_or_else(|| format!("config field {} is missing", name))?)
+    };
+    let u8_field = |value: &serde_json::Value, name: &str| -> SourceResult<u8> {
+        Ok(u8::try_from(u64_field(value, name)?)
+            .map_err(|_| format!("config field {} does not fit in a u8", name))?)
+    };
+    let fees = &value["fees"];
+    Ok(ReserveConfig {
+        optimal_utilization_rate: u8_field(&value, "optimal_utilization_rate")?,
+        loan_to_value_ratio: u8_field(&value, "loan_to_value_ratio")?,
+        liquidation_bonus: u8_field(&value, "liquidation_bonus")?,
+        liquidation_threshold: u8_field(&value, "liquidation_threshold")?,
+        min_borrow_rate: u8_field(&value, "min_borrow_rate")?,
+        optimal_borrow_rate: u8_field(&value, "optimal_borrow_rate")?,
+        max_borrow_rate: u8_field(&value, "max_borrow_rate")?,
+        fees: ReserveFees {
+            borrow_fee_wad: u64_field(fees, "borrow_fee_wad")?,
+            flash_loan_fee_wad: u64_field(fees, "flash_loan_fee_wad")?,
+            host_fee_percentage: u8_field(fees, "host_fee_percentage")?,
+        },
+        deposit_limit: u64_field(&value, "deposit_limit")?,
+        borrow_limit: u64_field(&value, "borrow_limit")?,
+        fee_receiver: match (value["fee_receiver"].as_str(), current_fee_receiver) {
+            (Some(fee_receiver), _) => Pubkey::from_str(fee_receiver)?,
+            (None, Some(current_fee_receiver)) => current_fee_receiver,
+            (None, None) => return Err("config field fee_receiver is missing".into()),
+        },
+    })
+}
+
+fn decode(args: &[String]) -> SourceResult<()> {
+    let (kind, path) = match args {
+        [kind, path] => (kind.as_str(), path.as_str()),
+        _ => return Err("decode takes an account type and a file".into()),
+    };
+    let data = read_account_dump(path)?;
+    match kind {
+        "reserve" => println!("{:#?}", Reserve::unpack(&data)?),
+        "obligation" => println!("{:#?}", Obligation::unpack(&data)?),
+        "market" => println!("{:#?}", LendingMarket::unpack(&data)?),
+        _ => return Err(format!("unknown account type {}", kind).into()),
+    }
+    Ok(())
+}
+
+fn load_market(flags: &Flags) -> SourceResult<MarketAccounts> {
+    MarketAccounts::load(
+        &FileAccountSource::new(flags.string("snapshot")?),
+        &flags.pubkey_or("program", solend_token_lending::id())?,
+        &flags.pubkey("market")?,
+    )
+}
+
+fn find_obligation(market: &MarketAccounts, pubkey: &Pubkey) -> SourceResult<Obligation> {
+    Ok(market
+        .obligations
+        .iter()
+        .find(|(key, _)| key == pubkey)
+        .map(|(_, obligation)| obligation.clone())
+        .ok_or_else(|| format!("obligation {} is not in the snapshot", pubkey))?)
+}
+
+/// Parses a comma separated list of pubkeys, empty when the flag is absent
+fn pubkey_list(flags: &Flags, name: &str) -> SourceResult<Vec<Pubkey>> {
+    match flags.0.get(name) {
+        Some(value) => value
+            .split(',')
+            .map(|pubkey| -> SourceResult<Pubkey> { Ok(Pubkey::from_str(pubkey.trim())?) })
+            .collect(),
+        None => Ok(vec![]),
+    }
+}
+
+fn find_reserve<'m>(market: &'m MarketAccounts, pubkey: &Pubkey) -> SourceResult<&'m Reserve> {
+    Ok(market
+        .reserves
+        .get(pubkey)
+        .ok_or_else(|| format!("reserve {} is not in the snapshot", pubkey))?)
+}
+
+/// Builds the instructions for a transaction command, returning them with the default payer
+fn build(command: &str, flags: &Flags) -> SourceResult<(Vec<Instruction>, Pubkey)> {
+    let program_id = flags.pubkey_or("program", solend_token_lending::id())?;
+    match command {
+        "init-market" => {
+            let owner = flags.pubkey("owner")?;
+            let quote = flags.0.get("quote").map(String::as_str).unwrap_or("USD");
+            let quote_currency = quote_currency(quote)?;
+            Ok((
+                vec![solend_ix::init_lending_market(
+                    program_id,
+                    owner,
+                    quote_currency,
+                    flags.pubkey("market")?,
+                    flags.pubkey_or(
+                        "oracle-program",
+                        Pubkey::from_str("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH")?,
+                    )?,
+                    flags.pubkey_or(
+                        "switchboard-program",
+                        Pubkey::from_str("DtmE9D2CSB4L5D6A15mraeEjrGMm6auWVzgaD8hK2tZM")?,
+                    )?,
+                )],
+                owner,
+            ))
+        }
+        "init-reserve" => {
+            let owner = flags.pubkey("owner")?;
+            let transfer_authority = flags.pubkey_or("transfer-authority", owner)?;
+            let config = read_reserve_config(flags.string("config")?, None)?;
+            Ok((
+                vec![solend_ix::init_reserve(
+                    program_id,
+                    flags.amount()?,
+                    config,
+                    flags.pubkey("source-liquidity")?,
+                    flags.pubkey("destination-collateral")?,
+                    flags.pubkey("reserve")?,
+                    flags.pubkey("liquidity-mint")?,
+                    flags.pubkey("liquidity-supply")?,
+                    flags.pubkey("collateral-mint")?,
+                    flags.pubkey("collateral-supply")?,
+                    flags.pubkey("pyth-product")?,
+                    flags.pubkey("pyth-price")?,
+                    flags.pubkey("switchboard-feed")?,
+                    flags.pubkey("market")?,
+                    owner,
+                    transfer_authority,
+                )],
+                owner,
+            ))
+        }
+        "update-config" => {
+            let market = load_market(flags)?;
+            let owner = flags.pubkey("owner")?;
+            let reserve_pubkey = flags.pubkey("reserve")?;
+            let reserve = find_reserve(&market, &reserve_pubkey)?;
+            let config =
+                read_reserve_config(flags.string("config")?, Some(reserve.config.fee_receiver))?;
+            Ok((
+                vec![solend_ix::update_reserve_config(
+                    program_id,
+                    config,
+                    reserve_pubkey,
+                    flags.pubkey("market")?,
+                    owner,
+                    flags.pubkey("pyth-product")?,
+                    flags.pubkey("pyth-price")?,
+                    flags.pubkey("switchboard-feed")?,
+                )],
+                owner,
+            ))
+        }
+        "init-obligation" => {
+            let owner = flags.pubkey("owner")?;
+            Ok((
+                vec![solend_ix::init_obligation(
+                    program_id,
+                    flags.pubkey("obligation")?,
+                    flags.pubkey("market")?,
+                    owner,
+                )],
+                owner,
+            ))
+        }
+        "refresh" => {
+            let market = load_market(flags)?;
+            let payer = flags.pubkey("payer")?;
+            let instructions = match (
+                flags.0.contains_key("reserve"),
+                flags.0.contains_key("obligation"),
+            ) {
+                (true, false) => {
+                    let reserve_pubkey = flags.pubkey("reserve")?;
+                    let reserve = find_reserve(&market, &reserve_pubkey)?;
+                    vec![refresh_reserve_instruction(program_id, reserve_pubkey, reserve)]
+                }
+                (false, true) => {
+                    let obligation_pubkey = flags.pubkey("obligation")?;
+                    let obligation = find_obligation(&market, &obligation_pubkey)?;
+                    refresh_obligation_instructions(
+                        program_id,
+                        obligation_pubkey,
+                        &obligation,
+                        &market.reserves,
+                    )?
+                }
+                _ => return Err("refresh takes one of --reserve or --obligation".into()),
+            };
+            Ok((instructions, payer))
+        }
+        "deposit" => {
+            let market = load_market(flags)?;
+            let owner = flags.pubkey("owner")?;
+            let reserve_pubkey = flags.pubkey("reserve")?;
+            let reserve = find_reserve(&market, &reserve_pubkey)?;
+            Ok((
+                vec![
+                    solend_ix::deposit_reserve_liquidity_and_obligation_collateral(
+                        program_id,
+                        flags.amount()?,
+                        flags.pubkey("source-liquidity")?,
+                        flags.pubkey("user-collateral")?,
+                        reserve_pubkey,
+                        reserve.liquidity.supply_pubkey,
+                        reserve.collateral.mint_pubkey,
+                        reserve.lending_market,
+                        reserve.collateral.supply_pubkey,
+                        flags.pubkey("obligation")?,
+                        owner,
+                        reserve.liquidity.pyth_oracle_pubkey,
+                        reserve.liquidity.switchboard_oracle_pubkey,
+                        flags.pubkey_or("transfer-authority", owner)?,
+                    ),
+                ],
+                owner,
+            ))
+        }
+        "withdraw" => {
+            let market = load_market(flags)?;
+            let owner = flags.pubkey("owner")?;
+            let reserve_pubkey = flags.pubkey("reserve")?;
+            let reserve = find_reserve(&market, &reserve_pubkey)?;
+            let obligation_pubkey = flags.pubkey("obligation")?;
+            let obligation = find_obligation(&market, &obligation_pubkey)?;
+
+            let mut instructions = refresh_obligation_instructions(
+                program_id,
+                obligation_pubkey,
+                &obligation,
+                &market.reserves,
+            )?;
+            instructions.push(solend_ix::withdraw_obligation_collateral(
+                program_id,
+                flags.amount()?,
+                reserve.collateral.supply_pubkey,
+                flags.pubkey("destination-collateral")?,
+                reserve_pubkey,
+                obligation_pubkey,
+                reserve.lending_market,
+                owner,
+            ));
+            Ok((instructions, owner))
+        }
+        "redeem" => {
+            let market = load_market(flags)?;
+            let transfer_authority = flags.pubkey("transfer-authority")?;
+            let reserve_pubkey = flags.pubkey("reserve")?;
+            let reserve = find_reserve(&market, &reserve_pubkey)?;
+            Ok((
+                vec![
+                    refresh_reserve_instruction(program_id, reserve_pubkey, reserve),
+                    solend_ix::redeem_reserve_collateral(
+                        program_id,
+                        flags.amount()?,
+                        flags.pubkey("source-collateral")?,
+                        flags.pubkey("destination-liquidity")?,
+                        reserve_pubkey,
+                        reserve.collateral.mint_pubkey,
+                        reserve.liquidity.supply_pubkey,
+                        reserve.lending_market,
+                        transfer_authority,
+                    ),
+                ],
+                transfer_authority,
+            ))
+        }
+        "borrow" => {
+            let market = load_market(flags)?;
+            let owner = flags.pubkey("owner")?;
+            let reserve_pubkey = flags.pubkey("reserve")?;
+            let reserve = find_reserve(&market, &reserve_pubkey)?;
+            let obligation_pubkey = flags.pubkey("obligation")?;
+            let obligation = find_obligation(&market, &obligation_pubkey)?;
+
+            let mut instructions = vec![];
+            if obligation
+                .borrows
+                .iter()
+                .all(|liquidity| liquidity.borrow_reserve != reserve_pubkey)
+            {
+                instructions.push(refresh_reserve_instruction(program_id, reserve_pubkey, reserve));
+            }
+            instructions.extend(refresh_obligation_instructions(
+                program_id,
+                obligation_pubkey,
+                &obligation,
+                &market.reserves,
+            )?);
+            instructions.push(solend_ix::borrow_obligation_liquidity(
+                program_id,
+                flags.amount()?,
+                reserve.liquidity.supply_pubkey,
+                flags.pubkey("destination-liquidity")?,
+                reserve_pubkey,
+                reserve.config.fee_receiver,
+                obligation_pubkey,
+                reserve.lending_market,
+                owner,
+                None,
+            ));
+            Ok((instructions, owner))
+        }
+        "repay" => {
+            let market = load_market(flags)?;
+            let transfer_authority = flags.pubkey("transfer-authority")?;
+            let reserve_pubkey = flags.pubkey("reserve")?;
+            let reserve = find_reserve(&market, &reserve_pubkey)?;
+            Ok((
+                vec![
+                    refresh_reserve_instruction(program_id, reserve_pubkey, reserve),
+                    solend_ix::repay_obligation_liquidity(
+                        program_id,
+                        flags.amount()?,
+                        flags.pubkey("source-liquidity")?,
+                        reserve.liquidity.supply_pubkey,
+                        reserve_pubkey,
+                        flags.pubkey("obligation")?,
+                        reserve.lending_market,
+                        transfer_authority,
+                    ),
+                ],
+                transfer_authority,
+            ))
+        }
+        "liquidate" => {
+            let market = load_market(flags)?;
+            let transfer_authority = flags.pubkey("transfer-authority")?;
+            let obligation_pubkey = flags.pubkey("obligation")?;
+            let obligation = find_obligation(&market, &obligation_pubkey)?;
+            let repay_reserve_pubkey = flags.pubkey("repay-reserve")?;
+            let repay_reserve = find_reserve(&market, &repay_reserve_pubkey)?;
+            let withdraw_reserve_pubkey = flags.pubkey("withdraw-reserve")?;
+            let withdraw_reserve = find_reserve(&market, &withdraw_reserve_pubkey)?;
+
+            let mut instructions = refresh_obligation_instructions(
+                program_id,
+                obligation_pubkey,
+                &obligation,
+                &market.reserves,
+            )?;
+            instructions.push(solend_ix::liquidate_obligation(
+                program_id,
+                flags.amount()?,
+                flags.pubkey("source-liquidity")?,
+                flags.pubkey("destination-collateral")?,
+                repay_reserve_pubkey,
+                repay_reserve.liquidity.supply_pubkey,
+                withdraw_reserve_pubkey,
+                withdraw_reserve.collateral.supply_pubkey,
+                obligation_pubkey,
+                obligation.lending_market,
+                transfer_authority,
+            ));
+            Ok((instructions, transfer_authority))
+        }
+        "flash-loan" => {
+            let market = load_market(flags)?;
+            let payer = flags.pubkey("payer")?;
+            let reserve_pubkey = flags.pubkey("reserve")?;
+            let reserve = find_reserve(&market, &reserve_pubkey)?;
+            let receiver_accounts = pubkey_list(flags, "receiver-accounts")?
+                .into_iter()
+                .map(|pubkey| AccountMeta::new(pubkey, false))
+                .collect();
+            Ok((
+                vec![solend_ix::flash_loan(
+                    program_id,
+                    flags.amount()?,
+                    reserve.liquidity.supply_pubkey,
+                    flags.pubkey("destination-liquidity")?,
+                    reserve_pubkey,
+                    reserve.config.fee_receiver,
+                    flags.pubkey_or("host-fee-receiver", reserve.config.fee_receiver)?,
+                    reserve.lending_market,
+                    flags.pubkey("receiver-program")?,
+                    receiver_accounts,
+                )],
+                payer,
+            ))
+        }
+        _ => Err(format!("unknown command {}", command).into()),
+    }
+}
+
+fn run(args: &[String]) -> SourceResult<()> {
+    let (command, rest) = args.split_first().ok_or("missing command")?;
+    if command == "decode" {
+        return decode(rest);
+    }
+
+    let flags = Flags::parse(rest)?;
+    let (instructions, default_payer) = build(command, &flags)?;
+    let payer = flags.pubkey_or("payer", default_payer)?;
+    let transaction = Transaction::new_with_payer(&instructions, Some(&payer));
+    let bytes = bincode::serialize(&transaction)?;
+    match flags.0.get("encoding").map(String::as_str).unwrap_or("base64") {
+        "base64" => println!("{}", base64::encode(bytes)),
+        "base58" => println!("{}", bs58::encode(bytes).into_string()),
+        encoding => return Err(format!("unknown encoding {}", encoding).into()),
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(err) = run(&args) {
+        eprintln!("error: {}\n\n{}", err, USAGE);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,175 @@
+use std::{env, error::Error, process, str::FromStr, thread, time::Duration};
+
+use solana_sdk::{
+    hash::Hash,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signer},
+    transaction::Transaction,
+};
+use solend_anchor::{
+    account_source::{AccountSource, FileAccountSource, MarketAccounts, RpcAccountSource},
+    liquidation::{liquidation_instructions, scan_obligations, LiquidatorAccounts},
+};
+use spl_associated_token_account::get_associated_token_address;
+
+const USAGE: &str = "usage: solend-liquidator --market <pubkey> (--rpc <url> | --snapshot <file>)
+    [--keypair <path>] [--program <pubkey>] [--interval <secs>] [--once] [--dry-run]";
+
+struct Args {
+    market: Pubkey,
+    program_id: Pubkey,
+    rpc: Option<String>,
+    snapshot: Option<String>,
+    keypair: Option<String>,
+    interval: u64,
+    once: bool,
+    dry_run: bool,
+}
+
+impl Args {
+    fn parse() -> Result<Self, Box<dyn Error>> {
+        let mut market = None;
+        let mut args = Args {
+            market: Pubkey::default(),
+            program_id: solend_token_lending::id(),
+            rpc: None,
+            snapshot: None,
+            keypair: None,
+            interval: 10,
+            once: false,
+            dry_run: false,
+        };
+
+        let mut argv = env::args().skip(1);
+        while let Some(arg) = argv.next() {
+            let mut value = || argv.next().ok_or(format!("missing value for {}", arg));
+            match arg.as_str() {
+                "--market" => market = Some(Pubkey::from_str(&value()?)?),
+                "--program" => args.program_id = Pubkey::from_str(&value()?)?,
+                "--rpc" => args.rpc = Some(value()?),
+                "--snapshot" => args.snapshot = Some(value()?),
+                "--keypair" => args.keypair = Some(value()?),
+                "--interval" => args.interval = value()?.parse()?,
+                "--once" => args.once = true,
+                "--dry-run" => args.dry_run = true,
+                _ => return Err(format!("unknown argument {}", arg).into()),
+            }
+        }
+
+        args.market = market.ok_or("--market is required")?;
+        if args.rpc.is_none() == args.snapshot.is_none() {
+            return Err("exactly one of --rpc or --snapshot is required".into());
+        }
+        if !args.dry_run && (args.rpc.is_none() || args.keypair.is_none()) {
+            return Err("sending transactions requires --rpc and --keypair".into());
+        }
+        Ok(args)
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let rpc = args.rpc.as_ref().map(RpcAccountSource::new);
+    let snapshot = args.snapshot.as_ref().map(FileAccountSource::new);
+    let source: &dyn AccountSource = match (&rpc, &snapshot) {
+        (Some(rpc), _) => rpc,
+        (_, Some(snapshot)) => snapshot,
+        _ => unreachable!(),
+    };
+    let payer = match &args.keypair {
+        Some(path) => read_keypair_file(path)?,
+        None => Keypair::new(),
+    };
+
+    loop {
+        let market = MarketAccounts::load(source, &args.program_id, &args.market)?;
+        for (pubkey, err) in &market.unpack_failures {
+            eprintln!("skipping account {}: {}", pubkey, err);
+        }
+        let scan = scan_obligations(&market.obligations, &market.reserves);
+        for (pubkey, err) in &scan.failures {
+            eprintln!("skipping obligation {}: {}", pubkey, err);
+        }
+        println!(
+            "{} obligations, {} liquidatable",
+            market.obligations.len(),
+            scan.opportunities.len()
+        );
+
+        for opportunity in &scan.opportunities {
+            let repay_reserve = &market.reserves[&opportunity.repay_reserve];
+            let withdraw_reserve = &market.reserves[&opportunity.withdraw_reserve];
+            let liquidator = LiquidatorAccounts {
+                transfer_authority: payer.pubkey(),
+                source_liquidity: get_associated_token_address(
+                    &payer.pubkey(),
+                    &repay_reserve.liquidity.mint_pubkey,
+                ),
+                destination_collateral: get_associated_token_address(
+                    &payer.pubkey(),
+                    &withdraw_reserve.collateral.mint_pubkey,
+                ),
+                destination_liquidity: get_associated_token_address(
+                    &payer.pubkey(),
+                    &withdraw_reserve.liquidity.mint_pubkey,
+                ),
+            };
+            let obligation = market
+                .obligations
+                .iter()
+                .find(|(pubkey, _)| *pubkey == opportunity.obligation)
+                .map(|(_, obligation)| obligation)
+                .ok_or("scanned obligation disappeared")?;
+            let instructions = liquidation_instructions(
+                args.program_id,
+                opportunity,
+                obligation,
+                &market.reserves,
+                &liquidator,
+            )?;
+
+            println!(
+                "obligation {} repay {} of {} for {} collateral of {}, profit {}",
+                opportunity.obligation,
+                opportunity.repay_amount,
+                opportunity.repay_reserve,
+                opportunity.withdraw_collateral_amount,
+                opportunity.withdraw_reserve,
+                opportunity.profit
+            );
+
+            let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+            match (&rpc, args.dry_run) {
+                (Some(rpc), false) => {
+                    transaction.sign(&[&payer], rpc.client().get_latest_blockhash()?);
+                    match rpc.client().send_and_confirm_transaction(&transaction) {
+                        Ok(signature) => println!("  sent {}", signature),
+                        Err(err) => println!("  failed: {}", err),
+                    }
+                }
+                _ => {
+                    transaction.message.recent_blockhash = Hash::default();
+                    println!("  {}", base64::encode(bincode::serialize(&transaction)?));
+                }
+            }
+        }
+
+        if args.once {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_secs(args.interval));
+    }
+}
+
+fn main() {
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(err) = run(args) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
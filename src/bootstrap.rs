@@ -0,0 +1,309 @@
+use anchor_lang::{prelude::Pubkey, solana_program::program_pack::Pack, Id};
+use solana_sdk::{
+    instruction::Instruction,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    system_instruction,
+};
+use solend_token_lending::{
+    instruction as solend_ix,
+    state::{LendingMarket, Reserve, ReserveConfig},
+};
+
+use crate::{account_source::SourceResult, Solend};
+
+/// Pads a quote currency symbol such as `USD` to the 32 bytes Solend stores
+pub fn quote_currency(symbol: &str) -> SourceResult<[u8; 32]> {
+    if symbol.len() > 32 {
+        return Err("quote currency is longer than 32 bytes".into());
+    }
+    let mut quote_currency = [0u8; 32];
+    quote_currency[..symbol.len()].copy_from_slice(symbol.as_bytes());
+    Ok(quote_currency)
+}
+
+/// One reserve of the market. `source_liquidity` is owned by the market owner and
+/// funds the `initial_liquidity` deposit `init_reserve` requires.
+#[derive(Clone, Debug)]
+pub struct AssetSpec {
+    pub liquidity_mint: Pubkey,
+    pub source_liquidity: Pubkey,
+    pub initial_liquidity: u64,
+    /// `fee_receiver` is replaced by the generated fee receiver account
+    pub config: ReserveConfig,
+    pub pyth_product: Pubkey,
+    pub pyth_price: Pubkey,
+    pub switchboard_feed: Pubkey,
+}
+
+/// Keypairs generated for a reserve's accounts
+pub struct ReserveKeypairs {
+    pub reserve: Keypair,
+    pub collateral_mint: Keypair,
+    pub liquidity_supply: Keypair,
+    pub collateral_supply: Keypair,
+    pub fee_receiver: Keypair,
+    /// Receives the collateral minted for the initial deposit, owned by the market owner
+    pub destination_collateral: Keypair,
+}
+
+impl ReserveKeypairs {
+    fn generate() -> Self {
+        Self {
+            reserve: Keypair::new(),
+            collateral_mint: Keypair::new(),
+            liquidity_supply: Keypair::new(),
+            collateral_supply: Keypair::new(),
+            fee_receiver: Keypair::new(),
+            destination_collateral: Keypair::new(),
+        }
+    }
+
+    pub fn signers(&self) -> Vec<&Keypair> {
+        vec![
+            &self.reserve,
+            &self.collateral_mint,
+            &self.liquidity_supply,
+            &self.collateral_supply,
+            &self.fee_receiver,
+            &self.destination_collateral,
+        ]
+    }
+}
+
+/// Instructions for one step of the bootstrap, each small enough for one transaction
+/// when signed by the payer, the market owner and `signers`
+pub struct BootstrapStep<'k> {
+    pub instructions: Vec<Instruction>,
+    pub signers: Vec<&'k Keypair>,
+}
+
+pub struct BootstrapOutput {
+    pub lending_market: Keypair,
+    pub reserves: Vec<ReserveKeypairs>,
+    market_instructions: Vec<Instruction>,
+    reserve_allocations: Vec<Vec<Instruction>>,
+    reserve_inits: Vec<Instruction>,
+}
+
+impl BootstrapOutput {
+    /// The market first, then two steps per asset in the order they were added: the
+    /// account allocations, then `init_reserve`. Together they exceed one transaction.
+    pub fn steps(&self) -> Vec<BootstrapStep<'_>> {
+        let mut steps = vec![BootstrapStep {
+            instructions: self.market_instructions.clone(),
+            signers: vec![&self.lending_market],
+        }];
+        for ((keypairs, allocations), init_reserve) in self
+            .reserves
+            .iter()
+            .zip(&self.reserve_allocations)
+            .zip(&self.reserve_inits)
+        {
+            steps.push(BootstrapStep {
+                instructions: allocations.clone(),
+                signers: keypairs.signers(),
+            });
+            steps.push(BootstrapStep {
+                instructions: vec![init_reserve.clone()],
+                signers: vec![],
+            });
+        }
+        steps
+    }
+
+    /// Every instruction in execution order
+    pub fn instructions(&self) -> Vec<Instruction> {
+        self.steps()
+            .into_iter()
+            .flat_map(|step| step.instructions)
+            .collect()
+    }
+}
+
+/// Builds everything needed to stand up a market and its reserves: the account
+/// allocations, `init_lending_market` and an `init_reserve` per asset
+pub struct MarketBootstrap {
+    program_id: Pubkey,
+    payer: Pubkey,
+    owner: Pubkey,
+    quote_currency: [u8; 32],
+    oracle_program_id: Pubkey,
+    switchboard_oracle_program_id: Pubkey,
+    assets: Vec<AssetSpec>,
+    rent: Rent,
+}
+
+impl MarketBootstrap {
+    pub fn new(
+        payer: Pubkey,
+        owner: Pubkey,
+        quote_currency: [u8; 32],
+        oracle_program_id: Pubkey,
+        switchboard_oracle_program_id: Pubkey,
+    ) -> Self {
+        Self {
+            program_id: Solend::id(),
+            payer,
+            owner,
+            quote_currency,
+            oracle_program_id,
+            switchboard_oracle_program_id,
+            assets: vec![],
+            rent: Rent::default(),
+        }
+    }
+
+    /// Targets a Solend deployment other than the default program id
+    pub fn program_id(mut self, program_id: Pubkey) -> Self {
+        self.program_id = program_id;
+        self
+    }
+
+    /// Rent used to fund the allocated accounts, `Rent::default()` unless set
+    pub fn rent(mut self, rent: Rent) -> Self {
+        self.rent = rent;
+        self
+    }
+
+    pub fn asset(mut self, asset: AssetSpec) -> Self {
+        self.assets.push(asset);
+        self
+    }
+
+    fn create_account(&self, pubkey: &Pubkey, space: usize, owner: &Pubkey) -> Instruction {
+        system_instruction::create_account(
+            &self.payer,
+            pubkey,
+            self.rent.minimum_balance(space),
+            space as u64,
+            owner,
+        )
+    }
+
+    /// Generates the keypairs and the ordered instructions. Nothing is sent.
+    pub fn build(&self) -> BootstrapOutput {
+        let lending_market = Keypair::new();
+        let market_instructions = vec![
+            self.create_account(
+                &lending_market.pubkey(),
+                LendingMarket::LEN,
+                &self.program_id,
+            ),
+            solend_ix::init_lending_market(
+                self.program_id,
+                self.owner,
+                self.quote_currency,
+                lending_market.pubkey(),
+                self.oracle_program_id,
+                self.switchboard_oracle_program_id,
+            ),
+        ];
+
+        let mut reserves = vec![];
+        let mut reserve_allocations = vec![];
+        let mut reserve_inits = vec![];
+        for asset in &self.assets {
+            let keypairs = ReserveKeypairs::generate();
+            let mut config = asset.config;
+            config.fee_receiver = keypairs.fee_receiver.pubkey();
+
+            // Solend initializes the token accounts and mint itself, so they are only
+            // allocated here and handed over to the token program
+            let mut allocations = vec![self.create_account(
+                &keypairs.reserve.pubkey(),
+                Reserve::LEN,
+                &self.program_id,
+            )];
+            allocations.push(self.create_account(
+                &keypairs.collateral_mint.pubkey(),
+                spl_token::state::Mint::LEN,
+                &spl_token::id(),
+            ));
+            for token_account in [
+                &keypairs.liquidity_supply,
+                &keypairs.collateral_supply,
+                &keypairs.fee_receiver,
+                &keypairs.destination_collateral,
+            ] {
+                allocations.push(self.create_account(
+                    &token_account.pubkey(),
+                    spl_token::state::Account::LEN,
+                    &spl_token::id(),
+                ));
+            }
+            reserve_inits.push(solend_ix::init_reserve(
+                self.program_id,
+                asset.initial_liquidity,
+                config,
+                asset.source_liquidity,
+                keypairs.destination_collateral.pubkey(),
+                keypairs.reserve.pubkey(),
+                asset.liquidity_mint,
+                keypairs.liquidity_supply.pubkey(),
+                keypairs.collateral_mint.pubkey(),
+                keypairs.collateral_supply.pubkey(),
+                asset.pyth_product,
+                asset.pyth_price,
+                asset.switchboard_feed,
+                lending_market.pubkey(),
+                self.owner,
+                self.owner,
+            ));
+
+            reserves.push(keypairs);
+            reserve_allocations.push(allocations);
+        }
+
+        BootstrapOutput {
+            lending_market,
+            reserves,
+            market_instructions,
+            reserve_allocations,
+            reserve_inits,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{message::Message, packet::PACKET_DATA_SIZE, transaction::Transaction};
+
+    use super::*;
+
+    fn asset() -> AssetSpec {
+        AssetSpec {
+            liquidity_mint: Pubkey::new_unique(),
+            source_liquidity: Pubkey::new_unique(),
+            initial_liquidity: 1_000_000,
+            config: ReserveConfig::default(),
+            pyth_product: Pubkey::new_unique(),
+            pyth_price: Pubkey::new_unique(),
+            switchboard_feed: Pubkey::new_unique(),
+        }
+    }
+
+    #[test]
+    fn every_step_fits_in_one_transaction() {
+        let payer = Pubkey::new_unique();
+        let output = MarketBootstrap::new(
+            payer,
+            Pubkey::new_unique(),
+            quote_currency("USD").unwrap(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        )
+        .asset(asset())
+        .asset(asset())
+        .build();
+
+        let steps = output.steps();
+        assert_eq!(steps.len(), 5);
+        for step in steps {
+            let transaction =
+                Transaction::new_unsigned(Message::new(&step.instructions, Some(&payer)));
+            let size = bincode::serialized_size(&transaction).unwrap() as usize;
+            assert!(size <= PACKET_DATA_SIZE, "{} byte step", size);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+
+use anchor_lang::{
+    error,
+    prelude::{Pubkey, Rent},
+    solana_program::{
+        instruction::Instruction, program_error::ProgramError, program_pack::Pack,
+        system_instruction,
+    },
+};
+use solend_token_lending::state::{Obligation, Reserve};
+
+use crate::error::SolendAnchorError;
+
+/// `refresh_reserve` for every reserve of the obligation followed by `refresh_obligation`,
+/// the prefix Solend requires before borrowing, withdrawing or liquidating.
+pub fn refresh_obligation_instructions(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    obligation: &Obligation,
+    reserves: &HashMap<Pubkey, Reserve>,
+) -> Result<Vec<Instruction>, ProgramError> {
+    let reserve_pubkeys: Vec<Pubkey> = obligation
+        .deposits
+        .iter()
+        .map(|collateral| collateral.deposit_reserve)
+        .chain(obligation.borrows.iter().map(|liquidity| liquidity.borrow_reserve))
+        .collect();
+
+    let mut instructions = vec![];
+    let mut refreshed = vec![];
+    for reserve_pubkey in &reserve_pubkeys {
+        if refreshed.contains(reserve_pubkey) {
+            continue;
+        }
+        let reserve = reserves
+            .get(reserve_pubkey)
+            .ok_or_else(|| ProgramError::from(error!(SolendAnchorError::MissingReserve)))?;
+        instructions.push(refresh_reserve_instruction(program_id, *reserve_pubkey, reserve));
+        refreshed.push(*reserve_pubkey);
+    }
+
+    instructions.push(solend_token_lending::instruction::refresh_obligation(
+        program_id,
+        obligation_pubkey,
+        reserve_pubkeys,
+    ));
+    Ok(instructions)
+}
+
+pub fn refresh_reserve_instruction(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve: &Reserve,
+) -> Instruction {
+    solend_token_lending::instruction::refresh_reserve(
+        program_id,
+        reserve_pubkey,
+        reserve.liquidity.pyth_oracle_pubkey,
+        reserve.liquidity.switchboard_oracle_pubkey,
+    )
+}
+
+/// Creates `wsol_account` owned by `owner` holding `lamports` of wrapped SOL, funded by `payer`
+pub fn wrap_sol_instructions(
+    payer: Pubkey,
+    wsol_account: Pubkey,
+    owner: Pubkey,
+    lamports: u64,
+    rent: &Rent,
+) -> Result<Vec<Instruction>, ProgramError> {
+    let space = spl_token::state::Account::LEN;
+    Ok(vec![
+        system_instruction::create_account(
+            &payer,
+            &wsol_account,
+            rent.minimum_balance(space) + lamports,
+            space as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            &wsol_account,
+            &spl_token::native_mint::id(),
+            &owner,
+        )?,
+    ])
+}
+
+/// Closes `wsol_account`, sending its lamports to `owner`
+pub fn unwrap_sol_instruction(
+    wsol_account: Pubkey,
+    owner: Pubkey,
+) -> Result<Instruction, ProgramError> {
+    spl_token::instruction::close_account(&spl_token::id(), &wsol_account, &owner, &owner, &[])
+}
+
+/// Surrounds `instructions` with the creation of a temporary wrapped SOL account and its
+/// closing, for Solend instructions that spend or receive SOL through `wsol_account`.
+/// `wsol_account` must sign the transaction.
+pub fn with_wrapped_sol(
+    payer: Pubkey,
+    wsol_account: Pubkey,
+    owner: Pubkey,
+    lamports: u64,
+    rent: &Rent,
+    instructions: Vec<Instruction>,
+) -> Result<Vec<Instruction>, ProgramError> {
+    let mut wrapped = wrap_sol_instructions(payer, wsol_account, owner, lamports, rent)?;
+    wrapped.extend(instructions);
+    wrapped.push(unwrap_sol_instruction(wsol_account, owner)?);
+    Ok(wrapped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAMPORTS: u64 = 1_000_000_000;
+
+    fn wrapped(instructions: Vec<Instruction>) -> (Pubkey, Pubkey, Pubkey, Vec<Instruction>) {
+        let (payer, wsol_account, owner) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let wrapped = with_wrapped_sol(
+            payer,
+            wsol_account,
+            owner,
+            LAMPORTS,
+            &Rent::default(),
+            instructions,
+        )
+        .unwrap();
+        (payer, wsol_account, owner, wrapped)
+    }
+
+    #[test]
+    fn wrap_sol_funds_rent_and_the_amount() {
+        let (payer, wsol_account, owner) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let rent = Rent::default();
+        let space = spl_token::state::Account::LEN;
+        assert_eq!(
+            wrap_sol_instructions(payer, wsol_account, owner, LAMPORTS, &rent),
+            Ok(vec![
+                system_instruction::create_account(
+                    &payer,
+                    &wsol_account,
+                    rent.minimum_balance(space) + LAMPORTS,
+                    space as u64,
+                    &spl_token::id(),
+                ),
+                spl_token::instruction::initialize_account(
+                    &spl_token::id(),
+                    &wsol_account,
+                    &spl_token::native_mint::id(),
+                    &owner,
+                )
+                .unwrap(),
+            ])
+        );
+    }
+
+    #[test]
+    fn wrapped_instructions_run_between_wrapping_and_closing() {
+        let inner = vec![
+            system_instruction::transfer(&Pubkey::new_unique(), &Pubkey::new_unique(), 1),
+            system_instruction::transfer(&Pubkey::new_unique(), &Pubkey::new_unique(), 2),
+        ];
+        let (payer, wsol_account, owner, wrapped) = wrapped(inner.clone());
+
+        assert_eq!(wrapped.len(), 5);
+        assert_eq!(
+            wrapped[..2],
+            wrap_sol_instructions(payer, wsol_account, owner, LAMPORTS, &Rent::default())
+                .unwrap()[..]
+        );
+        assert_eq!(wrapped[2..4], inner[..]);
+        assert_eq!(wrapped[4], unwrap_sol_instruction(wsol_account, owner).unwrap());
+    }
+
+    #[test]
+    fn closing_returns_the_lamports_to_the_owner() {
+        let (_, wsol_account, owner, wrapped) = wrapped(vec![]);
+        let close = wrapped.last().unwrap();
+        assert_eq!(close.program_id, spl_token::id());
+        // close_account takes the account, the destination and the authority
+        let keys: Vec<Pubkey> = close.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(keys, vec![wsol_account, owner, owner]);
+    }
+}
@@ -0,0 +1,469 @@
+//! Repays debt with an obligation's own collateral: withdraw, redeem, swap into the
+//! debt asset and repay. There is no flash loan funded variant, for the reentrancy
+//! reason given in [`crate::leverage`], so the withdrawal has to keep the obligation
+//! healthy on its own. `plan_collateral_repay` sizes it; each round frees room for the
+//! next, but a position with no room left under its borrow limit has to be topped up
+//! or partly repaid from elsewhere first.
+
+use anchor_lang::{
+    error,
+    prelude::{AccountInfo, Clock, CpiContext, Program, Pubkey, Sysvar},
+    solana_program::{entrypoint::ProgramResult, program_error::ProgramError, program_pack::Pack},
+    Accounts, ToAccountInfo,
+};
+use solend_token_lending::{
+    math::{Decimal, TryDiv, TryMul, TrySub},
+    state::{Obligation, Reserve},
+};
+
+use crate::{
+    error::SolendAnchorError, liquidation::liquidity_market_value, redeem_reserve_collateral,
+    refresh_obligation, refresh_reserve, repay_obligation_liquidity, token::token_balance,
+    withdraw_obligation_collateral, RedeemReserveCollateralAccounts,
+    RefreshObligationAccounts, RefreshReserveAccounts, RepayObligationLiquidityAccounts,
+    TokenProgram, WithdrawObligationCollateralAccounts,
+};
+
+/// Swaps the redeemed collateral liquidity into the debt liquidity. Implementors hold
+/// their own DEX accounts and must leave the output in `user_debt_liquidity_pubkey`.
+pub trait SwapCallback {
+    fn swap(&mut self, amount_in: u64, min_amount_out: u64) -> ProgramResult;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CollateralRepayParams {
+    /// Collateral tokens withdrawn from the obligation to fund the swap
+    pub withdraw_collateral_amount: u64,
+    /// Tolerated shortfall of the swap output against oracle prices, in basis points
+    pub max_slippage_bps: u16,
+}
+
+#[derive(Accounts)]
+pub struct CollateralRepayAccounts<'info> {
+    /// Receives the swap output, which is then repaid to the obligation
+    pub user_debt_liquidity_pubkey: AccountInfo<'info>,
+    pub user_collateral_pubkey: AccountInfo<'info>,
+    /// Receives the redeemed collateral liquidity, the swap input
+    pub user_withdraw_liquidity_pubkey: AccountInfo<'info>,
+    pub repay_reserve_pubkey: AccountInfo<'info>,
+    pub repay_reserve_liquidity_supply_pubkey: AccountInfo<'info>,
+    pub repay_reserve_pyth_oracle_pubkey: AccountInfo<'info>,
+    pub repay_reserve_switchboard_oracle_pubkey: AccountInfo<'info>,
+    pub withdraw_reserve_pubkey: AccountInfo<'info>,
+    pub withdraw_reserve_collateral_supply_pubkey: AccountInfo<'info>,
+    pub withdraw_reserve_collateral_mint_pubkey: AccountInfo<'info>,
+    pub withdraw_reserve_liquidity_supply_pubkey: AccountInfo<'info>,
+    pub withdraw_reserve_pyth_oracle_pubkey: AccountInfo<'info>,
+    pub withdraw_reserve_switchboard_oracle_pubkey: AccountInfo<'info>,
+    pub lending_market_pubkey: AccountInfo<'info>,
+    pub lending_market_authority_pubkey: AccountInfo<'info>,
+    pub obligation_pubkey: AccountInfo<'info>,
+    pub obligation_owner_pubkey: AccountInfo<'info>,
+    pub user_transfer_authority_pubkey: AccountInfo<'info>,
+    pub clock_sysvar: Sysvar<'info, Clock>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+impl<'info> CollateralRepayAccounts<'info> {
+    fn refresh_repay_reserve_accounts(&self) -> RefreshReserveAccounts<'info> {
+        RefreshReserveAccounts {
+            reserve_pubkey: self.repay_reserve_pubkey.clone(),
+            reserve_liquidity_pyth_oracle_pubkey: self.repay_reserve_pyth_oracle_pubkey.clone(),
+            reserve_liquidity_switchboard_oracle_pubkey: self
+                .repay_reserve_switchboard_oracle_pubkey
+                .clone(),
+            clock_sysvar: self.clock_sysvar.clone(),
+        }
+    }
+
+    fn refresh_withdraw_reserve_accounts(&self) -> RefreshReserveAccounts<'info> {
+        RefreshReserveAccounts {
+            reserve_pubkey: self.withdraw_reserve_pubkey.clone(),
+            reserve_liquidity_pyth_oracle_pubkey: self.withdraw_reserve_pyth_oracle_pubkey.clone(),
+            reserve_liquidity_switchboard_oracle_pubkey: self
+                .withdraw_reserve_switchboard_oracle_pubkey
+                .clone(),
+            clock_sysvar: self.clock_sysvar.clone(),
+        }
+    }
+
+    fn refresh_obligation_accounts(&self) -> RefreshObligationAccounts<'info> {
+        // refresh_obligation expects the clock sysvar right after the obligation
+        RefreshObligationAccounts {
+            obligation_pubkey: self.obligation_pubkey.clone(),
+            reserve_pubkeys: self.clock_sysvar.to_account_info(),
+        }
+    }
+
+    fn repay_accounts(&self) -> RepayObligationLiquidityAccounts<'info> {
+        RepayObligationLiquidityAccounts {
+            source_liquidity_pubkey: self.user_debt_liquidity_pubkey.clone(),
+            destination_liquidity_pubkey: self.repay_reserve_liquidity_supply_pubkey.clone(),
+            repay_reserve_pubkey: self.repay_reserve_pubkey.clone(),
+            obligation_pubkey: self.obligation_pubkey.clone(),
+            lending_market_pubkey: self.lending_market_pubkey.clone(),
+            user_transfer_authority_pubkey: self.user_transfer_authority_pubkey.clone(),
+            clock_sysvar: self.clock_sysvar.clone(),
+            token_program: self.token_program.clone(),
+        }
+    }
+
+    fn withdraw_accounts(&self) -> WithdrawObligationCollateralAccounts<'info> {
+        WithdrawObligationCollateralAccounts {
+            source_collateral_pubkey: self.withdraw_reserve_collateral_supply_pubkey.clone(),
+            destination_collateral_pubkey: self.user_collateral_pubkey.clone(),
+            withdraw_reserve_pubkey: self.withdraw_reserve_pubkey.clone(),
+            obligation_pubkey: self.obligation_pubkey.clone(),
+            lending_market_pubkey: self.lending_market_pubkey.clone(),
+            lending_market_authority_pubkey: self.lending_market_authority_pubkey.clone(),
+            obligation_owner_pubkey: self.obligation_owner_pubkey.clone(),
+            clock_sysvar: self.clock_sysvar.clone(),
+            token_program: self.token_program.clone(),
+        }
+    }
+
+    fn redeem_accounts(&self) -> RedeemReserveCollateralAccounts<'info> {
+        RedeemReserveCollateralAccounts {
+            source_collateral_pubkey: self.user_collateral_pubkey.clone(),
+            destination_liquidity_pubkey: self.user_withdraw_liquidity_pubkey.clone(),
+            reserve_pubkey: self.withdraw_reserve_pubkey.clone(),
+            reserve_collateral_mint_pubkey: self.withdraw_reserve_collateral_mint_pubkey.clone(),
+            reserve_liquidity_supply_pubkey: self.withdraw_reserve_liquidity_supply_pubkey.clone(),
+            lending_market_pubkey: self.lending_market_pubkey.clone(),
+            lending_market_authority_pubkey: self.lending_market_authority_pubkey.clone(),
+            user_transfer_authority_pubkey: self.user_transfer_authority_pubkey.clone(),
+            clock_sysvar: self.clock_sysvar.clone(),
+            token_program: self.token_program.clone(),
+        }
+    }
+}
+
+/// Minimum debt liquidity the swap must return for `amount_in` withdraw liquidity,
+/// at the reserves' refreshed oracle prices less `max_slippage_bps`
+pub fn min_swap_output(
+    withdraw_reserve: &Reserve,
+    repay_reserve: &Reserve,
+    amount_in: u64,
+    max_slippage_bps: u16,
+) -> Result<u64, ProgramError> {
+    let value_in = liquidity_market_value(withdraw_reserve, amount_in.into())?;
+    let decimals = 10u64
+        .checked_pow(repay_reserve.liquidity.mint_decimals as u32)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    let expected_out = value_in
+        .try_mul(decimals)?
+        .try_div(repay_reserve.liquidity.market_price)?;
+    let tolerance = 10_000u64.saturating_sub(max_slippage_bps as u64);
+    expected_out
+        .try_mul(tolerance)?
+        .try_div(Decimal::from(10_000u64))?
+        .try_floor_u64()
+}
+
+/// One round of `repay_with_collateral`, sized from a refreshed obligation
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CollateralRepayPlan {
+    /// Collateral tokens to withdraw
+    pub withdraw_collateral_amount: u64,
+    /// Liquidity the withdrawn collateral redeems for, the swap input
+    pub swap_amount_in: u64,
+    /// Least swap output `repay_with_collateral` accepts for `swap_amount_in`
+    pub min_swap_output: u64,
+}
+
+/// Plans the withdrawal for one round: as much collateral as the obligation can give up
+/// while staying under its allowed borrow value, but no more than repaying the whole
+/// debt in the repay reserve needs after `max_slippage_bps`. `obligation` and both
+/// reserves must be refreshed.
+pub fn plan_collateral_repay(
+    obligation: &Obligation,
+    withdraw_reserve_pubkey: &Pubkey,
+    withdraw_reserve: &Reserve,
+    repay_reserve_pubkey: &Pubkey,
+    repay_reserve: &Reserve,
+    max_slippage_bps: u16,
+) -> Result<CollateralRepayPlan, ProgramError> {
+    let collateral = obligation
+        .deposits
+        .iter()
+        .find(|collateral| collateral.deposit_reserve == *withdraw_reserve_pubkey)
+        .ok_or_else(|| ProgramError::from(error!(SolendAnchorError::ReserveNotInObligation)))?;
+    let liquidity = obligation
+        .borrows
+        .iter()
+        .find(|liquidity| liquidity.borrow_reserve == *repay_reserve_pubkey)
+        .ok_or_else(|| ProgramError::from(error!(SolendAnchorError::ReserveNotInObligation)))?;
+    if collateral.market_value == Decimal::zero() {
+        return Err(error!(SolendAnchorError::NothingWithdrawable).into());
+    }
+
+    // Collateral value that can leave before borrows reach the allowed borrow value
+    let withdraw_ltv = Decimal::from_percent(withdraw_reserve.config.loan_to_value_ratio);
+    let healthy_value = if withdraw_ltv == Decimal::zero() {
+        collateral.market_value
+    } else {
+        obligation
+            .allowed_borrow_value
+            .try_sub(obligation.borrowed_value)
+            .unwrap_or_else(|_| Decimal::zero())
+            .try_div(withdraw_ltv)?
+    };
+    let tolerance = 10_000u64.saturating_sub(max_slippage_bps as u64);
+    let needed_value = if tolerance == 0 {
+        collateral.market_value
+    } else {
+        liquidity
+            .market_value
+            .try_mul(10_000u64)?
+            .try_div(tolerance)?
+    };
+    let withdraw_value = healthy_value.min(needed_value).min(collateral.market_value);
+
+    let withdraw_collateral_amount = withdraw_value
+        .try_div(collateral.market_value)?
+        .try_mul(collateral.deposited_amount)?
+        .try_floor_u64()?;
+    if withdraw_collateral_amount == 0 {
+        return Err(error!(SolendAnchorError::NothingWithdrawable).into());
+    }
+    let swap_amount_in = withdraw_reserve
+        .collateral_exchange_rate()?
+        .collateral_to_liquidity(withdraw_collateral_amount)?;
+    Ok(CollateralRepayPlan {
+        withdraw_collateral_amount,
+        swap_amount_in,
+        min_swap_output: min_swap_output(
+            withdraw_reserve,
+            repay_reserve,
+            swap_amount_in,
+            max_slippage_bps,
+        )?,
+    })
+}
+
+/// Withdraws and redeems `withdraw_collateral_amount` of collateral, as sized by
+/// `plan_collateral_repay`, swaps it through `swap` and repays the obligation with the
+/// output. Fails if the swap output is below
+/// the oracle price less `max_slippage_bps`. Returns the amount repaid; any output above
+/// the outstanding debt stays in `user_debt_liquidity_pubkey`.
+/// `remaining_accounts` are the obligation's deposit and borrow reserves, in order,
+/// as expected by `refresh_obligation`; every one of them must be refreshed this slot.
+pub fn repay_with_collateral<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, CollateralRepayAccounts<'info>>,
+    params: &CollateralRepayParams,
+    swap: &mut impl SwapCallback,
+) -> Result<u64, ProgramError> {
+    refresh_reserve(CpiContext::new_with_signer(
+        ctx.program.clone(),
+        ctx.accounts.refresh_withdraw_reserve_accounts(),
+        ctx.signer_seeds,
+    ))?;
+    refresh_reserve(CpiContext::new_with_signer(
+        ctx.program.clone(),
+        ctx.accounts.refresh_repay_reserve_accounts(),
+        ctx.signer_seeds,
+    ))?;
+    refresh_obligation(
+        CpiContext::new_with_signer(
+            ctx.program.clone(),
+            ctx.accounts.refresh_obligation_accounts(),
+            ctx.signer_seeds,
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.clone()),
+    )?;
+    withdraw_obligation_collateral(
+        CpiContext::new_with_signer(
+            ctx.program.clone(),
+            ctx.accounts.withdraw_accounts(),
+            ctx.signer_seeds,
+        ),
+        params.withdraw_collateral_amount,
+    )?;
+
+    let withdraw_liquidity_before = token_balance(&ctx.accounts.user_withdraw_liquidity_pubkey)?;
+    redeem_reserve_collateral(
+        CpiContext::new_with_signer(
+            ctx.program.clone(),
+            ctx.accounts.redeem_accounts(),
+            ctx.signer_seeds,
+        ),
+        params.withdraw_collateral_amount,
+    )?;
+    let amount_in = token_balance(&ctx.accounts.user_withdraw_liquidity_pubkey)?
+        .saturating_sub(withdraw_liquidity_before);
+
+    let min_amount_out = {
+        let withdraw_reserve =
+            Reserve::unpack(&ctx.accounts.withdraw_reserve_pubkey.try_borrow_data()?)?;
+        let repay_reserve =
+            Reserve::unpack(&ctx.accounts.repay_reserve_pubkey.try_borrow_data()?)?;
+        min_swap_output(
+            &withdraw_reserve,
+            &repay_reserve,
+            amount_in,
+            params.max_slippage_bps,
+        )?
+    };
+
+    let debt_liquidity_before = token_balance(&ctx.accounts.user_debt_liquidity_pubkey)?;
+    swap.swap(amount_in, min_amount_out)?;
+    let amount_out = token_balance(&ctx.accounts.user_debt_liquidity_pubkey)?
+        .saturating_sub(debt_liquidity_before);
+    if amount_out < min_amount_out {
+        return Err(error!(SolendAnchorError::SlippageExceeded).into());
+    }
+
+    // Redeeming marks the withdraw reserve stale, which is also the repay reserve when
+    // the debt and collateral share a mint
+    refresh_reserve(CpiContext::new_with_signer(
+        ctx.program.clone(),
+        ctx.accounts.refresh_repay_reserve_accounts(),
+        ctx.signer_seeds,
+    ))?;
+    let debt_liquidity_before_repay = token_balance(&ctx.accounts.user_debt_liquidity_pubkey)?;
+    repay_obligation_liquidity(
+        CpiContext::new_with_signer(
+            ctx.program.clone(),
+            ctx.accounts.repay_accounts(),
+            ctx.signer_seeds,
+        ),
+        amount_out,
+    )?;
+    Ok(debt_liquidity_before_repay
+        .saturating_sub(token_balance(&ctx.accounts.user_debt_liquidity_pubkey)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use solend_token_lending::state::{ObligationCollateral, ObligationLiquidity};
+
+    use super::*;
+
+    const ONE: u64 = 1_000_000;
+
+    fn reserve(price: u64, mint_decimals: u8) -> Reserve {
+        let mut reserve = Reserve::default();
+        reserve.liquidity.market_price = Decimal::from(price);
+        reserve.liquidity.mint_decimals = mint_decimals;
+        reserve
+    }
+
+    /// 100 tokens of $10 collateral at 50% LTV against `borrowed` dollars of $1 debt
+    fn position(borrowed: u64) -> (Pubkey, Reserve, Pubkey, Reserve, Obligation) {
+        let (withdraw_reserve_pubkey, repay_reserve_pubkey) =
+            (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut withdraw_reserve = reserve(10, 6);
+        withdraw_reserve.config.loan_to_value_ratio = 50;
+
+        let mut collateral = ObligationCollateral::new(withdraw_reserve_pubkey);
+        collateral.deposited_amount = 100 * ONE;
+        collateral.market_value = Decimal::from(1_000u64);
+        let mut liquidity = ObligationLiquidity::new(repay_reserve_pubkey);
+        liquidity.borrowed_amount_wads = Decimal::from(borrowed * ONE);
+        liquidity.market_value = Decimal::from(borrowed);
+        let obligation = Obligation {
+            deposits: vec![collateral],
+            borrows: vec![liquidity],
+            deposited_value: Decimal::from(1_000u64),
+            borrowed_value: Decimal::from(borrowed),
+            allowed_borrow_value: Decimal::from(500u64),
+            ..Obligation::default()
+        };
+        (
+            withdraw_reserve_pubkey,
+            withdraw_reserve,
+            repay_reserve_pubkey,
+            reserve(1, 6),
+            obligation,
+        )
+    }
+
+    fn plan(borrowed: u64, max_slippage_bps: u16) -> Result<CollateralRepayPlan, ProgramError> {
+        let (withdraw_pubkey, withdraw_reserve, repay_pubkey, repay_reserve, obligation) =
+            position(borrowed);
+        plan_collateral_repay(
+            &obligation,
+            &withdraw_pubkey,
+            &withdraw_reserve,
+            &repay_pubkey,
+            &repay_reserve,
+            max_slippage_bps,
+        )
+    }
+
+    #[test]
+    fn min_swap_output_converts_at_oracle_prices() {
+        // 2 tokens of $10 into $1 tokens with 9 decimals
+        let (withdraw_reserve, repay_reserve) = (reserve(10, 6), reserve(1, 9));
+        assert_eq!(
+            min_swap_output(&withdraw_reserve, &repay_reserve, 2_000_000, 0),
+            Ok(20_000_000_000)
+        );
+    }
+
+    #[test]
+    fn min_swap_output_allows_the_slippage_tolerance() {
+        let (withdraw_reserve, repay_reserve) = (reserve(10, 6), reserve(1, 9));
+        assert_eq!(
+            min_swap_output(&withdraw_reserve, &repay_reserve, 2_000_000, 50),
+            Ok(19_900_000_000)
+        );
+        assert_eq!(
+            min_swap_output(&withdraw_reserve, &repay_reserve, 2_000_000, 10_000),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn plan_withdraws_only_what_keeps_the_obligation_healthy() {
+        // $100 of room under the allowed borrow value frees $200 of 50% LTV collateral
+        assert_eq!(
+            plan(400, 50),
+            Ok(CollateralRepayPlan {
+                withdraw_collateral_amount: 20 * ONE,
+                swap_amount_in: 20 * ONE,
+                min_swap_output: 199 * ONE,
+            })
+        );
+    }
+
+    #[test]
+    fn plan_withdraws_only_what_the_debt_needs() {
+        // $100 of debt needs $100 of collateral, or a little more with slippage
+        assert_eq!(
+            plan(100, 0),
+            Ok(CollateralRepayPlan {
+                withdraw_collateral_amount: 10 * ONE,
+                swap_amount_in: 10 * ONE,
+                min_swap_output: 100 * ONE,
+            })
+        );
+        let with_slippage = plan(100, 50).unwrap();
+        assert!(with_slippage.withdraw_collateral_amount > 10 * ONE);
+        assert!(with_slippage.min_swap_output <= 100 * ONE);
+    }
+
+    #[test]
+    fn plan_fails_at_the_borrow_limit() {
+        assert_eq!(
+            plan(500, 50),
+            Err(error!(SolendAnchorError::NothingWithdrawable).into())
+        );
+    }
+
+    #[test]
+    fn plan_fails_for_reserves_outside_the_obligation() {
+        let (withdraw_pubkey, withdraw_reserve, _, repay_reserve, obligation) = position(100);
+        assert_eq!(
+            plan_collateral_repay(
+                &obligation,
+                &withdraw_pubkey,
+                &withdraw_reserve,
+                &Pubkey::new_unique(),
+                &repay_reserve,
+                50,
+            ),
+            Err(error!(SolendAnchorError::ReserveNotInObligation).into())
+        );
+    }
+}
@@ -0,0 +1,1381 @@
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::{instruction::Instruction, program_error::ProgramError},
+};
+use solend_token_lending::{instruction::LendingInstruction, state::ReserveConfig};
+
+/// A Solend instruction decoded back into its arguments and named accounts.
+/// Sysvars and the token program are left out of the account fields.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SolendInstruction {
+    InitLendingMarket {
+        owner: Pubkey,
+        quote_currency: [u8; 32],
+        lending_market: Pubkey,
+        oracle_program_id: Pubkey,
+        switchboard_oracle_program_id: Pubkey,
+    },
+    SetLendingMarketOwner {
+        new_owner: Pubkey,
+        lending_market: Pubkey,
+        lending_market_owner: Pubkey,
+    },
+    InitReserve {
+        liquidity_amount: u64,
+        config: ReserveConfig,
+        source_liquidity: Pubkey,
+        destination_collateral: Pubkey,
+        reserve: Pubkey,
+        reserve_liquidity_mint: Pubkey,
+        reserve_liquidity_supply: Pubkey,
+        reserve_liquidity_fee_receiver: Pubkey,
+        reserve_collateral_mint: Pubkey,
+        reserve_collateral_supply: Pubkey,
+        pyth_product: Pubkey,
+        pyth_price: Pubkey,
+        switchboard_feed: Pubkey,
+        lending_market: Pubkey,
+        lending_market_authority: Pubkey,
+        lending_market_owner: Pubkey,
+        user_transfer_authority: Pubkey,
+    },
+    RefreshReserve {
+        reserve: Pubkey,
+        reserve_liquidity_pyth_oracle: Pubkey,
+        reserve_liquidity_switchboard_oracle: Pubkey,
+    },
+    DepositReserveLiquidity {
+        liquidity_amount: u64,
+        source_liquidity: Pubkey,
+        destination_collateral: Pubkey,
+        reserve: Pubkey,
+        reserve_liquidity_supply: Pubkey,
+        reserve_collateral_mint: Pubkey,
+        lending_market: Pubkey,
+        lending_market_authority: Pubkey,
+        user_transfer_authority: Pubkey,
+    },
+    RedeemReserveCollateral {
+        collateral_amount: u64,
+        source_collateral: Pubkey,
+        destination_liquidity: Pubkey,
+        reserve: Pubkey,
+        reserve_collateral_mint: Pubkey,
+        reserve_liquidity_supply: Pubkey,
+        lending_market: Pubkey,
+        lending_market_authority: Pubkey,
+        user_transfer_authority: Pubkey,
+    },
+    InitObligation {
+        obligation: Pubkey,
+        lending_market: Pubkey,
+        obligation_owner: Pubkey,
+    },
+    RefreshObligation {
+        obligation: Pubkey,
+        reserves: Vec<Pubkey>,
+    },
+    DepositObligationCollateral {
+        collateral_amount: u64,
+        source_collateral: Pubkey,
+        destination_collateral: Pubkey,
+        deposit_reserve: Pubkey,
+        obligation: Pubkey,
+        lending_market: Pubkey,
+        obligation_owner: Pubkey,
+        user_transfer_authority: Pubkey,
+    },
+    WithdrawObligationCollateral {
+        collateral_amount: u64,
+        source_collateral: Pubkey,
+        destination_collateral: Pubkey,
+        withdraw_reserve: Pubkey,
+        obligation: Pubkey,
+        lending_market: Pubkey,
+        lending_market_authority: Pubkey,
+        obligation_owner: Pubkey,
+    },
+    BorrowObligationLiquidity {
+        liquidity_amount: u64,
+        source_liquidity: Pubkey,
+        destination_liquidity: Pubkey,
+        borrow_reserve: Pubkey,
+        borrow_reserve_liquidity_fee_receiver: Pubkey,
+        obligation: Pubkey,
+        lending_market: Pubkey,
+        lending_market_authority: Pubkey,
+        obligation_owner: Pubkey,
+        host_fee_receiver: Option<Pubkey>,
+    },
+    RepayObligationLiquidity {
+        liquidity_amount: u64,
+        source_liquidity: Pubkey,
+        destination_liquidity: Pubkey,
+        repay_reserve: Pubkey,
+        obligation: Pubkey,
+        lending_market: Pubkey,
+        user_transfer_authority: Pubkey,
+    },
+    LiquidateObligation {
+        liquidity_amount: u64,
+        source_liquidity: Pubkey,
+        destination_collateral: Pubkey,
+        repay_reserve: Pubkey,
+        repay_reserve_liquidity_supply: Pubkey,
+        withdraw_reserve: Pubkey,
+        withdraw_reserve_collateral_supply: Pubkey,
+        obligation: Pubkey,
+        lending_market: Pubkey,
+        lending_market_authority: Pubkey,
+        user_transfer_authority: Pubkey,
+    },
+    FlashLoan {
+        amount: u64,
+        source_liquidity: Pubkey,
+        destination_liquidity: Pubkey,
+        reserve: Pubkey,
+        reserve_liquidity_fee_receiver: Pubkey,
+        host_fee_receiver: Pubkey,
+        lending_market: Pubkey,
+        lending_market_authority: Pubkey,
+        flash_loan_receiver_program_id: Pubkey,
+        flash_loan_receiver_accounts: Vec<Pubkey>,
+    },
+    DepositReserveLiquidityAndObligationCollateral {
+        liquidity_amount: u64,
+        source_liquidity: Pubkey,
+        user_collateral: Pubkey,
+        reserve: Pubkey,
+        reserve_liquidity_supply: Pubkey,
+        reserve_collateral_mint: Pubkey,
+        lending_market: Pubkey,
+        lending_market_authority: Pubkey,
+        destination_deposit_collateral: Pubkey,
+        obligation: Pubkey,
+        obligation_owner: Pubkey,
+        reserve_liquidity_pyth_oracle: Pubkey,
+        reserve_liquidity_switchboard_oracle: Pubkey,
+        user_transfer_authority: Pubkey,
+    },
+    UpdateReserveConfig {
+        config: ReserveConfig,
+        reserve: Pubkey,
+        lending_market: Pubkey,
+        lending_market_authority: Pubkey,
+        lending_market_owner: Pubkey,
+        pyth_product: Pubkey,
+        pyth_price: Pubkey,
+        switchboard_feed: Pubkey,
+    },
+}
+
+struct AccountKeys<'a> {
+    keys: &'a [Pubkey],
+    next: usize,
+}
+
+impl<'a> AccountKeys<'a> {
+    fn next(&mut self) -> Result<Pubkey, ProgramError> {
+        let key = self
+            .keys
+            .get(self.next)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        self.next += 1;
+        Ok(*key)
+    }
+
+    /// Steps over sysvars and the token program
+    fn skip(&mut self, count: usize) -> Result<(), ProgramError> {
+        for _ in 0..count {
+            self.next()?;
+        }
+        Ok(())
+    }
+
+    fn rest(&mut self) -> Vec<Pubkey> {
+        let rest = self.keys.get(self.next..).unwrap_or_default().to_vec();
+        self.next = self.keys.len();
+        rest
+    }
+}
+
+/// Decodes Solend instruction data and the instruction's account keys, in order
+pub fn decode_instruction(
+    data: &[u8],
+    account_keys: &[Pubkey],
+) -> Result<SolendInstruction, ProgramError> {
+    let mut keys = AccountKeys {
+        keys: account_keys,
+        next: 0,
+    };
+    let k = &mut keys;
+
+    let instruction = match LendingInstruction::unpack(data)? {
+        LendingInstruction::InitLendingMarket {
+            owner,
+            quote_currency,
+        } => {
+            let lending_market = k.next()?;
+            k.skip(2)?;
+            SolendInstruction::InitLendingMarket {
+                owner,
+                quote_currency,
+                lending_market,
+                oracle_program_id: k.next()?,
+                switchboard_oracle_program_id: k.next()?,
+            }
+        }
+        LendingInstruction::SetLendingMarketOwner { new_owner } => {
+            SolendInstruction::SetLendingMarketOwner {
+                new_owner,
+                lending_market: k.next()?,
+                lending_market_owner: k.next()?,
+            }
+        }
+        LendingInstruction::InitReserve {
+            liquidity_amount,
+            config,
+        } => SolendInstruction::InitReserve {
+            liquidity_amount,
+            config,
+            source_liquidity: k.next()?,
+            destination_collateral: k.next()?,
+            reserve: k.next()?,
+            reserve_liquidity_mint: k.next()?,
+            reserve_liquidity_supply: k.next()?,
+            reserve_liquidity_fee_receiver: k.next()?,
+            reserve_collateral_mint: k.next()?,
+            reserve_collateral_supply: k.next()?,
+            pyth_product: k.next()?,
+            pyth_price: k.next()?,
+            switchboard_feed: k.next()?,
+            lending_market: k.next()?,
+            lending_market_authority: k.next()?,
+            lending_market_owner: k.next()?,
+            user_transfer_authority: k.next()?,
+        },
+        LendingInstruction::RefreshReserve => SolendInstruction::RefreshReserve {
+            reserve: k.next()?,
+            reserve_liquidity_pyth_oracle: k.next()?,
+            reserve_liquidity_switchboard_oracle: k.next()?,
+        },
+        LendingInstruction::DepositReserveLiquidity { liquidity_amount } => {
+            SolendInstruction::DepositReserveLiquidity {
+                liquidity_amount,
+                source_liquidity: k.next()?,
+                destination_collateral: k.next()?,
+                reserve: k.next()?,
+                reserve_liquidity_supply: k.next()?,
+                reserve_collateral_mint: k.next()?,
+                lending_market: k.next()?,
+                lending_market_authority: k.next()?,
+                user_transfer_authority: k.next()?,
+            }
+        }
+        LendingInstruction::RedeemReserveCollateral { collateral_amount } => {
+            SolendInstruction::RedeemReserveCollateral {
+                collateral_amount,
+                source_collateral: k.next()?,
+                destination_liquidity: k.next()?,
+                reserve: k.next()?,
+                reserve_collateral_mint: k.next()?,
+                reserve_liquidity_supply: k.next()?,
+                lending_market: k.next()?,
+                lending_market_authority: k.next()?,
+                user_transfer_authority: k.next()?,
+            }
+        }
+        LendingInstruction::InitObligation => SolendInstruction::InitObligation {
+            obligation: k.next()?,
+            lending_market: k.next()?,
+            obligation_owner: k.next()?,
+        },
+        LendingInstruction::RefreshObligation => {
+            let obligation = k.next()?;
+            k.skip(1)?;
+            SolendInstruction::RefreshObligation {
+                obligation,
+                reserves: k.rest(),
+            }
+        }
+        LendingInstruction::DepositObligationCollateral { collateral_amount } => {
+            SolendInstruction::DepositObligationCollateral {
+                collateral_amount,
+                source_collateral: k.next()?,
+                destination_collateral: k.next()?,
+                deposit_reserve: k.next()?,
+                obligation: k.next()?,
+                lending_market: k.next()?,
+                obligation_owner: k.next()?,
+                user_transfer_authority: k.next()?,
+            }
+        }
+        LendingInstruction::WithdrawObligationCollateral { collateral_amount } => {
+            SolendInstruction::WithdrawObligationCollateral {
+                collateral_amount,
+                source_collateral: k.next()?,
+                destination_collateral: k.next()?,
+                withdraw_reserve: k.next()?,
+                obligation: k.next()?,
+                lending_market: k.next()?,
+                lending_market_authority: k.next()?,
+                obligation_owner: k.next()?,
+            }
+        }
+        LendingInstruction::BorrowObligationLiquidity { liquidity_amount } => {
+            let source_liquidity = k.next()?;
+            let destination_liquidity = k.next()?;
+            let borrow_reserve = k.next()?;
+            let borrow_reserve_liquidity_fee_receiver = k.next()?;
+            let obligation = k.next()?;
+            let lending_market = k.next()?;
+            let lending_market_authority = k.next()?;
+            let obligation_owner = k.next()?;
+            k.skip(2)?;
+            SolendInstruction::BorrowObligationLiquidity {
+                liquidity_amount,
+                source_liquidity,
+                destination_liquidity,
+                borrow_reserve,
+                borrow_reserve_liquidity_fee_receiver,
+                obligation,
+                lending_market,
+                lending_market_authority,
+                obligation_owner,
+                host_fee_receiver: k.rest().first().copied(),
+            }
+        }
+        LendingInstruction::RepayObligationLiquidity { liquidity_amount } => {
+            SolendInstruction::RepayObligationLiquidity {
+                liquidity_amount,
+                source_liquidity: k.next()?,
+                destination_liquidity: k.next()?,
+                repay_reserve: k.next()?,
+                obligation: k.next()?,
+                lending_market: k.next()?,
+                user_transfer_authority: k.next()?,
+            }
+        }
+        LendingInstruction::LiquidateObligation { liquidity_amount } => {
+            SolendInstruction::LiquidateObligation {
+                liquidity_amount,
+                source_liquidity: k.next()?,
+                destination_collateral: k.next()?,
+                repay_reserve: k.next()?,
+                repay_reserve_liquidity_supply: k.next()?,
+                withdraw_reserve: k.next()?,
+                withdraw_reserve_collateral_supply: k.next()?,
+                obligation: k.next()?,
+                lending_market: k.next()?,
+                lending_market_authority: k.next()?,
+                user_transfer_authority: k.next()?,
+            }
+        }
+        LendingInstruction::FlashLoan { amount } => {
+            let source_liquidity = k.next()?;
+            let destination_liquidity = k.next()?;
+            let reserve = k.next()?;
+            let reserve_liquidity_fee_receiver = k.next()?;
+            let host_fee_receiver = k.next()?;
+            let lending_market = k.next()?;
+            let lending_market_authority = k.next()?;
+            k.skip(1)?;
+            SolendInstruction::FlashLoan {
+                amount,
+                source_liquidity,
+                destination_liquidity,
+                reserve,
+                reserve_liquidity_fee_receiver,
+                host_fee_receiver,
+                lending_market,
+                lending_market_authority,
+                flash_loan_receiver_program_id: k.next()?,
+                flash_loan_receiver_accounts: k.rest(),
+            }
+        }
+        LendingInstruction::DepositReserveLiquidityAndObligationCollateral {
+            liquidity_amount,
+        } => SolendInstruction::DepositReserveLiquidityAndObligationCollateral {
+            liquidity_amount,
+            source_liquidity: k.next()?,
+            user_collateral: k.next()?,
+            reserve: k.next()?,
+            reserve_liquidity_supply: k.next()?,
+            reserve_collateral_mint: k.next()?,
+            lending_market: k.next()?,
+            lending_market_authority: k.next()?,
+            destination_deposit_collateral: k.next()?,
+            obligation: k.next()?,
+            obligation_owner: k.next()?,
+            reserve_liquidity_pyth_oracle: k.next()?,
+            reserve_liquidity_switchboard_oracle: k.next()?,
+            user_transfer_authority: k.next()?,
+        },
+        LendingInstruction::UpdateReserveConfig { config } => {
+            SolendInstruction::UpdateReserveConfig {
+                config,
+                reserve: k.next()?,
+                lending_market: k.next()?,
+                lending_market_authority: k.next()?,
+                lending_market_owner: k.next()?,
+                pyth_product: k.next()?,
+                pyth_price: k.next()?,
+                switchboard_feed: k.next()?,
+            }
+        }
+    };
+    Ok(instruction)
+}
+
+/// Decodes an instruction, checking that it targets `program_id`
+pub fn decode(
+    program_id: &Pubkey,
+    instruction: &Instruction,
+) -> Result<SolendInstruction, ProgramError> {
+    if instruction.program_id != *program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let account_keys: Vec<Pubkey> = instruction
+        .accounts
+        .iter()
+        .map(|meta| meta.pubkey)
+        .collect();
+    decode_instruction(&instruction.data, &account_keys)
+}
+
+impl SolendInstruction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SolendInstruction::InitLendingMarket { .. } => "InitLendingMarket",
+            SolendInstruction::SetLendingMarketOwner { .. } => "SetLendingMarketOwner",
+            SolendInstruction::InitReserve { .. } => "InitReserve",
+            SolendInstruction::RefreshReserve { .. } => "RefreshReserve",
+            SolendInstruction::DepositReserveLiquidity { .. } => "DepositReserveLiquidity",
+            SolendInstruction::RedeemReserveCollateral { .. } => "RedeemReserveCollateral",
+            SolendInstruction::InitObligation { .. } => "InitObligation",
+            SolendInstruction::RefreshObligation { .. } => "RefreshObligation",
+            SolendInstruction::DepositObligationCollateral { .. } => "DepositObligationCollateral",
+            SolendInstruction::WithdrawObligationCollateral { .. } => {
+                "WithdrawObligationCollateral"
+            }
+            SolendInstruction::BorrowObligationLiquidity { .. } => "BorrowObligationLiquidity",
+            SolendInstruction::RepayObligationLiquidity { .. } => "RepayObligationLiquidity",
+            SolendInstruction::LiquidateObligation { .. } => "LiquidateObligation",
+            SolendInstruction::FlashLoan { .. } => "FlashLoan",
+            SolendInstruction::DepositReserveLiquidityAndObligationCollateral { .. } => {
+                "DepositReserveLiquidityAndObligationCollateral"
+            }
+            SolendInstruction::UpdateReserveConfig { .. } => "UpdateReserveConfig",
+        }
+    }
+
+    /// Named accounts in instruction order, sysvars and the token program excluded
+    pub fn labeled_accounts(&self) -> Vec<(&'static str, Pubkey)> {
+        match self {
+            SolendInstruction::InitLendingMarket {
+                lending_market,
+                oracle_program_id,
+                switchboard_oracle_program_id,
+                ..
+            } => vec![
+                ("lending_market", *lending_market),
+                ("oracle_program_id", *oracle_program_id),
+                ("switchboard_oracle_program_id", *switchboard_oracle_program_id),
+            ],
+            SolendInstruction::SetLendingMarketOwner {
+                lending_market,
+                lending_market_owner,
+                ..
+            } => vec![
+                ("lending_market", *lending_market),
+                ("lending_market_owner", *lending_market_owner),
+            ],
+            SolendInstruction::InitReserve {
+                source_liquidity,
+                destination_collateral,
+                reserve,
+                reserve_liquidity_mint,
+                reserve_liquidity_supply,
+                reserve_liquidity_fee_receiver,
+                reserve_collateral_mint,
+                reserve_collateral_supply,
+                pyth_product,
+                pyth_price,
+                switchboard_feed,
+                lending_market,
+                lending_market_authority,
+                lending_market_owner,
+                user_transfer_authority,
+                ..
+            } => vec![
+                ("source_liquidity", *source_liquidity),
+                ("destination_collateral", *destination_collateral),
+                ("reserve", *reserve),
+                ("reserve_liquidity_mint", *reserve_liquidity_mint),
+                ("reserve_liquidity_supply", *reserve_liquidity_supply),
+                ("reserve_liquidity_fee_receiver", *reserve_liquidity_fee_receiver),
+                ("reserve_collateral_mint", *reserve_collateral_mint),
+                ("reserve_collateral_supply", *reserve_collateral_supply),
+                ("pyth_product", *pyth_product),
+                ("pyth_price", *pyth_price),
+                ("switchboard_feed", *switchboard_feed),
+                ("lending_market", *lending_market),
+                ("lending_market_authority", *lending_market_authority),
+                ("lending_market_owner", *lending_market_owner),
+                ("user_transfer_authority", *user_transfer_authority),
+            ],
+            SolendInstruction::RefreshReserve {
+                reserve,
+                reserve_liquidity_pyth_oracle,
+                reserve_liquidity_switchboard_oracle,
+            } => vec![
+                ("reserve", *reserve),
+                ("reserve_liquidity_pyth_oracle", *reserve_liquidity_pyth_oracle),
+                (
+                    "reserve_liquidity_switchboard_oracle",
+                    *reserve_liquidity_switchboard_oracle,
+                ),
+            ],
+            SolendInstruction::DepositReserveLiquidity {
+                source_liquidity,
+                destination_collateral,
+                reserve,
+                reserve_liquidity_supply,
+                reserve_collateral_mint,
+                lending_market,
+                lending_market_authority,
+                user_transfer_authority,
+                ..
+            } => vec![
+                ("source_liquidity", *source_liquidity),
+                ("destination_collateral", *destination_collateral),
+                ("reserve", *reserve),
+                ("reserve_liquidity_supply", *reserve_liquidity_supply),
+                ("reserve_collateral_mint", *reserve_collateral_mint),
+                ("lending_market", *lending_market),
+                ("lending_market_authority", *lending_market_authority),
+                ("user_transfer_authority", *user_transfer_authority),
+            ],
+            SolendInstruction::RedeemReserveCollateral {
+                source_collateral,
+                destination_liquidity,
+                reserve,
+                reserve_collateral_mint,
+                reserve_liquidity_supply,
+                lending_market,
+                lending_market_authority,
+                user_transfer_authority,
+                ..
+            } => vec![
+                ("source_collateral", *source_collateral),
+                ("destination_liquidity", *destination_liquidity),
+                ("reserve", *reserve),
+                ("reserve_collateral_mint", *reserve_collateral_mint),
+                ("reserve_liquidity_supply", *reserve_liquidity_supply),
+                ("lending_market", *lending_market),
+                ("lending_market_authority", *lending_market_authority),
+                ("user_transfer_authority", *user_transfer_authority),
+            ],
+            SolendInstruction::InitObligation {
+                obligation,
+                lending_market,
+                obligation_owner,
+            } => vec![
+                ("obligation", *obligation),
+                ("lending_market", *lending_market),
+                ("obligation_owner", *obligation_owner),
+            ],
+            SolendInstruction::RefreshObligation {
+                obligation,
+                reserves,
+            } => {
+                let mut accounts = vec![("obligation", *obligation)];
+                accounts.extend(reserves.iter().map(|reserve| ("reserve", *reserve)));
+                accounts
+            }
+            SolendInstruction::DepositObligationCollateral {
+                source_collateral,
+                destination_collateral,
+                deposit_reserve,
+                obligation,
+                lending_market,
+                obligation_owner,
+                user_transfer_authority,
+                ..
+            } => vec![
+                ("source_collateral", *source_collateral),
+                ("destination_collateral", *destination_collateral),
+                ("deposit_reserve", *deposit_reserve),
+                ("obligation", *obligation),
+                ("lending_market", *lending_market),
+                ("obligation_owner", *obligation_owner),
+                ("user_transfer_authority", *user_transfer_authority),
+            ],
+            SolendInstruction::WithdrawObligationCollateral {
+                source_collateral,
+                destination_collateral,
+                withdraw_reserve,
+                obligation,
+                lending_market,
+                lending_market_authority,
+                obligation_owner,
+                ..
+            } => vec![
+                ("source_collateral", *source_collateral),
+                ("destination_collateral", *destination_collateral),
+                ("withdraw_reserve", *withdraw_reserve),
+                ("obligation", *obligation),
+                ("lending_market", *lending_market),
+                ("lending_market_authority", *lending_market_authority),
+                ("obligation_owner", *obligation_owner),
+            ],
+            SolendInstruction::BorrowObligationLiquidity {
+                source_liquidity,
+                destination_liquidity,
+                borrow_reserve,
+                borrow_reserve_liquidity_fee_receiver,
+                obligation,
+                lending_market,
+                lending_market_authority,
+                obligation_owner,
+                host_fee_receiver,
+                ..
+            } => {
+                let mut accounts = vec![
+                    ("source_liquidity", *source_liquidity),
+                    ("destination_liquidity", *destination_liquidity),
+                    ("borrow_reserve", *borrow_reserve),
+                    (
+                        "borrow_reserve_liquidity_fee_receiver",
+                        *borrow_reserve_liquidity_fee_receiver,
+                    ),
+                    ("obligation", *obligation),
+                    ("lending_market", *lending_market),
+                    ("lending_market_authority", *lending_market_authority),
+                    ("obligation_owner", *obligation_owner),
+                ];
+                if let Some(host_fee_receiver) = host_fee_receiver {
+                    accounts.push(("host_fee_receiver", *host_fee_receiver));
+                }
+                accounts
+            }
+            SolendInstruction::RepayObligationLiquidity {
+                source_liquidity,
+                destination_liquidity,
+                repay_reserve,
+                obligation,
+                lending_market,
+                user_transfer_authority,
+                ..
+            } => vec![
+                ("source_liquidity", *source_liquidity),
+                ("destination_liquidity", *destination_liquidity),
+                ("repay_reserve", *repay_reserve),
+                ("obligation", *obligation),
+                ("lending_market", *lending_market),
+                ("user_transfer_authority", *user_transfer_authority),
+            ],
+            SolendInstruction::LiquidateObligation {
+                source_liquidity,
+                destination_collateral,
+                repay_reserve,
+                repay_reserve_liquidity_supply,
+                withdraw_reserve,
+                withdraw_reserve_collateral_supply,
+                obligation,
+                lending_market,
+                lending_market_authority,
+                user_transfer_authority,
+                ..
+            } => vec![
+                ("source_liquidity", *source_liquidity),
+                ("destination_collateral", *destination_collateral),
+                ("repay_reserve", *repay_reserve),
+                ("repay_reserve_liquidity_supply", *repay_reserve_liquidity_supply),
+                ("withdraw_reserve", *withdraw_reserve),
+                (
+                    "withdraw_reserve_collateral_supply",
+                    *withdraw_reserve_collateral_supply,
+                ),
+                ("obligation", *obligation),
+                ("lending_market", *lending_market),
+                ("lending_market_authority", *lending_market_authority),
+                ("user_transfer_authority", *user_transfer_authority),
+            ],
+            SolendInstruction::FlashLoan {
+                source_liquidity,
+                destination_liquidity,
+                reserve,
+                reserve_liquidity_fee_receiver,
+                host_fee_receiver,
+                lending_market,
+                lending_market_authority,
+                flash_loan_receiver_program_id,
+                flash_loan_receiver_accounts,
+                ..
+            } => {
+                let mut accounts = vec![
+                    ("source_liquidity", *source_liquidity),
+                    ("destination_liquidity", *destination_liquidity),
+                    ("reserve", *reserve),
+                    ("reserve_liquidity_fee_receiver", *reserve_liquidity_fee_receiver),
+                    ("host_fee_receiver", *host_fee_receiver),
+                    ("lending_market", *lending_market),
+                    ("lending_market_authority", *lending_market_authority),
+                    ("flash_loan_receiver_program_id", *flash_loan_receiver_program_id),
+                ];
+                accounts.extend(
+                    flash_loan_receiver_accounts
+                        .iter()
+                        .map(|account| ("flash_loan_receiver_account", *account)),
+                );
+                accounts
+            }
+            SolendInstruction::DepositReserveLiquidityAndObligationCollateral {
+                source_liquidity,
+                user_collateral,
+                reserve,
+                reserve_liquidity_supply,
+                reserve_collateral_mint,
+                lending_market,
+                lending_market_authority,
+                destination_deposit_collateral,
+                obligation,
+                obligation_owner,
+                reserve_liquidity_pyth_oracle,
+                reserve_liquidity_switchboard_oracle,
+                user_transfer_authority,
+                ..
+            } => vec![
+                ("source_liquidity", *source_liquidity),
+                ("user_collateral", *user_collateral),
+                ("reserve", *reserve),
+                ("reserve_liquidity_supply", *reserve_liquidity_supply),
+                ("reserve_collateral_mint", *reserve_collateral_mint),
+                ("lending_market", *lending_market),
+                ("lending_market_authority", *lending_market_authority),
+                ("destination_deposit_collateral", *destination_deposit_collateral),
+                ("obligation", *obligation),
+                ("obligation_owner", *obligation_owner),
+                ("reserve_liquidity_pyth_oracle", *reserve_liquidity_pyth_oracle),
+                (
+                    "reserve_liquidity_switchboard_oracle",
+                    *reserve_liquidity_switchboard_oracle,
+                ),
+                ("user_transfer_authority", *user_transfer_authority),
+            ],
+            SolendInstruction::UpdateReserveConfig {
+                reserve,
+                lending_market,
+                lending_market_authority,
+                lending_market_owner,
+                pyth_product,
+                pyth_price,
+                switchboard_feed,
+                ..
+            } => vec![
+                ("reserve", *reserve),
+                ("lending_market", *lending_market),
+                ("lending_market_authority", *lending_market_authority),
+                ("lending_market_owner", *lending_market_owner),
+                ("pyth_product", *pyth_product),
+                ("pyth_price", *pyth_price),
+                ("switchboard_feed", *switchboard_feed),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::solana_program::instruction::AccountMeta;
+    use solend_token_lending::instruction as solend_ix;
+
+    use super::*;
+
+    fn keys<const N: usize>() -> [Pubkey; N] {
+        [(); N].map(|_| Pubkey::new_unique())
+    }
+
+    fn authority(lending_market: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[lending_market.as_ref()], &solend_token_lending::id()).0
+    }
+
+    fn config(fee_receiver: Pubkey) -> ReserveConfig {
+        ReserveConfig {
+            optimal_utilization_rate: 80,
+            loan_to_value_ratio: 50,
+            liquidation_bonus: 5,
+            liquidation_threshold: 55,
+            min_borrow_rate: 0,
+            optimal_borrow_rate: 4,
+            max_borrow_rate: 30,
+            deposit_limit: 1_000_000,
+            borrow_limit: 500_000,
+            fee_receiver,
+            ..ReserveConfig::default()
+        }
+    }
+
+    fn round_trip(instruction: Instruction) -> SolendInstruction {
+        decode(&solend_token_lending::id(), &instruction).unwrap()
+    }
+
+    #[test]
+    fn init_lending_market() {
+        let [owner, lending_market, oracle_program_id, switchboard_oracle_program_id] = keys();
+        let quote_currency = [7u8; 32];
+        assert_eq!(
+            round_trip(solend_ix::init_lending_market(
+                solend_token_lending::id(),
+                owner,
+                quote_currency,
+                lending_market,
+                oracle_program_id,
+                switchboard_oracle_program_id,
+            )),
+            SolendInstruction::InitLendingMarket {
+                owner,
+                quote_currency,
+                lending_market,
+                oracle_program_id,
+                switchboard_oracle_program_id,
+            }
+        );
+    }
+
+    #[test]
+    fn set_lending_market_owner() {
+        let [lending_market, lending_market_owner, new_owner] = keys();
+        assert_eq!(
+            round_trip(solend_ix::set_lending_market_owner(
+                solend_token_lending::id(),
+                lending_market,
+                lending_market_owner,
+                new_owner,
+            )),
+            SolendInstruction::SetLendingMarketOwner {
+                new_owner,
+                lending_market,
+                lending_market_owner,
+            }
+        );
+    }
+
+    #[test]
+    fn init_reserve() {
+        let [
+            source_liquidity,
+            destination_collateral,
+            reserve,
+            reserve_liquidity_mint,
+            reserve_liquidity_supply,
+            reserve_liquidity_fee_receiver,
+            reserve_collateral_mint,
+            reserve_collateral_supply,
+            pyth_product,
+            pyth_price,
+            switchboard_feed,
+            lending_market,
+            lending_market_owner,
+            user_transfer_authority,
+        ] = keys();
+        let config = config(reserve_liquidity_fee_receiver);
+        assert_eq!(
+            round_trip(solend_ix::init_reserve(
+                solend_token_lending::id(),
+                1_000,
+                config,
+                source_liquidity,
+                destination_collateral,
+                reserve,
+                reserve_liquidity_mint,
+                reserve_liquidity_supply,
+                reserve_collateral_mint,
+                reserve_collateral_supply,
+                pyth_product,
+                pyth_price,
+                switchboard_feed,
+                lending_market,
+                lending_market_owner,
+                user_transfer_authority,
+            )),
+            SolendInstruction::InitReserve {
+                liquidity_amount: 1_000,
+                config,
+                source_liquidity,
+                destination_collateral,
+                reserve,
+                reserve_liquidity_mint,
+                reserve_liquidity_supply,
+                reserve_liquidity_fee_receiver,
+                reserve_collateral_mint,
+                reserve_collateral_supply,
+                pyth_product,
+                pyth_price,
+                switchboard_feed,
+                lending_market,
+                lending_market_authority: authority(&lending_market),
+                lending_market_owner,
+                user_transfer_authority,
+            }
+        );
+    }
+
+    #[test]
+    fn refresh_reserve() {
+        let [reserve, reserve_liquidity_pyth_oracle, reserve_liquidity_switchboard_oracle] = keys();
+        assert_eq!(
+            round_trip(solend_ix::refresh_reserve(
+                solend_token_lending::id(),
+                reserve,
+                reserve_liquidity_pyth_oracle,
+                reserve_liquidity_switchboard_oracle,
+            )),
+            SolendInstruction::RefreshReserve {
+                reserve,
+                reserve_liquidity_pyth_oracle,
+                reserve_liquidity_switchboard_oracle,
+            }
+        );
+    }
+
+    #[test]
+    fn deposit_reserve_liquidity() {
+        let [
+            source_liquidity,
+            destination_collateral,
+            reserve,
+            reserve_liquidity_supply,
+            reserve_collateral_mint,
+            lending_market,
+            user_transfer_authority,
+        ] = keys();
+        assert_eq!(
+            round_trip(solend_ix::deposit_reserve_liquidity(
+                solend_token_lending::id(),
+                1_000,
+                source_liquidity,
+                destination_collateral,
+                reserve,
+                reserve_liquidity_supply,
+                reserve_collateral_mint,
+                lending_market,
+                user_transfer_authority,
+            )),
+            SolendInstruction::DepositReserveLiquidity {
+                liquidity_amount: 1_000,
+                source_liquidity,
+                destination_collateral,
+                reserve,
+                reserve_liquidity_supply,
+                reserve_collateral_mint,
+                lending_market,
+                lending_market_authority: authority(&lending_market),
+                user_transfer_authority,
+            }
+        );
+    }
+
+    #[test]
+    fn redeem_reserve_collateral() {
+        let [
+            source_collateral,
+            destination_liquidity,
+            reserve,
+            reserve_collateral_mint,
+            reserve_liquidity_supply,
+            lending_market,
+            user_transfer_authority,
+        ] = keys();
+        assert_eq!(
+            round_trip(solend_ix::redeem_reserve_collateral(
+                solend_token_lending::id(),
+                1_000,
+                source_collateral,
+                destination_liquidity,
+                reserve,
+                reserve_collateral_mint,
+                reserve_liquidity_supply,
+                lending_market,
+                user_transfer_authority,
+            )),
+            SolendInstruction::RedeemReserveCollateral {
+                collateral_amount: 1_000,
+                source_collateral,
+                destination_liquidity,
+                reserve,
+                reserve_collateral_mint,
+                reserve_liquidity_supply,
+                lending_market,
+                lending_market_authority: authority(&lending_market),
+                user_transfer_authority,
+            }
+        );
+    }
+
+    #[test]
+    fn init_obligation() {
+        let [obligation, lending_market, obligation_owner] = keys();
+        assert_eq!(
+            round_trip(solend_ix::init_obligation(
+                solend_token_lending::id(),
+                obligation,
+                lending_market,
+                obligation_owner,
+            )),
+            SolendInstruction::InitObligation {
+                obligation,
+                lending_market,
+                obligation_owner,
+            }
+        );
+    }
+
+    #[test]
+    fn refresh_obligation() {
+        let [obligation, deposit_reserve, borrow_reserve] = keys();
+        assert_eq!(
+            round_trip(solend_ix::refresh_obligation(
+                solend_token_lending::id(),
+                obligation,
+                vec![deposit_reserve, borrow_reserve],
+            )),
+            SolendInstruction::RefreshObligation {
+                obligation,
+                reserves: vec![deposit_reserve, borrow_reserve],
+            }
+        );
+    }
+
+    #[test]
+    fn deposit_obligation_collateral() {
+        let [
+            source_collateral,
+            destination_collateral,
+            deposit_reserve,
+            obligation,
+            lending_market,
+            obligation_owner,
+            user_transfer_authority,
+        ] = keys();
+        assert_eq!(
+            round_trip(solend_ix::deposit_obligation_collateral(
+                solend_token_lending::id(),
+                1_000,
+                source_collateral,
+                destination_collateral,
+                deposit_reserve,
+                obligation,
+                lending_market,
+                obligation_owner,
+                user_transfer_authority,
+            )),
+            SolendInstruction::DepositObligationCollateral {
+                collateral_amount: 1_000,
+                source_collateral,
+                destination_collateral,
+                deposit_reserve,
+                obligation,
+                lending_market,
+                obligation_owner,
+                user_transfer_authority,
+            }
+        );
+    }
+
+    #[test]
+    fn withdraw_obligation_collateral() {
+        let [
+            source_collateral,
+            destination_collateral,
+            withdraw_reserve,
+            obligation,
+            lending_market,
+            obligation_owner,
+        ] = keys();
+        assert_eq!(
+            round_trip(solend_ix::withdraw_obligation_collateral(
+                solend_token_lending::id(),
+                1_000,
+                source_collateral,
+                destination_collateral,
+                withdraw_reserve,
+                obligation,
+                lending_market,
+                obligation_owner,
+            )),
+            SolendInstruction::WithdrawObligationCollateral {
+                collateral_amount: 1_000,
+                source_collateral,
+                destination_collateral,
+                withdraw_reserve,
+                obligation,
+                lending_market,
+                lending_market_authority: authority(&lending_market),
+                obligation_owner,
+            }
+        );
+    }
+
+    #[test]
+    fn borrow_obligation_liquidity() {
+        let [
+            source_liquidity,
+            destination_liquidity,
+            borrow_reserve,
+            borrow_reserve_liquidity_fee_receiver,
+            obligation,
+            lending_market,
+            obligation_owner,
+            host_fee_receiver,
+        ] = keys();
+        for host_fee_receiver in [None, Some(host_fee_receiver)] {
+            assert_eq!(
+                round_trip(solend_ix::borrow_obligation_liquidity(
+                    solend_token_lending::id(),
+                    1_000,
+                    source_liquidity,
+                    destination_liquidity,
+                    borrow_reserve,
+                    borrow_reserve_liquidity_fee_receiver,
+                    obligation,
+                    lending_market,
+                    obligation_owner,
+                    host_fee_receiver,
+                )),
+                SolendInstruction::BorrowObligationLiquidity {
+                    liquidity_amount: 1_000,
+                    source_liquidity,
+                    destination_liquidity,
+                    borrow_reserve,
+                    borrow_reserve_liquidity_fee_receiver,
+                    obligation,
+                    lending_market,
+                    lending_market_authority: authority(&lending_market),
+                    obligation_owner,
+                    host_fee_receiver,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn repay_obligation_liquidity() {
+        let [
+            source_liquidity,
+            destination_liquidity,
+            repay_reserve,
+            obligation,
+            lending_market,
+            user_transfer_authority,
+        ] = keys();
+        assert_eq!(
+            round_trip(solend_ix::repay_obligation_liquidity(
+                solend_token_lending::id(),
+                1_000,
+                source_liquidity,
+                destination_liquidity,
+                repay_reserve,
+                obligation,
+                lending_market,
+                user_transfer_authority,
+            )),
+            SolendInstruction::RepayObligationLiquidity {
+                liquidity_amount: 1_000,
+                source_liquidity,
+                destination_liquidity,
+                repay_reserve,
+                obligation,
+                lending_market,
+                user_transfer_authority,
+            }
+        );
+    }
+
+    #[test]
+    fn liquidate_obligation() {
+        let [
+            source_liquidity,
+            destination_collateral,
+            repay_reserve,
+            repay_reserve_liquidity_supply,
+            withdraw_reserve,
+            withdraw_reserve_collateral_supply,
+            obligation,
+            lending_market,
+            user_transfer_authority,
+        ] = keys();
+        assert_eq!(
+            round_trip(solend_ix::liquidate_obligation(
+                solend_token_lending::id(),
+                1_000,
+                source_liquidity,
+                destination_collateral,
+                repay_reserve,
+                repay_reserve_liquidity_supply,
+                withdraw_reserve,
+                withdraw_reserve_collateral_supply,
+                obligation,
+                lending_market,
+                user_transfer_authority,
+            )),
+            SolendInstruction::LiquidateObligation {
+                liquidity_amount: 1_000,
+                source_liquidity,
+                destination_collateral,
+                repay_reserve,
+                repay_reserve_liquidity_supply,
+                withdraw_reserve,
+                withdraw_reserve_collateral_supply,
+                obligation,
+                lending_market,
+                lending_market_authority: authority(&lending_market),
+                user_transfer_authority,
+            }
+        );
+    }
+
+    #[test]
+    fn flash_loan() {
+        let [
+            source_liquidity,
+            destination_liquidity,
+            reserve,
+            reserve_liquidity_fee_receiver,
+            host_fee_receiver,
+            lending_market,
+            flash_loan_receiver_program_id,
+            receiver_account,
+        ] = keys();
+        assert_eq!(
+            round_trip(solend_ix::flash_loan(
+                solend_token_lending::id(),
+                1_000,
+                source_liquidity,
+                destination_liquidity,
+                reserve,
+                reserve_liquidity_fee_receiver,
+                host_fee_receiver,
+                lending_market,
+                flash_loan_receiver_program_id,
+                vec![AccountMeta::new(receiver_account, false)],
+            )),
+            SolendInstruction::FlashLoan {
+                amount: 1_000,
+                source_liquidity,
+                destination_liquidity,
+                reserve,
+                reserve_liquidity_fee_receiver,
+                host_fee_receiver,
+                lending_market,
+                lending_market_authority: authority(&lending_market),
+                flash_loan_receiver_program_id,
+                flash_loan_receiver_accounts: vec![receiver_account],
+            }
+        );
+    }
+
+    #[test]
+    fn deposit_reserve_liquidity_and_obligation_collateral() {
+        let [
+            source_liquidity,
+            user_collateral,
+            reserve,
+            reserve_liquidity_supply,
+            reserve_collateral_mint,
+            lending_market,
+            destination_deposit_collateral,
+            obligation,
+            obligation_owner,
+            reserve_liquidity_pyth_oracle,
+            reserve_liquidity_switchboard_oracle,
+            user_transfer_authority,
+        ] = keys();
+        assert_eq!(
+            round_trip(solend_ix::deposit_reserve_liquidity_and_obligation_collateral(
+                solend_token_lending::id(),
+                1_000,
+                source_liquidity,
+                user_collateral,
+                reserve,
+                reserve_liquidity_supply,
+                reserve_collateral_mint,
+                lending_market,
+                destination_deposit_collateral,
+                obligation,
+                obligation_owner,
+                reserve_liquidity_pyth_oracle,
+                reserve_liquidity_switchboard_oracle,
+                user_transfer_authority,
+            )),
+            SolendInstruction::DepositReserveLiquidityAndObligationCollateral {
+                liquidity_amount: 1_000,
+                source_liquidity,
+                user_collateral,
+                reserve,
+                reserve_liquidity_supply,
+                reserve_collateral_mint,
+                lending_market,
+                lending_market_authority: authority(&lending_market),
+                destination_deposit_collateral,
+                obligation,
+                obligation_owner,
+                reserve_liquidity_pyth_oracle,
+                reserve_liquidity_switchboard_oracle,
+                user_transfer_authority,
+            }
+        );
+    }
+
+    #[test]
+    fn update_reserve_config() {
+        let [
+            reserve,
+            lending_market,
+            lending_market_owner,
+            pyth_product,
+            pyth_price,
+            switchboard_feed,
+            fee_receiver,
+        ] = keys();
+        let config = config(fee_receiver);
+        assert_eq!(
+            round_trip(solend_ix::update_reserve_config(
+                solend_token_lending::id(),
+                config,
+                reserve,
+                lending_market,
+                lending_market_owner,
+                pyth_product,
+                pyth_price,
+                switchboard_feed,
+            )),
+            SolendInstruction::UpdateReserveConfig {
+                config,
+                reserve,
+                lending_market,
+                lending_market_authority: authority(&lending_market),
+                lending_market_owner,
+                pyth_product,
+                pyth_price,
+                switchboard_feed,
+            }
+        );
+    }
+
+    #[test]
+    fn decode_rejects_other_programs() {
+        let [obligation, lending_market, obligation_owner] = keys();
+        let instruction = solend_ix::init_obligation(
+            Pubkey::new_unique(),
+            obligation,
+            lending_market,
+            obligation_owner,
+        );
+        assert_eq!(
+            decode(&solend_token_lending::id(), &instruction),
+            Err(ProgramError::IncorrectProgramId)
+        );
+    }
+}
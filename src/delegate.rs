@@ -0,0 +1,268 @@
+//! Lets a program move a user's tokens through Solend with a PDA transfer authority.
+//! The PDA signs the Solend CPI as `user_transfer_authority_pubkey` through the
+//! context's signer seeds, and the delegation is revoked afterwards so nothing is left
+//! approved. Revoking needs the owner, so the owner signs the outer transaction.
+//!
+//! The `_delegated` wrappers approve the PDA for the amount themselves. The
+//! `_preapproved` wrappers leave approving to the owner, typically in an earlier
+//! instruction of the same transaction, and fail unless the PDA was approved for at
+//! least the amount the program is about to spend.
+//!
+//! `withdraw_obligation_collateral` has no variant here: it moves collateral out of
+//! the reserve, not out of a user's token account, and is signed by the obligation
+//! owner rather than a transfer authority.
+
+use anchor_lang::{
+    error,
+    prelude::{AccountInfo, CpiContext},
+    solana_program::{entrypoint::ProgramResult, program_option::COption, program_pack::Pack},
+    Key, ToAccountInfo,
+};
+
+use crate::{
+    deposit_reserve_liquidity, error::SolendAnchorError, liquidate_obligation,
+    redeem_reserve_collateral, repay_obligation_liquidity,
+    token::{approve, revoke},
+    DepositReserveLiquidityAccounts, LiquidateObligationAccounts, RedeemReserveCollateralAccounts,
+    RepayObligationLiquidityAccounts,
+};
+
+/// Fails unless `source` has delegated at least `amount` to `delegate`
+pub fn check_delegation(
+    source: &AccountInfo,
+    delegate: &AccountInfo,
+    amount: u64,
+) -> ProgramResult {
+    let account = spl_token::state::Account::unpack(&source.try_borrow_data()?)?;
+    if account.delegate != COption::Some(delegate.key()) || account.delegated_amount < amount {
+        return Err(error!(SolendAnchorError::InsufficientDelegation).into());
+    }
+    Ok(())
+}
+
+/// Approves `delegate` for `amount` of `source`, runs `cpi` and revokes the delegation
+pub fn with_delegation<'info>(
+    token_program: &AccountInfo<'info>,
+    source: &AccountInfo<'info>,
+    delegate: &AccountInfo<'info>,
+    owner: &AccountInfo<'info>,
+    amount: u64,
+    cpi: impl FnOnce() -> ProgramResult,
+) -> ProgramResult {
+    approve(token_program, source, delegate, owner, amount, &[])?;
+    cpi()?;
+    revoke(token_program, source, owner, &[])
+}
+
+/// Requires `owner` to have approved `delegate` for at least `amount` of `source`, runs
+/// `cpi` and revokes the delegation
+pub fn with_preapproved_delegation<'info>(
+    token_program: &AccountInfo<'info>,
+    source: &AccountInfo<'info>,
+    delegate: &AccountInfo<'info>,
+    owner: &AccountInfo<'info>,
+    amount: u64,
+    cpi: impl FnOnce() -> ProgramResult,
+) -> ProgramResult {
+    check_delegation(source, delegate, amount)?;
+    cpi()?;
+    revoke(token_program, source, owner, &[])
+}
+
+/// Deposits with the PDA in `user_transfer_authority_pubkey` spending `owner`'s liquidity
+pub fn deposit_reserve_liquidity_delegated<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, DepositReserveLiquidityAccounts<'info>>,
+    owner: &AccountInfo<'info>,
+    liquidity_amount: u64,
+) -> ProgramResult {
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let source = ctx.accounts.source_liquidity_pubkey.clone();
+    let delegate = ctx.accounts.user_transfer_authority_pubkey.to_account_info();
+    with_delegation(&token_program, &source, &delegate, owner, liquidity_amount, || {
+        deposit_reserve_liquidity(ctx, liquidity_amount)
+    })
+}
+
+/// Deposits `owner`'s liquidity with the PDA in `user_transfer_authority_pubkey`, which
+/// `owner` must already have approved for `liquidity_amount`
+pub fn deposit_reserve_liquidity_preapproved<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, DepositReserveLiquidityAccounts<'info>>,
+    owner: &AccountInfo<'info>,
+    liquidity_amount: u64,
+) -> ProgramResult {
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let source = ctx.accounts.source_liquidity_pubkey.clone();
+    let delegate = ctx.accounts.user_transfer_authority_pubkey.to_account_info();
+    with_preapproved_delegation(&token_program, &source, &delegate, owner, liquidity_amount, || {
+        deposit_reserve_liquidity(ctx, liquidity_amount)
+    })
+}
+
+/// Repays with the PDA in `user_transfer_authority_pubkey` spending `owner`'s liquidity.
+/// `approve_amount` bounds what can be spent, so it must be explicit even when
+/// `liquidity_amount` is Solend's `u64::MAX` repay-everything sentinel.
+pub fn repay_obligation_liquidity_delegated<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, RepayObligationLiquidityAccounts<'info>>,
+    owner: &AccountInfo<'info>,
+    approve_amount: u64,
+    liquidity_amount: u64,
+) -> ProgramResult {
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let source = ctx.accounts.source_liquidity_pubkey.clone();
+    let delegate = ctx.accounts.user_transfer_authority_pubkey.to_account_info();
+    with_delegation(&token_program, &source, &delegate, owner, approve_amount, || {
+        repay_obligation_liquidity(ctx, liquidity_amount)
+    })
+}
+
+/// Repays `owner`'s liquidity with the PDA in `user_transfer_authority_pubkey`, which
+/// `owner` must already have approved for at least `approved_amount`
+pub fn repay_obligation_liquidity_preapproved<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, RepayObligationLiquidityAccounts<'info>>,
+    owner: &AccountInfo<'info>,
+    approved_amount: u64,
+    liquidity_amount: u64,
+) -> ProgramResult {
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let source = ctx.accounts.source_liquidity_pubkey.clone();
+    let delegate = ctx.accounts.user_transfer_authority_pubkey.to_account_info();
+    with_preapproved_delegation(&token_program, &source, &delegate, owner, approved_amount, || {
+        repay_obligation_liquidity(ctx, liquidity_amount)
+    })
+}
+
+/// Redeems with the PDA in `user_transfer_authority_pubkey` spending `owner`'s collateral
+pub fn redeem_reserve_collateral_delegated<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, RedeemReserveCollateralAccounts<'info>>,
+    owner: &AccountInfo<'info>,
+    collateral_amount: u64,
+) -> ProgramResult {
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let source = ctx.accounts.source_collateral_pubkey.clone();
+    let delegate = ctx.accounts.user_transfer_authority_pubkey.to_account_info();
+    with_delegation(&token_program, &source, &delegate, owner, collateral_amount, || {
+        redeem_reserve_collateral(ctx, collateral_amount)
+    })
+}
+
+/// Redeems `owner`'s collateral with the PDA in `user_transfer_authority_pubkey`, which
+/// `owner` must already have approved for `collateral_amount`
+pub fn redeem_reserve_collateral_preapproved<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, RedeemReserveCollateralAccounts<'info>>,
+    owner: &AccountInfo<'info>,
+    collateral_amount: u64,
+) -> ProgramResult {
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let source = ctx.accounts.source_collateral_pubkey.clone();
+    let delegate = ctx.accounts.user_transfer_authority_pubkey.to_account_info();
+    with_preapproved_delegation(&token_program, &source, &delegate, owner, collateral_amount, || {
+        redeem_reserve_collateral(ctx, collateral_amount)
+    })
+}
+
+/// Liquidates with the PDA in `user_transfer_authority_pubkey` spending `owner`'s
+/// liquidity. As with repaying, `approve_amount` must be explicit when
+/// `liquidity_amount` is `u64::MAX`.
+pub fn liquidate_obligation_delegated<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, LiquidateObligationAccounts<'info>>,
+    owner: &AccountInfo<'info>,
+    approve_amount: u64,
+    liquidity_amount: u64,
+) -> ProgramResult {
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let source = ctx.accounts.source_liquidity_pubkey.clone();
+    let delegate = ctx.accounts.user_transfer_authority_pubkey.to_account_info();
+    with_delegation(&token_program, &source, &delegate, owner, approve_amount, || {
+        liquidate_obligation(ctx, liquidity_amount)
+    })
+}
+
+/// Liquidates with `owner`'s liquidity through the PDA in
+/// `user_transfer_authority_pubkey`, which `owner` must already have approved for at
+/// least `approved_amount`
+pub fn liquidate_obligation_preapproved<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, LiquidateObligationAccounts<'info>>,
+    owner: &AccountInfo<'info>,
+    approved_amount: u64,
+    liquidity_amount: u64,
+) -> ProgramResult {
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let source = ctx.accounts.source_liquidity_pubkey.clone();
+    let delegate = ctx.accounts.user_transfer_authority_pubkey.to_account_info();
+    with_preapproved_delegation(&token_program, &source, &delegate, owner, approved_amount, || {
+        liquidate_obligation(ctx, liquidity_amount)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+    use spl_token::state::{Account, AccountState};
+
+    use super::*;
+
+    /// Checks `amount` against a source that delegated `delegated_amount` to the
+    /// transfer authority, or to some other account
+    fn check(delegated_to_authority: bool, delegated_amount: u64, amount: u64) -> ProgramResult {
+        let source_key = Pubkey::new_unique();
+        let authority_key = Pubkey::new_unique();
+        let delegate = if delegated_to_authority {
+            authority_key
+        } else {
+            Pubkey::new_unique()
+        };
+        let mut source_data = vec![0; Account::LEN];
+        Account::pack(
+            Account {
+                mint: Pubkey::new_unique(),
+                owner: Pubkey::new_unique(),
+                delegate: COption::Some(delegate),
+                state: AccountState::Initialized,
+                delegated_amount,
+                ..Account::default()
+            },
+            &mut source_data,
+        )
+        .unwrap();
+        let (mut source_lamports, mut authority_lamports) = (0, 0);
+        let mut authority_data = vec![];
+        let source = AccountInfo::new(
+            &source_key,
+            false,
+            true,
+            &mut source_lamports,
+            &mut source_data,
+            &spl_token::ID,
+            false,
+            0,
+        );
+        let authority = AccountInfo::new(
+            &authority_key,
+            false,
+            false,
+            &mut authority_lamports,
+            &mut authority_data,
+            &authority_key,
+            false,
+            0,
+        );
+        check_delegation(&source, &authority, amount)
+    }
+
+    #[test]
+    fn delegation_must_cover_the_amount() {
+        assert_eq!(check(true, 100, 100), Ok(()));
+        assert_eq!(
+            check(true, 99, 100),
+            Err(error!(SolendAnchorError::InsufficientDelegation).into())
+        );
+    }
+
+    #[test]
+    fn delegation_must_be_to_the_transfer_authority() {
+        assert_eq!(
+            check(false, 100, 100),
+            Err(error!(SolendAnchorError::InsufficientDelegation).into())
+        );
+    }
+}
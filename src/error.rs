@@ -0,0 +1,61 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum SolendAnchorError {
+    #[msg("Target LTV must be non-zero and below the reserve loan to value ratio")]
+    InvalidTargetLtv,
+    #[msg("Leverage loop count must be greater than zero")]
+    InvalidLoopCount,
+    #[msg("Position is already at or below the target LTV")]
+    NothingToDeleverage,
+    #[msg("Obligation references a reserve that was not provided")]
+    MissingReserve,
+    #[msg("Swap output is below the oracle price less the allowed slippage")]
+    SlippageExceeded,
+    #[msg("Not enough liquidity left to repay the flash loan and its fee")]
+    InsufficientFlashLoanRepayment,
+    #[msg("Obligation would end up within the safety margin of its liquidation threshold")]
+    UnsafeLoanToValue,
+    #[msg("Oracle is the null oracle placeholder")]
+    NullOracle,
+    #[msg("Oracle account could not be parsed or holds an invalid price")]
+    InvalidOracle,
+    #[msg("Oracle price is stale")]
+    StaleOracle,
+    #[msg("Oracle confidence interval is too wide")]
+    OracleConfidenceTooWide,
+    #[msg("Reserve belongs to a different lending market")]
+    LendingMarketMismatch,
+    #[msg("Pyth oracle does not match the reserve's configured Pyth oracle")]
+    PythOracleMismatch,
+    #[msg("Switchboard oracle does not match the reserve's configured Switchboard oracle")]
+    SwitchboardOracleMismatch,
+    #[msg("Pyth oracle is not owned by the lending market's Pyth program")]
+    PythOracleOwnerMismatch,
+    #[msg("Switchboard oracle is not owned by the lending market's Switchboard program")]
+    SwitchboardOracleOwnerMismatch,
+    #[msg("Host fee receiver does not hold the reserve's liquidity mint")]
+    HostFeeReceiverMintMismatch,
+    #[msg("Host fee account is not the partner's host fee account")]
+    InvalidHostFeeAccount,
+    #[msg("Settlement destination is not a partner token account of the host fee mint")]
+    InvalidSettlementDestination,
+    #[msg("Deposit would exceed the reserve deposit limit")]
+    DepositLimitExceeded,
+    #[msg("Borrow would exceed the reserve borrow limit or available liquidity")]
+    BorrowLimitExceeded,
+    #[msg("Obligation already holds the maximum number of deposit and borrow reserves")]
+    ObligationReserveLimit,
+    #[msg("Source token account has not delegated enough to the transfer authority")]
+    InsufficientDelegation,
+    #[msg("CPI program is not the Solend program")]
+    InvalidSolendProgram,
+    #[msg("Obligation has no deposit or borrow in the given reserve")]
+    ReserveNotInObligation,
+    #[msg("Obligation cannot withdraw any collateral and stay under its borrow limit")]
+    NothingWithdrawable,
+    #[msg("Obligation has no debt in the repay reserve")]
+    NothingToRepay,
+    #[msg("Native mint account is not the wrapped SOL mint")]
+    InvalidNativeMint,
+}
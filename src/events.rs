@@ -0,0 +1,90 @@
+//! Events emitted by the CPI wrappers when the `events` feature is enabled.
+//! `source_delta` is what left the wrapper's source token account and
+//! `destination_delta` what arrived in its destination token account, measured
+//! around the CPI.
+
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct DepositReserveLiquidityEvent {
+    pub reserve: Pubkey,
+    pub liquidity_amount: u64,
+    pub source_delta: u64,
+    pub destination_delta: u64,
+}
+
+#[event]
+pub struct RedeemReserveCollateralEvent {
+    pub reserve: Pubkey,
+    pub collateral_amount: u64,
+    pub source_delta: u64,
+    pub destination_delta: u64,
+}
+
+#[event]
+pub struct DepositObligationCollateralEvent {
+    pub reserve: Pubkey,
+    pub obligation: Pubkey,
+    pub collateral_amount: u64,
+    pub source_delta: u64,
+    pub destination_delta: u64,
+}
+
+#[event]
+pub struct WithdrawObligationCollateralEvent {
+    pub reserve: Pubkey,
+    pub obligation: Pubkey,
+    pub collateral_amount: u64,
+    pub source_delta: u64,
+    pub destination_delta: u64,
+}
+
+#[event]
+pub struct BorrowObligationLiquidityEvent {
+    pub reserve: Pubkey,
+    pub obligation: Pubkey,
+    pub liquidity_amount: u64,
+    pub source_delta: u64,
+    pub destination_delta: u64,
+}
+
+#[event]
+pub struct RepayObligationLiquidityEvent {
+    pub reserve: Pubkey,
+    pub obligation: Pubkey,
+    pub liquidity_amount: u64,
+    pub source_delta: u64,
+    pub destination_delta: u64,
+}
+
+/// The source is the liquidator's liquidity, the destination its collateral
+#[event]
+pub struct LiquidateObligationEvent {
+    pub repay_reserve: Pubkey,
+    pub withdraw_reserve: Pubkey,
+    pub obligation: Pubkey,
+    pub liquidity_amount: u64,
+    pub source_delta: u64,
+    pub destination_delta: u64,
+}
+
+/// The flash borrowed amount comes back within the CPI, so only the fee is
+/// reported: `fee_paid` left the borrower's destination account and
+/// `protocol_fee_received` arrived in the reserve's fee receiver. The host's
+/// share, if any, is the difference.
+#[event]
+pub struct FlashLoanEvent {
+    pub reserve: Pubkey,
+    pub amount: u64,
+    pub fee_paid: u64,
+    pub protocol_fee_received: u64,
+}
+
+#[event]
+pub struct DepositReserveLiquidityAndObligationCollateralEvent {
+    pub reserve: Pubkey,
+    pub obligation: Pubkey,
+    pub liquidity_amount: u64,
+    pub source_delta: u64,
+    pub destination_delta: u64,
+}
@@ -0,0 +1,180 @@
+use anchor_lang::{
+    error,
+    prelude::CpiContext,
+    solana_program::{entrypoint::ProgramResult, program_error::ProgramError, program_pack::Pack},
+    Key,
+};
+use solend_token_lending::{
+    math::{Decimal, TryAdd, TryDiv, TryMul, TrySub},
+    state::{Obligation, Reserve},
+};
+
+use crate::{
+    borrow_obligation_liquidity, error::SolendAnchorError, liquidation::liquidity_market_value,
+    withdraw_obligation_collateral, BorrowObligationLiquidityAccounts,
+    WithdrawObligationCollateralAccounts,
+};
+
+/// Fails unless `borrowed_value / deposited_value` is at least `safety_margin`
+/// percentage points below the liquidation threshold `unhealthy_borrow_value / deposited_value`
+pub fn check_safety_margin(
+    deposited_value: Decimal,
+    borrowed_value: Decimal,
+    unhealthy_borrow_value: Decimal,
+    safety_margin: u8,
+) -> ProgramResult {
+    let margin_value = deposited_value.try_mul(Decimal::from_percent(safety_margin))?;
+    let max_borrow_value = unhealthy_borrow_value
+        .try_sub(margin_value)
+        .unwrap_or_else(|_| Decimal::zero());
+    if borrowed_value > max_borrow_value {
+        return Err(error!(SolendAnchorError::UnsafeLoanToValue).into());
+    }
+    Ok(())
+}
+
+/// Borrows, then re-reads the obligation and fails the transaction if the new debt
+/// leaves it within `safety_margin` percentage points of its liquidation threshold.
+/// The obligation must have been refreshed in this slot, as for any borrow.
+pub fn borrow_obligation_liquidity_guarded<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, BorrowObligationLiquidityAccounts<'info>>,
+    liquidity_amount: u64,
+    safety_margin: u8,
+) -> ProgramResult {
+    let obligation_info = ctx.accounts.obligation_pubkey.clone();
+    let reserve_info = ctx.accounts.borrow_reserve_pubkey.clone();
+    borrow_obligation_liquidity(ctx, liquidity_amount)?;
+
+    // Solend only marks the obligation stale after a borrow, so revalue the
+    // borrowed reserve's debt and keep the other values from the refresh
+    let obligation = Obligation::unpack(&obligation_info.try_borrow_data()?)?;
+    let reserve = Reserve::unpack(&reserve_info.try_borrow_data()?)?;
+    let mut borrowed_value = Decimal::zero();
+    for liquidity in &obligation.borrows {
+        let market_value = if liquidity.borrow_reserve == reserve_info.key() {
+            liquidity_market_value(&reserve, liquidity.borrowed_amount_wads)?
+        } else {
+            liquidity.market_value
+        };
+        borrowed_value = borrowed_value.try_add(market_value)?;
+    }
+
+    check_safety_margin(
+        obligation.deposited_value,
+        borrowed_value,
+        obligation.unhealthy_borrow_value,
+        safety_margin,
+    )
+}
+
+/// Withdraws, then re-reads the obligation and fails the transaction if the smaller
+/// collateral leaves it within `safety_margin` percentage points of its liquidation
+/// threshold. The obligation must have been refreshed in this slot, as for any withdraw.
+pub fn withdraw_obligation_collateral_guarded<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, WithdrawObligationCollateralAccounts<'info>>,
+    collateral_amount: u64,
+    safety_margin: u8,
+) -> ProgramResult {
+    let obligation_info = ctx.accounts.obligation_pubkey.clone();
+    let reserve_info = ctx.accounts.withdraw_reserve_pubkey.clone();
+    let deposit_before = Obligation::unpack(&obligation_info.try_borrow_data()?)?
+        .deposits
+        .into_iter()
+        .find(|collateral| collateral.deposit_reserve == reserve_info.key())
+        .ok_or_else(|| ProgramError::from(error!(SolendAnchorError::MissingReserve)))?;
+    withdraw_obligation_collateral(ctx, collateral_amount)?;
+
+    let obligation = Obligation::unpack(&obligation_info.try_borrow_data()?)?;
+    let deposited_amount_after = obligation
+        .deposits
+        .iter()
+        .find(|collateral| collateral.deposit_reserve == reserve_info.key())
+        .map(|collateral| collateral.deposited_amount)
+        .unwrap_or(0);
+    let withdrawn_amount = deposit_before
+        .deposited_amount
+        .saturating_sub(deposited_amount_after);
+    if withdrawn_amount == 0 {
+        return Ok(());
+    }
+
+    // Solend only marks the obligation stale after a withdraw, so take the
+    // withdrawn share of the collateral's refreshed value out by hand
+    let reserve = Reserve::unpack(&reserve_info.try_borrow_data()?)?;
+    let withdrawn_value = deposit_before
+        .market_value
+        .try_mul(withdrawn_amount)?
+        .try_div(deposit_before.deposited_amount)?;
+    let deposited_value = obligation
+        .deposited_value
+        .try_sub(withdrawn_value)
+        .unwrap_or_else(|_| Decimal::zero());
+    let withdrawn_unhealthy_value =
+        withdrawn_value.try_mul(Decimal::from_percent(reserve.config.liquidation_threshold))?;
+    let unhealthy_borrow_value = obligation
+        .unhealthy_borrow_value
+        .try_sub(withdrawn_unhealthy_value)
+        .unwrap_or_else(|_| Decimal::zero());
+
+    check_safety_margin(
+        deposited_value,
+        obligation.borrowed_value,
+        unhealthy_borrow_value,
+        safety_margin,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// $1000 of collateral with a 55% liquidation threshold, checked 5 points below it
+    fn check(borrowed_value: u64) -> ProgramResult {
+        check_safety_margin(
+            Decimal::from(1_000u64),
+            Decimal::from(borrowed_value),
+            Decimal::from(550u64),
+            5,
+        )
+    }
+
+    #[test]
+    fn borrows_at_the_margin_pass() {
+        assert_eq!(check(500), Ok(()));
+    }
+
+    #[test]
+    fn borrows_just_under_the_margin_pass() {
+        assert_eq!(check(499), Ok(()));
+    }
+
+    #[test]
+    fn borrows_just_over_the_margin_fail() {
+        assert_eq!(
+            check(501),
+            Err(error!(SolendAnchorError::UnsafeLoanToValue).into())
+        );
+    }
+
+    #[test]
+    fn no_borrows_pass() {
+        assert_eq!(check(0), Ok(()));
+    }
+
+    #[test]
+    fn margin_wider_than_the_threshold_allows_no_borrows() {
+        let check = |borrowed_value: u64| {
+            check_safety_margin(
+                Decimal::from(1_000u64),
+                Decimal::from(borrowed_value),
+                Decimal::from(550u64),
+                60,
+            )
+        };
+        assert_eq!(check(0), Ok(()));
+        assert_eq!(
+            check(1),
+            Err(error!(SolendAnchorError::UnsafeLoanToValue).into())
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use anchor_lang::{
+    error,
+    prelude::{AccountInfo, Clock, CpiContext, Program, Sysvar},
+    solana_program::{entrypoint::ProgramResult, program_error::ProgramError, program_pack::Pack},
+    Accounts, Key, ToAccountInfo, ToAccountInfos, ToAccountMetas,
+};
+use solend_token_lending::{
+    math::Decimal,
+    state::{FeeCalculation, Reserve},
+};
+
+use crate::{
+    borrow_obligation_liquidity, error::SolendAnchorError, BorrowObligationLiquidityAccounts,
+    TokenProgram,
+};
+
+/// How an origination fee is shared between the reserve's fee receiver and the host
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FeeSplit {
+    pub total_fee: u64,
+    /// Paid to the host fee receiver, when one is passed
+    pub host_fee: u64,
+    /// Left for the reserve's fee receiver
+    pub protocol_fee: u64,
+}
+
+/// Fee charged on top of borrowing `liquidity_amount` and the host's share of it.
+/// Meaningless for Solend's `u64::MAX` borrow-the-maximum sentinel.
+pub fn borrow_fee_split(
+    reserve: &Reserve,
+    liquidity_amount: u64,
+) -> Result<FeeSplit, ProgramError> {
+    let (total_fee, host_fee) = reserve
+        .config
+        .fees
+        .calculate_borrow_fees(Decimal::from(liquidity_amount), FeeCalculation::Exclusive)?;
+    Ok(FeeSplit {
+        total_fee,
+        host_fee,
+        protocol_fee: total_fee - host_fee,
+    })
+}
+
+/// Fee charged on a flash loan of `amount` and the host's share of it
+pub fn flash_loan_fee_split(reserve: &Reserve, amount: u64) -> Result<FeeSplit, ProgramError> {
+    let (total_fee, host_fee) = reserve
+        .config
+        .fees
+        .calculate_flash_loan_fees(Decimal::from(amount))?;
+    Ok(FeeSplit {
+        total_fee,
+        host_fee,
+        protocol_fee: total_fee - host_fee,
+    })
+}
+
+/// Fails unless `host_fee_receiver` is a token account of the reserve's liquidity mint
+pub fn check_host_fee_receiver(
+    reserve: &Reserve,
+    host_fee_receiver: &AccountInfo,
+) -> ProgramResult {
+    let account = spl_token::state::Account::unpack(&host_fee_receiver.try_borrow_data()?)?;
+    if account.mint != reserve.liquidity.mint_pubkey {
+        return Err(error!(SolendAnchorError::HostFeeReceiverMintMismatch).into());
+    }
+    Ok(())
+}
+
+/// `BorrowObligationLiquidityAccounts` with the host fee receiver as a named account
+#[derive(Accounts)]
+pub struct BorrowWithHostFeeAccounts<'info> {
+    pub source_liquidity_pubkey: AccountInfo<'info>,
+    pub destination_liquidity_pubkey: AccountInfo<'info>,
+    pub borrow_reserve_pubkey: AccountInfo<'info>,
+    pub borrow_reserve_liquidity_fee_receiver_pubkey: AccountInfo<'info>,
+    pub obligation_pubkey: AccountInfo<'info>,
+    pub lending_market_pubkey: AccountInfo<'info>,
+    pub lending_market_authority_pubkey: AccountInfo<'info>,
+    pub obligation_owner_pubkey: AccountInfo<'info>,
+    pub host_fee_receiver_pubkey: AccountInfo<'info>,
+    pub clock_sysvar: Sysvar<'info, Clock>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+/// Borrows with the host share of the origination fee going to `host_fee_receiver_pubkey`,
+/// after checking it holds the reserve's liquidity mint. The remaining accounts of `ctx`
+/// are not forwarded, so they stay free for the calling program.
+pub fn borrow_obligation_liquidity_with_host_fee<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, BorrowWithHostFeeAccounts<'info>>,
+    liquidity_amount: u64,
+) -> Result<FeeSplit, ProgramError> {
+    let reserve = Reserve::unpack(&ctx.accounts.borrow_reserve_pubkey.try_borrow_data()?)?;
+    check_host_fee_receiver(&reserve, &ctx.accounts.host_fee_receiver_pubkey)?;
+    let fee_split = borrow_fee_split(&reserve, liquidity_amount)?;
+
+    borrow_obligation_liquidity(
+        CpiContext::new_with_signer(
+            ctx.program.clone(),
+            BorrowObligationLiquidityAccounts {
+                source_liquidity_pubkey: ctx.accounts.source_liquidity_pubkey.clone(),
+                destination_liquidity_pubkey: ctx.accounts.destination_liquidity_pubkey.clone(),
+                borrow_reserve_pubkey: ctx.accounts.borrow_reserve_pubkey.clone(),
+                borrow_reserve_liquidity_fee_receiver_pubkey: ctx
+                    .accounts
+                    .borrow_reserve_liquidity_fee_receiver_pubkey
+                    .clone(),
+                obligation_pubkey: ctx.accounts.obligation_pubkey.clone(),
+                lending_market_pubkey: ctx.accounts.lending_market_pubkey.clone(),
+                lending_market_authority_pubkey: ctx
+                    .accounts
+                    .lending_market_authority_pubkey
+                    .clone(),
+                obligation_owner_pubkey: ctx.accounts.obligation_owner_pubkey.clone(),
+                clock_sysvar: ctx.accounts.clock_sysvar.clone(),
+                token_program: ctx.accounts.token_program.clone(),
+            },
+            ctx.signer_seeds,
+        )
+        .with_remaining_accounts(vec![ctx.accounts.host_fee_receiver_pubkey.to_account_info()]),
+        liquidity_amount,
+    )?;
+    Ok(fee_split)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE_PERCENT_WAD: u64 = 10_000_000_000_000_000;
+
+    /// Reserve charging `borrow_fee_wad` on borrows and `flash_loan_fee_wad` on flash
+    /// loans, `host_fee_percentage` of either going to the host
+    fn reserve(borrow_fee_wad: u64, flash_loan_fee_wad: u64, host_fee_percentage: u8) -> Reserve {
+        let mut reserve = Reserve::default();
+        reserve.config.fees.borrow_fee_wad = borrow_fee_wad;
+        reserve.config.fees.flash_loan_fee_wad = flash_loan_fee_wad;
+        reserve.config.fees.host_fee_percentage = host_fee_percentage;
+        reserve
+    }
+
+    #[test]
+    fn borrow_fee_is_charged_on_top_and_shared_with_the_host() {
+        assert_eq!(
+            borrow_fee_split(&reserve(ONE_PERCENT_WAD, 0, 20), 1_000_000),
+            Ok(FeeSplit {
+                total_fee: 10_000,
+                host_fee: 2_000,
+                protocol_fee: 8_000,
+            })
+        );
+    }
+
+    #[test]
+    fn borrow_fee_goes_to_the_protocol_without_a_host_share() {
+        assert_eq!(
+            borrow_fee_split(&reserve(ONE_PERCENT_WAD, 0, 0), 1_000_000),
+            Ok(FeeSplit {
+                total_fee: 10_000,
+                host_fee: 0,
+                protocol_fee: 10_000,
+            })
+        );
+    }
+
+    #[test]
+    fn borrow_fee_is_zero_when_the_reserve_charges_none() {
+        assert_eq!(
+            borrow_fee_split(&reserve(0, 0, 20), 1_000_000),
+            Ok(FeeSplit::default())
+        );
+    }
+
+    #[test]
+    fn small_borrow_pays_the_minimum_fee_to_both_receivers() {
+        let fee_split = borrow_fee_split(&reserve(ONE_PERCENT_WAD, 0, 20), 100).unwrap();
+        assert_eq!(fee_split.total_fee, 2);
+        assert_eq!(fee_split.host_fee, 1);
+        assert_eq!(fee_split.protocol_fee, 1);
+    }
+
+    #[test]
+    fn flash_loan_fee_uses_the_flash_loan_rate() {
+        // 0.3% flash loan fee
+        let reserve = reserve(ONE_PERCENT_WAD, 3_000_000_000_000_000, 20);
+        assert_eq!(
+            flash_loan_fee_split(&reserve, 1_000_000),
+            Ok(FeeSplit {
+                total_fee: 3_000,
+                host_fee: 600,
+                protocol_fee: 2_400,
+            })
+        );
+    }
+
+    #[test]
+    fn flash_loan_fee_is_zero_when_the_reserve_charges_none() {
+        assert_eq!(
+            flash_loan_fee_split(&reserve(ONE_PERCENT_WAD, 0, 20), 1_000_000),
+            Ok(FeeSplit::default())
+        );
+    }
+}
@@ -0,0 +1,527 @@
+use std::str::FromStr;
+
+use anchor_lang::{prelude::Pubkey, solana_program::program_error::ProgramError};
+
+use crate::{
+    account_source::SourceResult,
+    decoder::{decode_instruction, SolendInstruction},
+};
+
+/// A Solend instruction invoked through CPI, with the program that invoked it
+#[derive(Clone, Debug, PartialEq)]
+pub struct SolendCpi {
+    /// Index of the top-level instruction the CPI happened under
+    pub outer_index: usize,
+    pub caller_program: Pubkey,
+    pub instruction: SolendInstruction,
+}
+
+/// A Solend CPI whose data or accounts did not decode
+#[derive(Clone, Debug, PartialEq)]
+pub struct UndecodedCpi {
+    pub outer_index: usize,
+    /// Position among the inner instructions of `outer_index`
+    pub inner_index: usize,
+    pub caller_program: Pubkey,
+    pub error: ProgramError,
+}
+
+/// The Solend CPIs of a transaction. `failures` holds the ones that did not decode, so a
+/// transaction without Solend CPIs can be told apart from one with undecodable ones.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CpiScanResult {
+    pub cpis: Vec<SolendCpi>,
+    pub failures: Vec<UndecodedCpi>,
+}
+
+/// `CpiScanResult` with the decoded CPIs reduced to events
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventScanResult {
+    pub events: Vec<(SolendCpi, SolendEvent)>,
+    pub failures: Vec<UndecodedCpi>,
+}
+
+/// What a Solend CPI did, reduced to the fields indexers care about
+#[derive(Clone, Debug, PartialEq)]
+pub enum SolendEvent {
+    DepositLiquidity {
+        reserve: Pubkey,
+        obligation: Option<Pubkey>,
+        liquidity_amount: u64,
+    },
+    DepositCollateral {
+        reserve: Pubkey,
+        obligation: Pubkey,
+        collateral_amount: u64,
+    },
+    Borrow {
+        reserve: Pubkey,
+        obligation: Pubkey,
+        liquidity_amount: u64,
+    },
+    Repay {
+        reserve: Pubkey,
+        obligation: Pubkey,
+        liquidity_amount: u64,
+    },
+    Liquidate {
+        obligation: Pubkey,
+        repay_reserve: Pubkey,
+        withdraw_reserve: Pubkey,
+        liquidity_amount: u64,
+        liquidator: Pubkey,
+    },
+    FlashLoan {
+        reserve: Pubkey,
+        amount: u64,
+        receiver_program: Pubkey,
+    },
+}
+
+impl SolendEvent {
+    /// Refreshes, admin and withdraw instructions have no event
+    pub fn from_instruction(instruction: &SolendInstruction) -> Option<Self> {
+        match instruction {
+            SolendInstruction::DepositReserveLiquidity {
+                liquidity_amount,
+                reserve,
+                ..
+            } => Some(SolendEvent::DepositLiquidity {
+                reserve: *reserve,
+                obligation: None,
+                liquidity_amount: *liquidity_amount,
+            }),
+            SolendInstruction::DepositReserveLiquidityAndObligationCollateral {
+                liquidity_amount,
+                reserve,
+                obligation,
+                ..
+            } => Some(SolendEvent::DepositLiquidity {
+                reserve: *reserve,
+                obligation: Some(*obligation),
+                liquidity_amount: *liquidity_amount,
+            }),
+            SolendInstruction::DepositObligationCollateral {
+                collateral_amount,
+                deposit_reserve,
+                obligation,
+                ..
+            } => Some(SolendEvent::DepositCollateral {
+                reserve: *deposit_reserve,
+                obligation: *obligation,
+                collateral_amount: *collateral_amount,
+            }),
+            SolendInstruction::BorrowObligationLiquidity {
+                liquidity_amount,
+                borrow_reserve,
+                obligation,
+                ..
+            } => Some(SolendEvent::Borrow {
+                reserve: *borrow_reserve,
+                obligation: *obligation,
+                liquidity_amount: *liquidity_amount,
+            }),
+            SolendInstruction::RepayObligationLiquidity {
+                liquidity_amount,
+                repay_reserve,
+                obligation,
+                ..
+            } => Some(SolendEvent::Repay {
+                reserve: *repay_reserve,
+                obligation: *obligation,
+                liquidity_amount: *liquidity_amount,
+            }),
+            SolendInstruction::LiquidateObligation {
+                liquidity_amount,
+                repay_reserve,
+                withdraw_reserve,
+                obligation,
+                user_transfer_authority,
+                ..
+            } => Some(SolendEvent::Liquidate {
+                obligation: *obligation,
+                repay_reserve: *repay_reserve,
+                withdraw_reserve: *withdraw_reserve,
+                liquidity_amount: *liquidity_amount,
+                liquidator: *user_transfer_authority,
+            }),
+            SolendInstruction::FlashLoan {
+                amount,
+                reserve,
+                flash_loan_receiver_program_id,
+                ..
+            } => Some(SolendEvent::FlashLoan {
+                reserve: *reserve,
+                amount: *amount,
+                receiver_program: *flash_loan_receiver_program_id,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Account keys of a `getTransaction` result, lookup table addresses appended
+fn account_keys(transaction: &serde_json::Value) -> SourceResult<Vec<Pubkey>> {
+    let message = &transaction["transaction"]["message"];
+    let mut keys: Vec<&serde_json::Value> = message["accountKeys"]
+        .as_array()
+        .ok_or("transaction has no accountKeys")?
+        .iter()
+        .collect();
+    for loaded in ["writable", "readonly"] {
+        if let Some(addresses) = transaction["meta"]["loadedAddresses"][loaded].as_array() {
+            keys.extend(addresses);
+        }
+    }
+
+    keys.into_iter()
+        .map(|key| -> SourceResult<Pubkey> {
+            // `jsonParsed` encoding wraps each key in an object
+            let key = key
+                .as_str()
+                .or_else(|| key["pubkey"].as_str())
+                .ok_or("malformed account key")?;
+            Ok(Pubkey::from_str(key)?)
+        })
+        .collect()
+}
+
+fn key_at(keys: &[Pubkey], index: &serde_json::Value) -> SourceResult<Pubkey> {
+    let index = index.as_u64().ok_or("malformed account index")? as usize;
+    Ok(*keys.get(index).ok_or("account index out of range")?)
+}
+
+/// `jsonParsed` encoding names the program and accounts by address, `json` by index
+fn key(keys: &[Pubkey], value: &serde_json::Value) -> SourceResult<Pubkey> {
+    match value.as_str() {
+        Some(key) => Ok(Pubkey::from_str(key)?),
+        None => key_at(keys, value),
+    }
+}
+
+fn instruction_program(instruction: &serde_json::Value, keys: &[Pubkey]) -> SourceResult<Pubkey> {
+    match instruction.get("programId") {
+        Some(program_id) => key(keys, program_id),
+        None => key_at(keys, &instruction["programIdIndex"]),
+    }
+}
+
+/// Accounts and data of an instruction the RPC did not parse, which is always the case
+/// for Solend
+fn instruction_accounts_and_data(
+    instruction: &serde_json::Value,
+    keys: &[Pubkey],
+) -> SourceResult<(Vec<Pubkey>, Vec<u8>)> {
+    let accounts = instruction["accounts"]
+        .as_array()
+        .ok_or("instruction has no accounts")?
+        .iter()
+        .map(|account| key(keys, account))
+        .collect::<SourceResult<Vec<_>>>()?;
+    let data = bs58::decode(instruction["data"].as_str().ok_or("instruction has no data")?)
+        .into_vec()?;
+    Ok((accounts, data))
+}
+
+/// Finds the Solend instructions invoked through CPI in a saved `getTransaction` result
+/// (`json` or `jsonParsed` encoding, either the full JSON-RPC response or its `result`).
+/// When the RPC reports `stackHeight` the caller is the invoking inner instruction's
+/// program, otherwise it is the top-level instruction's program.
+pub fn solend_cpis(
+    transaction: &serde_json::Value,
+    solend_program_id: &Pubkey,
+) -> SourceResult<CpiScanResult> {
+    let transaction = transaction.get("result").unwrap_or(transaction);
+    let keys = account_keys(transaction)?;
+    let outer_instructions = transaction["transaction"]["message"]["instructions"]
+        .as_array()
+        .ok_or("transaction has no instructions")?;
+    let inner_groups = match transaction["meta"]["innerInstructions"].as_array() {
+        Some(inner_groups) => inner_groups,
+        None => return Ok(CpiScanResult::default()),
+    };
+
+    let mut scan = CpiScanResult::default();
+    for group in inner_groups {
+        let outer_index =
+            group["index"].as_u64().ok_or("inner instructions without index")? as usize;
+        let outer = outer_instructions
+            .get(outer_index)
+            .ok_or("inner instruction index out of range")?;
+        let outer_program = instruction_program(outer, &keys)?;
+
+        // Program at each stack height, the top-level instruction being height 1
+        let mut stack = vec![outer_program];
+        let inner_instructions = group["instructions"].as_array().into_iter().flatten();
+        for (inner_index, inner) in inner_instructions.enumerate() {
+            let program_id = instruction_program(inner, &keys)?;
+            let caller_program = match inner["stackHeight"].as_u64() {
+                Some(height) if height >= 2 => {
+                    stack.truncate(height as usize - 1);
+                    let caller = *stack.last().unwrap_or(&outer_program);
+                    stack.push(program_id);
+                    caller
+                }
+                _ => outer_program,
+            };
+
+            if program_id != *solend_program_id {
+                continue;
+            }
+            let (accounts, data) = instruction_accounts_and_data(inner, &keys)?;
+            match decode_instruction(&data, &accounts) {
+                Ok(instruction) => scan.cpis.push(SolendCpi {
+                    outer_index,
+                    caller_program,
+                    instruction,
+                }),
+                Err(error) => scan.failures.push(UndecodedCpi {
+                    outer_index,
+                    inner_index,
+                    caller_program,
+                    error,
+                }),
+            }
+        }
+    }
+    Ok(scan)
+}
+
+/// The normalized event stream of a transaction's Solend CPIs, in execution order
+pub fn solend_events(
+    transaction: &serde_json::Value,
+    solend_program_id: &Pubkey,
+) -> SourceResult<EventScanResult> {
+    let scan = solend_cpis(transaction, solend_program_id)?;
+    Ok(EventScanResult {
+        events: scan
+            .cpis
+            .into_iter()
+            .filter_map(|cpi| {
+                SolendEvent::from_instruction(&cpi.instruction).map(|event| (cpi, event))
+            })
+            .collect(),
+        failures: scan.failures,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::solana_program::{instruction::Instruction, sysvar};
+    use serde_json::{json, Value};
+    use solend_token_lending::instruction as solend_ix;
+
+    use super::*;
+
+    struct Fixture {
+        keys: Vec<Pubkey>,
+        caller: Pubkey,
+        middle: Pubkey,
+        reserve: Pubkey,
+        pyth: Pubkey,
+        switchboard: Pubkey,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let [payer, caller, middle, reserve, pyth, switchboard] =
+                [(); 6].map(|_| Pubkey::new_unique());
+            Self {
+                keys: vec![
+                    payer,
+                    caller,
+                    middle,
+                    solend_token_lending::id(),
+                    reserve,
+                    pyth,
+                    switchboard,
+                    sysvar::clock::id(),
+                ],
+                caller,
+                middle,
+                reserve,
+                pyth,
+                switchboard,
+            }
+        }
+
+        fn index(&self, key: &Pubkey) -> usize {
+            self.keys.iter().position(|k| k == key).unwrap()
+        }
+
+        fn refresh_reserve(&self) -> Instruction {
+            solend_ix::refresh_reserve(
+                solend_token_lending::id(),
+                self.reserve,
+                self.pyth,
+                self.switchboard,
+            )
+        }
+
+        fn refresh_reserve_cpi(&self, caller_program: Pubkey) -> SolendCpi {
+            SolendCpi {
+                outer_index: 0,
+                caller_program,
+                instruction: SolendInstruction::RefreshReserve {
+                    reserve: self.reserve,
+                    reserve_liquidity_pyth_oracle: self.pyth,
+                    reserve_liquidity_switchboard_oracle: self.switchboard,
+                },
+            }
+        }
+
+        /// `json` encoding of `instruction`, at `stack_height` when given
+        fn json(&self, instruction: &Instruction, stack_height: Option<u64>) -> Value {
+            let mut value = json!({
+                "programIdIndex": self.index(&instruction.program_id),
+                "accounts": instruction
+                    .accounts
+                    .iter()
+                    .map(|meta| self.index(&meta.pubkey))
+                    .collect::<Vec<_>>(),
+                "data": bs58::encode(&instruction.data).into_string(),
+            });
+            if let Some(stack_height) = stack_height {
+                value["stackHeight"] = json!(stack_height);
+            }
+            value
+        }
+
+        fn call(&self, program_id: Pubkey) -> Instruction {
+            Instruction::new_with_bytes(program_id, &[1, 2, 3], vec![])
+        }
+
+        fn transaction(&self, inner_instructions: Vec<Value>) -> Value {
+            json!({
+                "transaction": {
+                    "message": {
+                        "accountKeys": self
+                            .keys
+                            .iter()
+                            .map(|key| key.to_string())
+                            .collect::<Vec<_>>(),
+                        "instructions": [self.json(&self.call(self.caller), None)],
+                    },
+                },
+                "meta": {
+                    "innerInstructions": [{ "index": 0, "instructions": inner_instructions }],
+                },
+            })
+        }
+    }
+
+    #[test]
+    fn stack_height_attributes_each_cpi_to_its_caller() {
+        let fixture = Fixture::new();
+        let transaction = fixture.transaction(vec![
+            fixture.json(&fixture.call(fixture.middle), Some(2)),
+            fixture.json(&fixture.refresh_reserve(), Some(3)),
+            fixture.json(&fixture.refresh_reserve(), Some(2)),
+        ]);
+        let scan = solend_cpis(&json!({ "result": transaction }), &solend_token_lending::id())
+            .unwrap();
+        assert_eq!(
+            scan.cpis,
+            vec![
+                fixture.refresh_reserve_cpi(fixture.middle),
+                fixture.refresh_reserve_cpi(fixture.caller),
+            ]
+        );
+        assert!(scan.failures.is_empty());
+    }
+
+    #[test]
+    fn without_stack_height_the_caller_is_the_top_level_program() {
+        let fixture = Fixture::new();
+        let transaction = fixture.transaction(vec![
+            fixture.json(&fixture.call(fixture.middle), None),
+            fixture.json(&fixture.refresh_reserve(), None),
+        ]);
+        let scan = solend_cpis(&transaction, &solend_token_lending::id()).unwrap();
+        assert_eq!(scan.cpis, vec![fixture.refresh_reserve_cpi(fixture.caller)]);
+    }
+
+    #[test]
+    fn undecodable_solend_cpis_are_reported() {
+        let fixture = Fixture::new();
+        let transaction = fixture.transaction(vec![
+            fixture.json(&fixture.refresh_reserve(), Some(2)),
+            fixture.json(&fixture.call(solend_token_lending::id()), Some(2)),
+        ]);
+        let scan = solend_cpis(&transaction, &solend_token_lending::id()).unwrap();
+        assert_eq!(scan.cpis, vec![fixture.refresh_reserve_cpi(fixture.caller)]);
+        assert_eq!(scan.failures.len(), 1);
+        assert_eq!(scan.failures[0].outer_index, 0);
+        assert_eq!(scan.failures[0].inner_index, 1);
+        assert_eq!(scan.failures[0].caller_program, fixture.caller);
+    }
+
+    #[test]
+    fn json_parsed_transactions_are_read_by_address() {
+        let fixture = Fixture::new();
+        let refresh_reserve = fixture.refresh_reserve();
+        let transaction = json!({
+            "transaction": {
+                "message": {
+                    "accountKeys": fixture
+                        .keys
+                        .iter()
+                        .map(|key| json!({ "pubkey": key.to_string(), "signer": false }))
+                        .collect::<Vec<_>>(),
+                    "instructions": [{
+                        "programId": fixture.caller.to_string(),
+                        "accounts": [],
+                        "data": "",
+                    }],
+                },
+            },
+            "meta": {
+                "innerInstructions": [{
+                    "index": 0,
+                    "instructions": [
+                        {
+                            "program": "spl-token",
+                            "programId": spl_token::id().to_string(),
+                            "parsed": { "type": "transfer", "info": {} },
+                            "stackHeight": 2,
+                        },
+                        {
+                            "programId": solend_token_lending::id().to_string(),
+                            "accounts": refresh_reserve
+                                .accounts
+                                .iter()
+                                .map(|meta| meta.pubkey.to_string())
+                                .collect::<Vec<_>>(),
+                            "data": bs58::encode(&refresh_reserve.data).into_string(),
+                            "stackHeight": 2,
+                        },
+                    ],
+                }],
+            },
+        });
+        let scan = solend_cpis(&transaction, &solend_token_lending::id()).unwrap();
+        assert_eq!(scan.cpis, vec![fixture.refresh_reserve_cpi(fixture.caller)]);
+    }
+
+    #[test]
+    fn transactions_without_inner_instructions_have_no_cpis() {
+        let fixture = Fixture::new();
+        let mut transaction = fixture.transaction(vec![]);
+        transaction["meta"] = json!({});
+        assert_eq!(
+            solend_cpis(&transaction, &solend_token_lending::id()).unwrap(),
+            CpiScanResult::default()
+        );
+    }
+
+    #[test]
+    fn events_skip_instructions_without_one() {
+        let fixture = Fixture::new();
+        let transaction =
+            fixture.transaction(vec![fixture.json(&fixture.refresh_reserve(), Some(2))]);
+        assert_eq!(
+            solend_events(&transaction, &solend_token_lending::id()).unwrap(),
+            EventScanResult::default()
+        );
+    }
+}
@@ -0,0 +1,505 @@
+//! Same-asset leverage built from deposit/borrow rounds, and the matching unwind.
+//! There is no flash loan mode: Solend's `flash_loan` invokes its receiver while Solend
+//! is still on the call stack, and Solana rejects the reentrant deposit and borrow.
+
+use anchor_lang::{
+    error,
+    prelude::{AccountInfo, Clock, CpiContext, Program, Sysvar},
+    solana_program::{entrypoint::ProgramResult, program_error::ProgramError},
+    Accounts, ToAccountInfo,
+};
+use solend_token_lending::{
+    math::{Decimal, TryAdd, TryDiv, TryMul, TrySub},
+    state::{FeeCalculation, Reserve},
+};
+
+use crate::{
+    borrow_obligation_liquidity, deposit_reserve_liquidity_and_obligation_collateral,
+    error::SolendAnchorError, redeem_reserve_collateral, refresh_obligation, refresh_reserve,
+    repay_obligation_liquidity, withdraw_obligation_collateral,
+    BorrowObligationLiquidityAccounts, DepositReserveLiquidityAndObligationCollateralAccounts,
+    RedeemReserveCollateralAccounts, RefreshObligationAccounts,
+    RefreshReserveAccounts, RepayObligationLiquidityAccounts,
+    WithdrawObligationCollateralAccounts, TokenProgram,
+};
+
+/// One deposit followed by an optional borrow of the same asset
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LeverageStep {
+    pub deposit_amount: u64,
+    pub borrow_amount: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LeveragePlan {
+    pub steps: Vec<LeverageStep>,
+    /// Total liquidity deposited, including the initial amount
+    pub total_deposited: u64,
+    /// Total debt taken on, including borrow fees
+    pub total_borrowed: u64,
+}
+
+/// One withdraw, redeem and repay round, in collateral and liquidity units
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DeleverageStep {
+    pub collateral_amount: u64,
+    pub repay_amount: u64,
+}
+
+fn validate_target_ltv(reserve: &Reserve, target_ltv: u8) -> Result<(), ProgramError> {
+    if target_ltv == 0 || target_ltv >= reserve.config.loan_to_value_ratio {
+        return Err(error!(SolendAnchorError::InvalidTargetLtv).into());
+    }
+    Ok(())
+}
+
+/// Debt added to the obligation when borrowing `amount`, borrow fee included
+fn borrow_debt(reserve: &Reserve, amount: u64) -> Result<u64, ProgramError> {
+    let (borrow_fee, _) = reserve
+        .config
+        .fees
+        .calculate_borrow_fees(Decimal::from(amount), FeeCalculation::Exclusive)?;
+    amount
+        .checked_add(borrow_fee)
+        .ok_or(ProgramError::InvalidArgument)
+}
+
+/// Largest borrow whose debt, fee included, fits in `room`
+fn max_borrow_for_debt(reserve: &Reserve, room: u64) -> Result<u64, ProgramError> {
+    let fee_rate = Decimal::from_scaled_val(reserve.config.fees.borrow_fee_wad as u128);
+    let mut amount = Decimal::from(room)
+        .try_div(Decimal::one().try_add(fee_rate)?)?
+        .try_floor_u64()?;
+    while amount > 0 && borrow_debt(reserve, amount)? > room {
+        amount -= 1;
+    }
+    Ok(amount)
+}
+
+/// Plans a same-asset deposit/borrow loop on a fresh obligation. Each round borrows
+/// up to `target_ltv` percent of everything deposited so far and redeposits it, and
+/// the final step only deposits what the last borrow returned.
+pub fn plan_leverage_loops(
+    reserve: &Reserve,
+    initial_amount: u64,
+    target_ltv: u8,
+    loops: u8,
+) -> Result<LeveragePlan, ProgramError> {
+    validate_target_ltv(reserve, target_ltv)?;
+    if loops == 0 {
+        return Err(error!(SolendAnchorError::InvalidLoopCount).into());
+    }
+
+    let mut plan = LeveragePlan::default();
+    let mut next_deposit = initial_amount;
+    for _ in 0..loops {
+        plan.total_deposited = plan
+            .total_deposited
+            .checked_add(next_deposit)
+            .ok_or(ProgramError::InvalidArgument)?;
+        let max_debt = Decimal::from(plan.total_deposited)
+            .try_mul(Decimal::from_percent(target_ltv))?
+            .try_floor_u64()?;
+        let room = max_debt.saturating_sub(plan.total_borrowed);
+        let borrow_amount = max_borrow_for_debt(reserve, room)?;
+
+        plan.steps.push(LeverageStep {
+            deposit_amount: next_deposit,
+            borrow_amount,
+        });
+        plan.total_borrowed = plan
+            .total_borrowed
+            .checked_add(borrow_debt(reserve, borrow_amount)?)
+            .ok_or(ProgramError::InvalidArgument)?;
+        next_deposit = borrow_amount;
+        if borrow_amount == 0 {
+            break;
+        }
+    }
+
+    if next_deposit > 0 {
+        plan.total_deposited = plan
+            .total_deposited
+            .checked_add(next_deposit)
+            .ok_or(ProgramError::InvalidArgument)?;
+        plan.steps.push(LeverageStep {
+            deposit_amount: next_deposit,
+            borrow_amount: 0,
+        });
+    }
+
+    Ok(plan)
+}
+
+/// Plans withdraw/redeem/repay rounds that bring a same-asset position down to
+/// `target_ltv` percent. Every withdrawal keeps the obligation within the
+/// reserve's loan to value ratio so each round passes Solend's health check.
+pub fn plan_deleverage(
+    reserve: &Reserve,
+    deposited_liquidity: u64,
+    borrowed_liquidity: u64,
+    target_ltv: u8,
+    max_loops: u8,
+) -> Result<Vec<DeleverageStep>, ProgramError> {
+    validate_target_ltv(reserve, target_ltv)?;
+    if max_loops == 0 {
+        return Err(error!(SolendAnchorError::InvalidLoopCount).into());
+    }
+
+    let target = Decimal::from_percent(target_ltv);
+    let max_ltv = Decimal::from_percent(reserve.config.loan_to_value_ratio);
+    let exchange_rate = reserve.collateral_exchange_rate()?;
+
+    let mut deposited = deposited_liquidity;
+    let mut borrowed = borrowed_liquidity;
+    if Decimal::from(borrowed) <= Decimal::from(deposited).try_mul(target)? {
+        return Err(error!(SolendAnchorError::NothingToDeleverage).into());
+    }
+
+    let mut steps = vec![];
+    for _ in 0..max_loops {
+        let target_debt = Decimal::from(deposited).try_mul(target)?;
+        if Decimal::from(borrowed) <= target_debt {
+            break;
+        }
+
+        // Withdrawing and repaying w moves the LTV to (B - w) / (D - w)
+        let needed = Decimal::from(borrowed)
+            .try_sub(target_debt)?
+            .try_div(Decimal::one().try_sub(target)?)?
+            .try_ceil_u64()?;
+        let min_deposit = Decimal::from(borrowed).try_div(max_ltv)?.try_ceil_u64()?;
+        let withdrawable = deposited.saturating_sub(min_deposit);
+        let liquidity_amount = needed.min(withdrawable).min(borrowed);
+        if liquidity_amount == 0 {
+            break;
+        }
+
+        let collateral_amount = exchange_rate.liquidity_to_collateral(liquidity_amount)?;
+        let repay_amount = exchange_rate.collateral_to_liquidity(collateral_amount)?;
+        if collateral_amount == 0 || repay_amount == 0 {
+            break;
+        }
+
+        steps.push(DeleverageStep {
+            collateral_amount,
+            repay_amount,
+        });
+        deposited = deposited.saturating_sub(repay_amount);
+        borrowed = borrowed.saturating_sub(repay_amount);
+    }
+
+    Ok(steps)
+}
+
+#[derive(Accounts)]
+pub struct LeverageAccounts<'info> {
+    pub user_liquidity_pubkey: AccountInfo<'info>,
+    pub user_collateral_pubkey: AccountInfo<'info>,
+    pub reserve_pubkey: AccountInfo<'info>,
+    pub reserve_liquidity_supply_pubkey: AccountInfo<'info>,
+    pub reserve_collateral_mint_pubkey: AccountInfo<'info>,
+    pub reserve_collateral_supply_pubkey: AccountInfo<'info>,
+    pub reserve_liquidity_fee_receiver_pubkey: AccountInfo<'info>,
+    pub reserve_liquidity_pyth_oracle_pubkey: AccountInfo<'info>,
+    pub reserve_liquidity_switchboard_oracle_pubkey: AccountInfo<'info>,
+    pub lending_market_pubkey: AccountInfo<'info>,
+    pub lending_market_authority_pubkey: AccountInfo<'info>,
+    pub obligation_pubkey: AccountInfo<'info>,
+    pub obligation_owner_pubkey: AccountInfo<'info>,
+    pub user_transfer_authority_pubkey: AccountInfo<'info>,
+    pub clock_sysvar: Sysvar<'info, Clock>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+impl<'info> LeverageAccounts<'info> {
+    fn refresh_reserve_accounts(&self) -> RefreshReserveAccounts<'info> {
+        RefreshReserveAccounts {
+            reserve_pubkey: self.reserve_pubkey.clone(),
+            reserve_liquidity_pyth_oracle_pubkey: self.reserve_liquidity_pyth_oracle_pubkey.clone(),
+            reserve_liquidity_switchboard_oracle_pubkey: self
+                .reserve_liquidity_switchboard_oracle_pubkey
+                .clone(),
+            clock_sysvar: self.clock_sysvar.clone(),
+        }
+    }
+
+    fn refresh_obligation_accounts(&self) -> RefreshObligationAccounts<'info> {
+        // refresh_obligation expects the clock sysvar right after the obligation
+        RefreshObligationAccounts {
+            obligation_pubkey: self.obligation_pubkey.clone(),
+            reserve_pubkeys: self.clock_sysvar.to_account_info(),
+        }
+    }
+
+    fn deposit_accounts(&self) -> DepositReserveLiquidityAndObligationCollateralAccounts<'info> {
+        DepositReserveLiquidityAndObligationCollateralAccounts {
+            source_liquidity_pubkey: self.user_liquidity_pubkey.clone(),
+            user_collateral_pubkey: self.user_collateral_pubkey.clone(),
+            reserve_pubkey: self.reserve_pubkey.clone(),
+            reserve_liquidity_supply_pubkey: self.reserve_liquidity_supply_pubkey.clone(),
+            reserve_collateral_mint_pubkey: self.reserve_collateral_mint_pubkey.clone(),
+            lending_market_pubkey: self.lending_market_pubkey.clone(),
+            lending_market_authority_pubkey: self.lending_market_authority_pubkey.clone(),
+            destination_deposit_collateral_pubkey: self.reserve_collateral_supply_pubkey.clone(),
+            obligation_pubkey: self.obligation_pubkey.clone(),
+            obligation_owner_pubkey: self.obligation_owner_pubkey.clone(),
+            reserve_liquidity_pyth_oracle_pubkey: self.reserve_liquidity_pyth_oracle_pubkey.clone(),
+            reserve_liquidity_switchboard_oracle_pubkey: self
+                .reserve_liquidity_switchboard_oracle_pubkey
+                .clone(),
+            user_transfer_authority_pubkey: self.user_transfer_authority_pubkey.clone(),
+            clock_sysvar: self.clock_sysvar.clone(),
+            token_program: self.token_program.clone(),
+        }
+    }
+
+    fn borrow_accounts(&self) -> BorrowObligationLiquidityAccounts<'info> {
+        BorrowObligationLiquidityAccounts {
+            source_liquidity_pubkey: self.reserve_liquidity_supply_pubkey.clone(),
+            destination_liquidity_pubkey: self.user_liquidity_pubkey.clone(),
+            borrow_reserve_pubkey: self.reserve_pubkey.clone(),
+            borrow_reserve_liquidity_fee_receiver_pubkey: self
+                .reserve_liquidity_fee_receiver_pubkey
+                .clone(),
+            obligation_pubkey: self.obligation_pubkey.clone(),
+            lending_market_pubkey: self.lending_market_pubkey.clone(),
+            lending_market_authority_pubkey: self.lending_market_authority_pubkey.clone(),
+            obligation_owner_pubkey: self.obligation_owner_pubkey.clone(),
+            clock_sysvar: self.clock_sysvar.clone(),
+            token_program: self.token_program.clone(),
+        }
+    }
+
+    fn withdraw_accounts(&self) -> WithdrawObligationCollateralAccounts<'info> {
+        WithdrawObligationCollateralAccounts {
+            source_collateral_pubkey: self.reserve_collateral_supply_pubkey.clone(),
+            destination_collateral_pubkey: self.user_collateral_pubkey.clone(),
+            withdraw_reserve_pubkey: self.reserve_pubkey.clone(),
+            obligation_pubkey: self.obligation_pubkey.clone(),
+            lending_market_pubkey: self.lending_market_pubkey.clone(),
+            lending_market_authority_pubkey: self.lending_market_authority_pubkey.clone(),
+            obligation_owner_pubkey: self.obligation_owner_pubkey.clone(),
+            clock_sysvar: self.clock_sysvar.clone(),
+            token_program: self.token_program.clone(),
+        }
+    }
+
+    fn redeem_accounts(&self) -> RedeemReserveCollateralAccounts<'info> {
+        RedeemReserveCollateralAccounts {
+            source_collateral_pubkey: self.user_collateral_pubkey.clone(),
+            destination_liquidity_pubkey: self.user_liquidity_pubkey.clone(),
+            reserve_pubkey: self.reserve_pubkey.clone(),
+            reserve_collateral_mint_pubkey: self.reserve_collateral_mint_pubkey.clone(),
+            reserve_liquidity_supply_pubkey: self.reserve_liquidity_supply_pubkey.clone(),
+            lending_market_pubkey: self.lending_market_pubkey.clone(),
+            lending_market_authority_pubkey: self.lending_market_authority_pubkey.clone(),
+            user_transfer_authority_pubkey: self.user_transfer_authority_pubkey.clone(),
+            clock_sysvar: self.clock_sysvar.clone(),
+            token_program: self.token_program.clone(),
+        }
+    }
+
+    fn repay_accounts(&self) -> RepayObligationLiquidityAccounts<'info> {
+        RepayObligationLiquidityAccounts {
+            source_liquidity_pubkey: self.user_liquidity_pubkey.clone(),
+            destination_liquidity_pubkey: self.reserve_liquidity_supply_pubkey.clone(),
+            repay_reserve_pubkey: self.reserve_pubkey.clone(),
+            obligation_pubkey: self.obligation_pubkey.clone(),
+            lending_market_pubkey: self.lending_market_pubkey.clone(),
+            user_transfer_authority_pubkey: self.user_transfer_authority_pubkey.clone(),
+            clock_sysvar: self.clock_sysvar.clone(),
+            token_program: self.token_program.clone(),
+        }
+    }
+}
+
+/// Issues the deposit/borrow sequence of a `LeveragePlan`.
+/// `remaining_accounts` are the obligation's deposit and borrow reserves, in order,
+/// as expected by `refresh_obligation`; every one of them must be refreshed this slot.
+pub fn leverage_loop<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, LeverageAccounts<'info>>,
+    plan: &LeveragePlan,
+) -> ProgramResult {
+    for step in &plan.steps {
+        leverage_step(&ctx, step)?;
+    }
+    Ok(())
+}
+
+fn leverage_step<'a, 'b, 'c, 'info>(
+    ctx: &CpiContext<'a, 'b, 'c, 'info, LeverageAccounts<'info>>,
+    step: &LeverageStep,
+) -> ProgramResult {
+    deposit_reserve_liquidity_and_obligation_collateral(
+        CpiContext::new_with_signer(
+            ctx.program.clone(),
+            ctx.accounts.deposit_accounts(),
+            ctx.signer_seeds,
+        ),
+        step.deposit_amount,
+    )?;
+    if step.borrow_amount == 0 {
+        return Ok(());
+    }
+
+    refresh_reserve(CpiContext::new_with_signer(
+        ctx.program.clone(),
+        ctx.accounts.refresh_reserve_accounts(),
+        ctx.signer_seeds,
+    ))?;
+    refresh_obligation(
+        CpiContext::new_with_signer(
+            ctx.program.clone(),
+            ctx.accounts.refresh_obligation_accounts(),
+            ctx.signer_seeds,
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.clone()),
+    )?;
+    borrow_obligation_liquidity(
+        CpiContext::new_with_signer(
+            ctx.program.clone(),
+            ctx.accounts.borrow_accounts(),
+            ctx.signer_seeds,
+        ),
+        step.borrow_amount,
+    )
+}
+
+/// Issues the withdraw/redeem/repay rounds of `plan_deleverage`.
+/// `remaining_accounts` are the obligation's deposit and borrow reserves, in order,
+/// as expected by `refresh_obligation`; every one of them must be refreshed this slot.
+pub fn deleverage<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, LeverageAccounts<'info>>,
+    steps: &[DeleverageStep],
+) -> ProgramResult {
+    for step in steps {
+        refresh_reserve(CpiContext::new_with_signer(
+            ctx.program.clone(),
+            ctx.accounts.refresh_reserve_accounts(),
+            ctx.signer_seeds,
+        ))?;
+        refresh_obligation(
+            CpiContext::new_with_signer(
+                ctx.program.clone(),
+                ctx.accounts.refresh_obligation_accounts(),
+                ctx.signer_seeds,
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.clone()),
+        )?;
+        withdraw_obligation_collateral(
+            CpiContext::new_with_signer(
+                ctx.program.clone(),
+                ctx.accounts.withdraw_accounts(),
+                ctx.signer_seeds,
+            ),
+            step.collateral_amount,
+        )?;
+        redeem_reserve_collateral(
+            CpiContext::new_with_signer(
+                ctx.program.clone(),
+                ctx.accounts.redeem_accounts(),
+                ctx.signer_seeds,
+            ),
+            step.collateral_amount,
+        )?;
+
+        // Redeeming marks the reserve stale, and repay needs it fresh
+        refresh_reserve(CpiContext::new_with_signer(
+            ctx.program.clone(),
+            ctx.accounts.refresh_reserve_accounts(),
+            ctx.signer_seeds,
+        ))?;
+        repay_obligation_liquidity(
+            CpiContext::new_with_signer(
+                ctx.program.clone(),
+                ctx.accounts.repay_accounts(),
+                ctx.signer_seeds,
+            ),
+            step.repay_amount,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reserve(loan_to_value_ratio: u8, borrow_fee_wad: u64) -> Reserve {
+        let mut reserve = Reserve::default();
+        reserve.config.loan_to_value_ratio = loan_to_value_ratio;
+        reserve.config.fees.borrow_fee_wad = borrow_fee_wad;
+        reserve
+    }
+
+    fn step(deposit_amount: u64, borrow_amount: u64) -> LeverageStep {
+        LeverageStep {
+            deposit_amount,
+            borrow_amount,
+        }
+    }
+
+    #[test]
+    fn leverage_loops_borrow_up_to_target_ltv() {
+        let plan = plan_leverage_loops(&reserve(75, 0), 1_000, 50, 2).unwrap();
+        assert_eq!(
+            plan.steps,
+            vec![step(1_000, 500), step(500, 250), step(250, 0)]
+        );
+        assert_eq!(plan.total_deposited, 1_750);
+        assert_eq!(plan.total_borrowed, 750);
+    }
+
+    #[test]
+    fn leverage_loops_leave_room_for_borrow_fees() {
+        // 1% borrow fee
+        let reserve = reserve(75, 10_000_000_000_000_000);
+        let plan = plan_leverage_loops(&reserve, 1_000_000, 60, 4).unwrap();
+        let (mut deposited, mut debt) = (0u64, 0u64);
+        for leverage_step in &plan.steps {
+            deposited += leverage_step.deposit_amount;
+            debt += borrow_debt(&reserve, leverage_step.borrow_amount).unwrap();
+            assert!(debt * 100 <= deposited * 60);
+        }
+        assert_eq!(deposited, plan.total_deposited);
+        assert_eq!(debt, plan.total_borrowed);
+        assert!(plan.steps[0].borrow_amount < 600_000);
+    }
+
+    #[test]
+    fn leverage_loops_reject_bad_inputs() {
+        assert!(plan_leverage_loops(&reserve(75, 0), 1_000, 75, 2).is_err());
+        assert!(plan_leverage_loops(&reserve(75, 0), 1_000, 0, 2).is_err());
+        assert!(plan_leverage_loops(&reserve(75, 0), 1_000, 50, 0).is_err());
+    }
+
+    #[test]
+    fn deleverage_reaches_target_in_one_round() {
+        let steps = plan_deleverage(&reserve(75, 0), 1_000, 550, 50, 5).unwrap();
+        assert_eq!(
+            steps,
+            vec![DeleverageStep {
+                collateral_amount: 100,
+                repay_amount: 100,
+            }]
+        );
+    }
+
+    #[test]
+    fn deleverage_rounds_stay_within_loan_to_value() {
+        let steps = plan_deleverage(&reserve(75, 0), 1_000, 700, 50, 20).unwrap();
+        let (mut deposited, mut borrowed) = (1_000u64, 700u64);
+        for step in &steps {
+            deposited -= step.collateral_amount;
+            assert!(borrowed * 100 <= deposited * 75);
+            borrowed -= step.repay_amount;
+        }
+        assert!(borrowed * 100 <= deposited * 50);
+    }
+
+    #[test]
+    fn deleverage_rejects_position_below_target() {
+        assert!(plan_deleverage(&reserve(75, 0), 1_000, 400, 50, 5).is_err());
+    }
+}
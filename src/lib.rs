@@ -1,10 +1,80 @@
+//! Anchor CPI wrappers for the Solend lending program.
+//!
+//! Take the Solend program as `Program<'info, Solend>` in your accounts and build the
+//! context from it, the way the wrappers expect:
+//!
+//! ```ignore
+//! let cpi_ctx = CpiContext::new(
+//!     ctx.accounts.solend_program.to_account_info(),
+//!     RefreshReserveAccounts { ... },
+//! );
+//! solend_anchor::refresh_reserve(cpi_ctx)?;
+//! ```
+//!
+//! Every wrapper fails with `InvalidSolendProgram` when the context's program is not
+//! `Solend::id()`.
+//!
+//! Owners and transfer authorities are plain `AccountInfo`s rather than `Signer`s, so a
+//! PDA of the calling program can fill them and sign through `new_with_signer`.
+
 use anchor_lang::{
-    prelude::{AccountInfo, CpiContext, Program, Pubkey, Rent, Sysvar, Clock, Signer},
+    prelude::{AccountInfo, CpiContext, Program, Pubkey, Rent, Sysvar, Clock},
     solana_program::{self, entrypoint::ProgramResult},
-    Accounts, Key, ToAccountInfo, ToAccountInfos, ToAccountMetas,
+    Accounts, Id, Key, ToAccountInfo, ToAccountInfos, ToAccountMetas,
 };
 use solend_token_lending::state::{ReserveConfig, ReserveFees};
 
+#[cfg(feature = "events")]
+use anchor_lang::prelude::emit;
+
+/// Runs `$invoke` and, with the `events` feature, emits the event built from what left
+/// `$source` and arrived in `$destination` around it
+macro_rules! invoke_with_event {
+    (
+        $invoke:expr,
+        $source:expr,
+        $destination:expr,
+        |$source_delta:ident, $destination_delta:ident| $event:expr $(,)?
+    ) => {{
+        #[cfg(feature = "events")]
+        let balances = token::TokenBalances::measure($source, $destination)?;
+        $invoke?;
+        #[cfg(feature = "events")]
+        {
+            let ($source_delta, $destination_delta) = balances.deltas($source, $destination)?;
+            emit!($event);
+        }
+    }};
+}
+
+#[cfg(feature = "client")]
+pub mod account_source;
+pub mod amount;
+#[cfg(feature = "client")]
+pub mod bootstrap;
+pub mod builder;
+pub mod collateral_repay;
+pub mod decoder;
+pub mod delegate;
+pub mod error;
+#[cfg(feature = "events")]
+pub mod events;
+pub mod guards;
+pub mod host_fee;
+#[cfg(feature = "client")]
+pub mod inner_instructions;
+pub mod leverage;
+pub mod limits;
+pub mod liquidation;
+pub mod measured;
+pub mod native_sol;
+pub mod obligation;
+pub mod oracle;
+pub mod referral;
+pub mod reserve;
+pub mod snapshot;
+pub mod token;
+
 #[derive(Clone)]
 pub struct TokenProgram;
 
@@ -40,8 +110,9 @@ pub fn init_lending_market<'a, 'b, 'c, 'info>(
     ctx: CpiContext<'a, 'b, 'c, 'info, InitLendingMarketAccounts<'info>>,
     quote_currency: [u8; 32],
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     let ix = solend_token_lending::instruction::init_lending_market(
-        solend_token_lending::id(),
+        ctx.program.key(),
         ctx.accounts.owner.key(),
         quote_currency,
         ctx.accounts.lending_market_pubkey.key(),
@@ -52,6 +123,7 @@ pub fn init_lending_market<'a, 'b, 'c, 'info>(
     solana_program::program::invoke_signed(
         &ix,
         &[
+            ctx.program.clone(),
             ctx.accounts.lending_market_pubkey.to_account_info(),
             ctx.accounts.rent.to_account_info(),
             ctx.accounts.token_program.to_account_info(),
@@ -73,8 +145,9 @@ pub struct SetLendingMarketOwnerAccounts<'info> {
 pub fn set_lending_market_owner<'a, 'b, 'c, 'info>(
     ctx: CpiContext<'a, 'b, 'c, 'info, SetLendingMarketOwnerAccounts<'info>>,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     let ix = solend_token_lending::instruction::set_lending_market_owner(
-        solend_token_lending::id(),
+        ctx.program.key(),
         ctx.accounts.lending_market_pubkey.key(),
         ctx.accounts.lending_market_owner.key(),
         ctx.accounts.new_owner.key(),
@@ -83,6 +156,7 @@ pub fn set_lending_market_owner<'a, 'b, 'c, 'info>(
     solana_program::program::invoke_signed(
         &ix,
         &[
+            ctx.program.clone(),
             ctx.accounts.lending_market_pubkey.clone(),
             ctx.accounts.lending_market_owner.clone(),
         ],
@@ -104,8 +178,8 @@ pub struct InitReserveAccounts<'info> {
     pub pyth_price_pubkey: AccountInfo<'info>,
     pub switchboard_feed_pubkey: AccountInfo<'info>,
     pub lending_market_pubkey: AccountInfo<'info>,
-    pub lending_market_owner_pubkey: Signer<'info>,
-    pub user_transfer_authority_pubkey: Signer<'info>,
+    pub lending_market_owner_pubkey: AccountInfo<'info>,
+    pub user_transfer_authority_pubkey: AccountInfo<'info>,
     pub fee_receiver_pubkey: AccountInfo<'info>,
     pub lending_market_authority_pubkey: AccountInfo<'info>,
     pub rent_sysvar: Sysvar<'info, Rent>,
@@ -142,6 +216,7 @@ pub fn init_reserve<'a, 'b, 'c, 'info>(
     liquidity_amount: u64,
     config_data: ReserveConfigData,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     
     let config =  ReserveConfig {
         optimal_utilization_rate: config_data.optimal_utilization_rate,
@@ -149,7 +224,7 @@ pub fn init_reserve<'a, 'b, 'c, 'info>(
         liquidation_bonus: config_data.liquidation_bonus,
         liquidation_threshold: config_data.liquidation_threshold,
         min_borrow_rate: config_data.min_borrow_rate,
-        optimal_borrow_rate: config_data.optimal_utilization_rate,
+        optimal_borrow_rate: config_data.optimal_borrow_rate,
         max_borrow_rate: config_data.max_borrow_rate,
         fees: config_data.fees,
         deposit_limit: config_data.deposit_limit,
@@ -158,7 +233,7 @@ pub fn init_reserve<'a, 'b, 'c, 'info>(
     };
 
     let ix = solend_token_lending::instruction::init_reserve(
-        solend_token_lending::id(),
+        ctx.program.key(),
         liquidity_amount,
         config,
         ctx.accounts.source_liquidity_pubkey.key(),
@@ -177,18 +252,19 @@ pub fn init_reserve<'a, 'b, 'c, 'info>(
     );
     
     solana_program::program::invoke_signed(&ix, &[
+        ctx.program.clone(),
         ctx.accounts.source_liquidity_pubkey.to_account_info(),
         ctx.accounts.destination_collateral_pubkey.to_account_info(),
         ctx.accounts.reserve_pubkey.to_account_info(),
         ctx.accounts.reserve_liquidity_mint_pubkey.to_account_info(),
-        ctx.accounts.reserve_collateral_supply_pubkey.to_account_info(),
+        ctx.accounts.reserve_liquidity_supply_pubkey.to_account_info(),
         ctx.accounts.fee_receiver_pubkey.to_account_info(),
         ctx.accounts.reserve_collateral_mint_pubkey.to_account_info(),
         ctx.accounts.reserve_collateral_supply_pubkey.to_account_info(),
         ctx.accounts.pyth_product_pubkey.to_account_info(),
         ctx.accounts.pyth_price_pubkey.to_account_info(),
         ctx.accounts.switchboard_feed_pubkey.to_account_info(),
-        ctx.accounts.switchboard_feed_pubkey.to_account_info(),
+        ctx.accounts.lending_market_pubkey.to_account_info(),
         ctx.accounts.lending_market_authority_pubkey.to_account_info(),
         ctx.accounts.lending_market_owner_pubkey.to_account_info(),
         ctx.accounts.user_transfer_authority_pubkey.to_account_info(),
@@ -210,8 +286,9 @@ pub struct RefreshReserveAccounts<'info> {
 pub fn refresh_reserve<'a, 'b, 'c, 'info>(
     ctx: CpiContext<'a, 'b, 'c, 'info, RefreshReserveAccounts<'info>>,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     let ix = solend_token_lending::instruction::refresh_reserve(
-        solend_token_lending::id(),
+        ctx.program.key(),
         ctx.accounts.reserve_pubkey.key(),
         ctx.accounts.reserve_liquidity_pyth_oracle_pubkey.key(),
         ctx.accounts
@@ -221,6 +298,7 @@ pub fn refresh_reserve<'a, 'b, 'c, 'info>(
     solana_program::program::invoke_signed(
         &ix,
         &[
+            ctx.program.clone(),
             ctx.accounts.reserve_pubkey.to_account_info(),
             ctx.accounts.reserve_liquidity_pyth_oracle_pubkey.to_account_info(),
             ctx.accounts
@@ -241,7 +319,8 @@ pub struct DepositReserveLiquidityAccounts<'info> {
     pub reserve_liquidity_supply_pubkey: AccountInfo<'info>,
     pub reserve_collateral_mint_pubkey: AccountInfo<'info>,
     pub lending_market_pubkey: AccountInfo<'info>,
-    pub user_transfer_authority_pubkey: Signer<'info>,
+    pub lending_market_authority_pubkey: AccountInfo<'info>,
+    pub user_transfer_authority_pubkey: AccountInfo<'info>,
     pub clock_sysvar: Sysvar<'info, Clock>,
     pub token_program: Program<'info, TokenProgram>,
 }
@@ -250,8 +329,9 @@ pub fn deposit_reserve_liquidity<'a, 'b, 'c, 'info>(
     ctx: CpiContext<'a, 'b, 'c, 'info, DepositReserveLiquidityAccounts<'info>>,
     liquidity_amount: u64,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     let ix = solend_token_lending::instruction::deposit_reserve_liquidity(
-        solend_token_lending::id(),
+        ctx.program.key(),
         liquidity_amount,
         ctx.accounts.source_liquidity_pubkey.key(),
         ctx.accounts.destination_collateral_pubkey.key(),
@@ -261,22 +341,36 @@ pub fn deposit_reserve_liquidity<'a, 'b, 'c, 'info>(
         ctx.accounts.lending_market_pubkey.key(),
         ctx.accounts.user_transfer_authority_pubkey.key(),
     );
-    solana_program::program::invoke_signed(
-        &ix,
-        &[
-            ctx.accounts.source_liquidity_pubkey.to_account_info(),
-            ctx.accounts.destination_collateral_pubkey.to_account_info(),
-            ctx.accounts.reserve_pubkey.to_account_info(),
-            ctx.accounts.reserve_liquidity_supply_pubkey.to_account_info(),
-            ctx.accounts.reserve_collateral_mint_pubkey.to_account_info(),
-            ctx.accounts.lending_market_pubkey.to_account_info(),
-            ctx.accounts.user_transfer_authority_pubkey.to_account_info(),
-            ctx.accounts.clock_sysvar.to_account_info(),
-            ctx.accounts.token_program.to_account_info()
-        ],
-        ctx.signer_seeds,
-    )
-    .map_err(Into::into)
+
+    invoke_with_event!(
+        solana_program::program::invoke_signed(
+            &ix,
+            &[
+                ctx.program.clone(),
+                ctx.accounts.source_liquidity_pubkey.to_account_info(),
+                ctx.accounts.destination_collateral_pubkey.to_account_info(),
+                ctx.accounts.reserve_pubkey.to_account_info(),
+                ctx.accounts.reserve_liquidity_supply_pubkey.to_account_info(),
+                ctx.accounts.reserve_collateral_mint_pubkey.to_account_info(),
+                ctx.accounts.lending_market_pubkey.to_account_info(),
+                ctx.accounts.lending_market_authority_pubkey.to_account_info(),
+                ctx.accounts.user_transfer_authority_pubkey.to_account_info(),
+                ctx.accounts.clock_sysvar.to_account_info(),
+                ctx.accounts.token_program.to_account_info()
+            ],
+            ctx.signer_seeds,
+        ),
+        &ctx.accounts.source_liquidity_pubkey,
+        &ctx.accounts.destination_collateral_pubkey,
+        |source_delta, destination_delta| events::DepositReserveLiquidityEvent {
+            reserve: ctx.accounts.reserve_pubkey.key(),
+            liquidity_amount,
+            source_delta,
+            destination_delta,
+        },
+    );
+
+    Ok(())
 }
 
 #[derive(Accounts)]
@@ -287,7 +381,8 @@ pub struct RedeemReserveCollateralAccounts<'info> {
     pub reserve_collateral_mint_pubkey: AccountInfo<'info>,
     pub reserve_liquidity_supply_pubkey: AccountInfo<'info>,
     pub lending_market_pubkey: AccountInfo<'info>,
-    pub user_transfer_authority_pubkey: Signer<'info>,
+    pub lending_market_authority_pubkey: AccountInfo<'info>,
+    pub user_transfer_authority_pubkey: AccountInfo<'info>,
     pub clock_sysvar: Sysvar<'info, Clock>,
     pub token_program: Program<'info, TokenProgram>,
 }
@@ -296,8 +391,9 @@ pub fn redeem_reserve_collateral<'a, 'b, 'c, 'info>(
     ctx: CpiContext<'a, 'b, 'c, 'info, RedeemReserveCollateralAccounts<'info>>,
     collateral_amount: u64,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     let ix = solend_token_lending::instruction::redeem_reserve_collateral(
-        solend_token_lending::id(),
+        ctx.program.key(),
         collateral_amount,
         ctx.accounts.source_collateral_pubkey.key(),
         ctx.accounts.destination_liquidity_pubkey.key(),
@@ -307,22 +403,36 @@ pub fn redeem_reserve_collateral<'a, 'b, 'c, 'info>(
         ctx.accounts.lending_market_pubkey.key(),
         ctx.accounts.user_transfer_authority_pubkey.key(),
     );
-    solana_program::program::invoke_signed(
-        &ix,
-        &[
-            ctx.accounts.source_collateral_pubkey.to_account_info(),
-            ctx.accounts.destination_liquidity_pubkey.to_account_info(),
-            ctx.accounts.reserve_pubkey.to_account_info(),
-            ctx.accounts.reserve_collateral_mint_pubkey.to_account_info(),
-            ctx.accounts.reserve_liquidity_supply_pubkey.to_account_info(),
-            ctx.accounts.lending_market_pubkey.to_account_info(),
-            ctx.accounts.user_transfer_authority_pubkey.to_account_info(),
-            ctx.accounts.clock_sysvar.to_account_info(),
-            ctx.accounts.token_program.to_account_info()
-        ],
-        ctx.signer_seeds,
-    )
-    .map_err(Into::into)
+
+    invoke_with_event!(
+        solana_program::program::invoke_signed(
+            &ix,
+            &[
+                ctx.program.clone(),
+                ctx.accounts.source_collateral_pubkey.to_account_info(),
+                ctx.accounts.destination_liquidity_pubkey.to_account_info(),
+                ctx.accounts.reserve_pubkey.to_account_info(),
+                ctx.accounts.reserve_collateral_mint_pubkey.to_account_info(),
+                ctx.accounts.reserve_liquidity_supply_pubkey.to_account_info(),
+                ctx.accounts.lending_market_pubkey.to_account_info(),
+                ctx.accounts.lending_market_authority_pubkey.to_account_info(),
+                ctx.accounts.user_transfer_authority_pubkey.to_account_info(),
+                ctx.accounts.clock_sysvar.to_account_info(),
+                ctx.accounts.token_program.to_account_info()
+            ],
+            ctx.signer_seeds,
+        ),
+        &ctx.accounts.source_collateral_pubkey,
+        &ctx.accounts.destination_liquidity_pubkey,
+        |source_delta, destination_delta| events::RedeemReserveCollateralEvent {
+            reserve: ctx.accounts.reserve_pubkey.key(),
+            collateral_amount,
+            source_delta,
+            destination_delta,
+        },
+    );
+
+    Ok(())
 }
 
 #[derive(Accounts)]
@@ -338,8 +448,9 @@ pub struct InitObligationAccounts<'info> {
 pub fn init_obligation<'a, 'b, 'c, 'info>(
     ctx: CpiContext<'a, 'b, 'c, 'info, InitObligationAccounts<'info>>,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     let ix = solend_token_lending::instruction::init_obligation(
-        solend_token_lending::id(),
+        ctx.program.key(),
         ctx.accounts.obligation_pubkey.key(),
         ctx.accounts.lending_market_pubkey.key(),
         ctx.accounts.obligation_owner_pubkey.key(),
@@ -347,6 +458,7 @@ pub fn init_obligation<'a, 'b, 'c, 'info>(
     solana_program::program::invoke_signed(
         &ix,
         &[
+            ctx.program.clone(),
             ctx.accounts.obligation_pubkey.to_account_info(),
             ctx.accounts.lending_market_pubkey.to_account_info(),
             ctx.accounts.obligation_owner_pubkey.to_account_info(),
@@ -368,15 +480,17 @@ pub struct RefreshObligationAccounts<'info> {
 pub fn refresh_obligation<'a, 'b, 'c, 'info>(
     ctx: CpiContext<'a, 'b, 'c, 'info, RefreshObligationAccounts<'info>>,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     let reserve_pubkeys = ctx.remaining_accounts;
     let keys = reserve_pubkeys.iter().map(|k| k.key()).collect();
     let ix = solend_token_lending::instruction::refresh_obligation(
-        solend_token_lending::id(),
+        ctx.program.key(),
         ctx.accounts.obligation_pubkey.key(),
         keys,
     );
 
     let mut account_infos = vec![
+        ctx.program.clone(),
         ctx.accounts.obligation_pubkey.to_account_info(),
         ctx.accounts.reserve_pubkeys.to_account_info()
     ];
@@ -397,8 +511,8 @@ pub struct DepositObligationCollateralAccounts<'info> {
     pub deposit_reserve_pubkey: AccountInfo<'info>,
     pub obligation_pubkey: AccountInfo<'info>,
     pub lending_market_pubkey: AccountInfo<'info>,
-    pub obligation_owner_pubkey: Signer<'info>,
-    pub user_transfer_authority_pubkey: Signer<'info>,
+    pub obligation_owner_pubkey: AccountInfo<'info>,
+    pub user_transfer_authority_pubkey: AccountInfo<'info>,
     pub clock_sysvar: Sysvar<'info, Clock>,
     pub token_program: Program<'info, TokenProgram>,
 }
@@ -407,8 +521,9 @@ pub fn deposit_obligation_collateral<'a, 'b, 'c, 'info>(
     ctx: CpiContext<'a, 'b, 'c, 'info, DepositObligationCollateralAccounts<'info>>,
     collateral_amount: u64,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     let ix = solend_token_lending::instruction::deposit_obligation_collateral(
-        solend_token_lending::id(),
+        ctx.program.key(),
         collateral_amount,
         ctx.accounts.source_collateral_pubkey.key(),
         ctx.accounts.destination_collateral_pubkey.key(),
@@ -418,22 +533,36 @@ pub fn deposit_obligation_collateral<'a, 'b, 'c, 'info>(
         ctx.accounts.obligation_owner_pubkey.key(),
         ctx.accounts.user_transfer_authority_pubkey.key(),
     );
-    solana_program::program::invoke_signed(
-        &ix,
-        &[
-            ctx.accounts.source_collateral_pubkey.to_account_info(),
-            ctx.accounts.destination_collateral_pubkey.to_account_info(),
-            ctx.accounts.deposit_reserve_pubkey.to_account_info(),
-            ctx.accounts.obligation_pubkey.to_account_info(),
-            ctx.accounts.lending_market_pubkey.to_account_info(),
-            ctx.accounts.obligation_owner_pubkey.to_account_info(),
-            ctx.accounts.user_transfer_authority_pubkey.to_account_info(),
-            ctx.accounts.clock_sysvar.to_account_info(),
-            ctx.accounts.token_program.to_account_info()
-        ],
-        ctx.signer_seeds,
-    )
-    .map_err(Into::into)
+
+    invoke_with_event!(
+        solana_program::program::invoke_signed(
+            &ix,
+            &[
+                ctx.program.clone(),
+                ctx.accounts.source_collateral_pubkey.to_account_info(),
+                ctx.accounts.destination_collateral_pubkey.to_account_info(),
+                ctx.accounts.deposit_reserve_pubkey.to_account_info(),
+                ctx.accounts.obligation_pubkey.to_account_info(),
+                ctx.accounts.lending_market_pubkey.to_account_info(),
+                ctx.accounts.obligation_owner_pubkey.to_account_info(),
+                ctx.accounts.user_transfer_authority_pubkey.to_account_info(),
+                ctx.accounts.clock_sysvar.to_account_info(),
+                ctx.accounts.token_program.to_account_info()
+            ],
+            ctx.signer_seeds,
+        ),
+        &ctx.accounts.source_collateral_pubkey,
+        &ctx.accounts.destination_collateral_pubkey,
+        |source_delta, destination_delta| events::DepositObligationCollateralEvent {
+            reserve: ctx.accounts.deposit_reserve_pubkey.key(),
+            obligation: ctx.accounts.obligation_pubkey.key(),
+            collateral_amount,
+            source_delta,
+            destination_delta,
+        },
+    );
+
+    Ok(())
 }
 
 #[derive(Accounts)]
@@ -443,7 +572,8 @@ pub struct WithdrawObligationCollateralAccounts<'info> {
     pub withdraw_reserve_pubkey: AccountInfo<'info>,
     pub obligation_pubkey: AccountInfo<'info>,
     pub lending_market_pubkey: AccountInfo<'info>,
-    pub obligation_owner_pubkey: Signer<'info>,
+    pub lending_market_authority_pubkey: AccountInfo<'info>,
+    pub obligation_owner_pubkey: AccountInfo<'info>,
     pub clock_sysvar: Sysvar<'info, Clock>,
     pub token_program: Program<'info, TokenProgram>,
 }
@@ -452,8 +582,9 @@ pub fn withdraw_obligation_collateral<'a, 'b, 'c, 'info>(
     ctx: CpiContext<'a, 'b, 'c, 'info, WithdrawObligationCollateralAccounts<'info>>,
     collateral_amount: u64,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     let ix = solend_token_lending::instruction::withdraw_obligation_collateral(
-        solend_token_lending::id(),
+        ctx.program.key(),
         collateral_amount,
         ctx.accounts.source_collateral_pubkey.key(),
         ctx.accounts.destination_collateral_pubkey.key(),
@@ -464,21 +595,36 @@ pub fn withdraw_obligation_collateral<'a, 'b, 'c, 'info>(
     );
 
     let account_infos = vec![
+        ctx.program.clone(),
         ctx.accounts.source_collateral_pubkey.to_account_info(),
         ctx.accounts.destination_collateral_pubkey.to_account_info(),
         ctx.accounts.withdraw_reserve_pubkey.to_account_info(),
         ctx.accounts.obligation_pubkey.to_account_info(),
         ctx.accounts.lending_market_pubkey.to_account_info(),
+        ctx.accounts.lending_market_authority_pubkey.to_account_info(),
         ctx.accounts.obligation_owner_pubkey.to_account_info(),
         ctx.accounts.clock_sysvar.to_account_info(),
         ctx.accounts.token_program.to_account_info()
     ];
-    solana_program::program::invoke_signed(
-        &ix,
-        &account_infos,
-        ctx.signer_seeds,
-    )
-    .map_err(Into::into)
+
+    invoke_with_event!(
+        solana_program::program::invoke_signed(
+            &ix,
+            &account_infos,
+            ctx.signer_seeds,
+        ),
+        &ctx.accounts.source_collateral_pubkey,
+        &ctx.accounts.destination_collateral_pubkey,
+        |source_delta, destination_delta| events::WithdrawObligationCollateralEvent {
+            reserve: ctx.accounts.withdraw_reserve_pubkey.key(),
+            obligation: ctx.accounts.obligation_pubkey.key(),
+            collateral_amount,
+            source_delta,
+            destination_delta,
+        },
+    );
+
+    Ok(())
 }
 
 #[derive(Accounts)]
@@ -489,18 +635,22 @@ pub struct BorrowObligationLiquidityAccounts<'info> {
     pub borrow_reserve_liquidity_fee_receiver_pubkey: AccountInfo<'info>,
     pub obligation_pubkey: AccountInfo<'info>,
     pub lending_market_pubkey: AccountInfo<'info>,
-    pub obligation_owner_pubkey: Signer<'info>,
+    pub lending_market_authority_pubkey: AccountInfo<'info>,
+    pub obligation_owner_pubkey: AccountInfo<'info>,
     pub clock_sysvar: Sysvar<'info, Clock>,
     pub token_program: Program<'info, TokenProgram>,
 }
 
+/// Treats the first remaining account as the host fee receiver. Use
+/// `host_fee::borrow_obligation_liquidity_with_host_fee` to pass it explicitly.
 pub fn borrow_obligation_liquidity<'a, 'b, 'c, 'info>(
     ctx: CpiContext<'a, 'b, 'c, 'info, BorrowObligationLiquidityAccounts<'info>>,
     liquidity_amount: u64,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     let host_fee_receiver_pubkey = ctx.remaining_accounts.get(0);
     let ix = solend_token_lending::instruction::borrow_obligation_liquidity(
-        solend_token_lending::id(),
+        ctx.program.key(),
         liquidity_amount,
         ctx.accounts.source_liquidity_pubkey.key(),
         ctx.accounts.destination_liquidity_pubkey.key(),
@@ -515,6 +665,7 @@ pub fn borrow_obligation_liquidity<'a, 'b, 'c, 'info>(
     );
     
     let mut account_infos = vec![
+        ctx.program.clone(),
         ctx.accounts.source_liquidity_pubkey.to_account_info(),
         ctx.accounts.destination_liquidity_pubkey.to_account_info(),
         ctx.accounts.borrow_reserve_pubkey.to_account_info(),
@@ -523,6 +674,7 @@ pub fn borrow_obligation_liquidity<'a, 'b, 'c, 'info>(
             .to_account_info(),
         ctx.accounts.obligation_pubkey.to_account_info(),
         ctx.accounts.lending_market_pubkey.to_account_info(),
+        ctx.accounts.lending_market_authority_pubkey.to_account_info(),
         ctx.accounts.obligation_owner_pubkey.to_account_info(),
         ctx.accounts.clock_sysvar.to_account_info(),
         ctx.accounts.token_program.to_account_info()
@@ -532,12 +684,24 @@ pub fn borrow_obligation_liquidity<'a, 'b, 'c, 'info>(
         account_infos.push(host_fee_receiver_pubkey.to_account_info());
     }
 
-    solana_program::program::invoke_signed(
-        &ix,
-        &account_infos,
-        ctx.signer_seeds,
-    )
-    .map_err(Into::into)
+    invoke_with_event!(
+        solana_program::program::invoke_signed(
+            &ix,
+            &account_infos,
+            ctx.signer_seeds,
+        ),
+        &ctx.accounts.source_liquidity_pubkey,
+        &ctx.accounts.destination_liquidity_pubkey,
+        |source_delta, destination_delta| events::BorrowObligationLiquidityEvent {
+            reserve: ctx.accounts.borrow_reserve_pubkey.key(),
+            obligation: ctx.accounts.obligation_pubkey.key(),
+            liquidity_amount,
+            source_delta,
+            destination_delta,
+        },
+    );
+
+    Ok(())
 }
 
 #[derive(Accounts)]
@@ -547,7 +711,7 @@ pub struct RepayObligationLiquidityAccounts<'info> {
     pub repay_reserve_pubkey: AccountInfo<'info>,
     pub obligation_pubkey: AccountInfo<'info>,
     pub lending_market_pubkey: AccountInfo<'info>,
-    pub user_transfer_authority_pubkey: Signer<'info>,
+    pub user_transfer_authority_pubkey: AccountInfo<'info>,
     pub clock_sysvar: Sysvar<'info, Clock>,
     pub token_program: Program<'info, TokenProgram>,
 }
@@ -556,8 +720,9 @@ pub fn repay_obligation_liquidity<'a, 'b, 'c, 'info>(
     ctx: CpiContext<'a, 'b, 'c, 'info, RepayObligationLiquidityAccounts<'info>>,
     liquidity_amount: u64,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     let ix = solend_token_lending::instruction::repay_obligation_liquidity(
-        solend_token_lending::id(),
+        ctx.program.key(),
         liquidity_amount,
         ctx.accounts.source_liquidity_pubkey.key(),
         ctx.accounts.destination_liquidity_pubkey.key(),
@@ -566,21 +731,35 @@ pub fn repay_obligation_liquidity<'a, 'b, 'c, 'info>(
         ctx.accounts.lending_market_pubkey.key(),
         ctx.accounts.user_transfer_authority_pubkey.key(),
     );
-    solana_program::program::invoke_signed(
-        &ix,
-        &[
-            ctx.accounts.source_liquidity_pubkey.to_account_info(),
-            ctx.accounts.destination_liquidity_pubkey.to_account_info(),
-            ctx.accounts.repay_reserve_pubkey.to_account_info(),
-            ctx.accounts.obligation_pubkey.to_account_info(),
-            ctx.accounts.lending_market_pubkey.to_account_info(),
-            ctx.accounts.user_transfer_authority_pubkey.to_account_info(),
-            ctx.accounts.clock_sysvar.to_account_info(),
-            ctx.accounts.token_program.to_account_info()
-        ],
-        ctx.signer_seeds,
-    )
-    .map_err(Into::into)
+
+    invoke_with_event!(
+        solana_program::program::invoke_signed(
+            &ix,
+            &[
+                ctx.program.clone(),
+                ctx.accounts.source_liquidity_pubkey.to_account_info(),
+                ctx.accounts.destination_liquidity_pubkey.to_account_info(),
+                ctx.accounts.repay_reserve_pubkey.to_account_info(),
+                ctx.accounts.obligation_pubkey.to_account_info(),
+                ctx.accounts.lending_market_pubkey.to_account_info(),
+                ctx.accounts.user_transfer_authority_pubkey.to_account_info(),
+                ctx.accounts.clock_sysvar.to_account_info(),
+                ctx.accounts.token_program.to_account_info()
+            ],
+            ctx.signer_seeds,
+        ),
+        &ctx.accounts.source_liquidity_pubkey,
+        &ctx.accounts.destination_liquidity_pubkey,
+        |source_delta, destination_delta| events::RepayObligationLiquidityEvent {
+            reserve: ctx.accounts.repay_reserve_pubkey.key(),
+            obligation: ctx.accounts.obligation_pubkey.key(),
+            liquidity_amount,
+            source_delta,
+            destination_delta,
+        },
+    );
+
+    Ok(())
 }
 
 #[derive(Accounts)]
@@ -593,7 +772,8 @@ pub struct LiquidateObligationAccounts<'info> {
     pub withdraw_reserve_collateral_supply_pubkey: AccountInfo<'info>,
     pub obligation_pubkey: AccountInfo<'info>,
     pub lending_market_pubkey: AccountInfo<'info>,
-    pub user_transfer_authority_pubkey: Signer<'info>,
+    pub lending_market_authority_pubkey: AccountInfo<'info>,
+    pub user_transfer_authority_pubkey: AccountInfo<'info>,
     pub clock_sysvar: Sysvar<'info, Clock>,
     pub token_program: Program<'info, TokenProgram>
 }
@@ -602,8 +782,9 @@ pub fn liquidate_obligation<'a, 'b, 'c, 'info>(
     ctx: CpiContext<'a, 'b, 'c, 'info, LiquidateObligationAccounts<'info>>,
     liquidity_amount: u64,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     let ix = solend_token_lending::instruction::liquidate_obligation(
-        solend_token_lending::id(),
+        ctx.program.key(),
         liquidity_amount,
         ctx.accounts.source_liquidity_pubkey.key(),
         ctx.accounts.destination_collateral_pubkey.key(),
@@ -616,26 +797,41 @@ pub fn liquidate_obligation<'a, 'b, 'c, 'info>(
         ctx.accounts.user_transfer_authority_pubkey.key(),
     );
 
-    solana_program::program::invoke_signed(
-        &ix,
-        &[
-            ctx.accounts.source_liquidity_pubkey.to_account_info(),
-            ctx.accounts.destination_collateral_pubkey.to_account_info(),
-            ctx.accounts.repay_reserve_pubkey.to_account_info(),
-            ctx.accounts.repay_reserve_liquidity_supply_pubkey.to_account_info(),
-            ctx.accounts.withdraw_reserve_pubkey.to_account_info(),
-            ctx.accounts
-                .withdraw_reserve_collateral_supply_pubkey
-                .to_account_info(),
-            ctx.accounts.obligation_pubkey.to_account_info(),
-            ctx.accounts.lending_market_pubkey.to_account_info(),
-            ctx.accounts.user_transfer_authority_pubkey.to_account_info(),
-            ctx.accounts.clock_sysvar.to_account_info(),
-            ctx.accounts.token_program.to_account_info()
-        ],
-        ctx.signer_seeds,
-    )
-    .map_err(Into::into)
+    invoke_with_event!(
+        solana_program::program::invoke_signed(
+            &ix,
+            &[
+                ctx.program.clone(),
+                ctx.accounts.source_liquidity_pubkey.to_account_info(),
+                ctx.accounts.destination_collateral_pubkey.to_account_info(),
+                ctx.accounts.repay_reserve_pubkey.to_account_info(),
+                ctx.accounts.repay_reserve_liquidity_supply_pubkey.to_account_info(),
+                ctx.accounts.withdraw_reserve_pubkey.to_account_info(),
+                ctx.accounts
+                    .withdraw_reserve_collateral_supply_pubkey
+                    .to_account_info(),
+                ctx.accounts.obligation_pubkey.to_account_info(),
+                ctx.accounts.lending_market_pubkey.to_account_info(),
+                ctx.accounts.lending_market_authority_pubkey.to_account_info(),
+                ctx.accounts.user_transfer_authority_pubkey.to_account_info(),
+                ctx.accounts.clock_sysvar.to_account_info(),
+                ctx.accounts.token_program.to_account_info()
+            ],
+            ctx.signer_seeds,
+        ),
+        &ctx.accounts.source_liquidity_pubkey,
+        &ctx.accounts.destination_collateral_pubkey,
+        |source_delta, destination_delta| events::LiquidateObligationEvent {
+            repay_reserve: ctx.accounts.repay_reserve_pubkey.key(),
+            withdraw_reserve: ctx.accounts.withdraw_reserve_pubkey.key(),
+            obligation: ctx.accounts.obligation_pubkey.key(),
+            liquidity_amount,
+            source_delta,
+            destination_delta,
+        },
+    );
+
+    Ok(())
 }
 
 #[derive(Accounts)]
@@ -654,9 +850,10 @@ pub fn flash_loan<'a, 'b, 'c, 'info>(
     ctx: CpiContext<'a, 'b, 'c, 'info, FlashLoanAccounts<'info>>,
     amount: u64,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     let flash_loan_receiver_program_accounts: Vec<AccountInfo> = ctx.remaining_accounts;
     let ix = solend_token_lending::instruction::flash_loan(
-        solend_token_lending::id(),
+        ctx.program.key(),
         amount,
         ctx.accounts.source_liquidity_pubkey.key(),
         ctx.accounts.destination_liquidity_pubkey.key(),
@@ -669,6 +866,7 @@ pub fn flash_loan<'a, 'b, 'c, 'info>(
     );
 
     let mut accounts_infos = vec![
+        ctx.program.clone(),
         ctx.accounts.source_liquidity_pubkey.to_account_info(),
         ctx.accounts.destination_liquidity_pubkey.to_account_info(),
         ctx.accounts.reserve_pubkey.to_account_info(),
@@ -681,8 +879,21 @@ pub fn flash_loan<'a, 'b, 'c, 'info>(
     ];
     accounts_infos.extend(flash_loan_receiver_program_accounts);
 
-    solana_program::program::invoke_signed(&ix, &accounts_infos, ctx.signer_seeds)
-        .map_err(Into::into)
+    // The borrowed amount comes back within the CPI, so only the fee shows up: what
+    // the borrower ends up short of and what reached the reserve's fee receiver
+    invoke_with_event!(
+        solana_program::program::invoke_signed(&ix, &accounts_infos, ctx.signer_seeds),
+        &ctx.accounts.destination_liquidity_pubkey,
+        &ctx.accounts.reserve_liquidity_fee_receiver_pubkey,
+        |source_delta, destination_delta| events::FlashLoanEvent {
+            reserve: ctx.accounts.reserve_pubkey.key(),
+            amount,
+            fee_paid: source_delta,
+            protocol_fee_received: destination_delta,
+        },
+    );
+
+    Ok(())
 }
 
 #[derive(Accounts)]
@@ -696,10 +907,10 @@ pub struct DepositReserveLiquidityAndObligationCollateralAccounts<'info> {
     pub lending_market_authority_pubkey: AccountInfo<'info>,
     pub destination_deposit_collateral_pubkey: AccountInfo<'info>,
     pub obligation_pubkey: AccountInfo<'info>,
-    pub obligation_owner_pubkey: Signer<'info>,
+    pub obligation_owner_pubkey: AccountInfo<'info>,
     pub reserve_liquidity_pyth_oracle_pubkey: AccountInfo<'info>,
     pub reserve_liquidity_switchboard_oracle_pubkey: AccountInfo<'info>,
-    pub user_transfer_authority_pubkey: Signer<'info>,
+    pub user_transfer_authority_pubkey: AccountInfo<'info>,
     pub clock_sysvar: Sysvar<'info, Clock>,
     pub token_program: Program<'info, TokenProgram>,
 }
@@ -714,8 +925,10 @@ pub fn deposit_reserve_liquidity_and_obligation_collateral<'a, 'b, 'c, 'info>(
     >,
     liquidity_amount: u64,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
+
     let ix = solend_token_lending::instruction::deposit_reserve_liquidity_and_obligation_collateral(
-        solend_token_lending::id(),
+        ctx.program.key(),
         liquidity_amount,
         ctx.accounts.source_liquidity_pubkey.key(),
         ctx.accounts.user_collateral_pubkey.key(),
@@ -732,30 +945,46 @@ pub fn deposit_reserve_liquidity_and_obligation_collateral<'a, 'b, 'c, 'info>(
             .key(),
         ctx.accounts.user_transfer_authority_pubkey.key(),
     );
-    solana_program::program::invoke_signed(
-        &ix,
-        &[
-            ctx.accounts.source_liquidity_pubkey.to_account_info(),
-            ctx.accounts.user_collateral_pubkey.to_account_info(),
-            ctx.accounts.reserve_pubkey.to_account_info(),
-            ctx.accounts.reserve_liquidity_supply_pubkey.to_account_info(),
-            ctx.accounts.reserve_collateral_mint_pubkey.to_account_info(),
-            ctx.accounts.lending_market_pubkey.to_account_info(),
-            ctx.accounts.lending_market_authority_pubkey.to_account_info(),
-            ctx.accounts.destination_deposit_collateral_pubkey.to_account_info(),
-            ctx.accounts.obligation_pubkey.to_account_info(),
-            ctx.accounts.obligation_owner_pubkey.to_account_info(),
-            ctx.accounts.reserve_liquidity_pyth_oracle_pubkey.to_account_info(),
-            ctx.accounts
-                .reserve_liquidity_switchboard_oracle_pubkey
-                .to_account_info(),
-            ctx.accounts.user_transfer_authority_pubkey.to_account_info(),
-            ctx.accounts.clock_sysvar.to_account_info(),
-            ctx.accounts.token_program.to_account_info()
-        ],
-        ctx.signer_seeds,
-    )
-    .map_err(Into::into)
+
+    invoke_with_event!(
+        solana_program::program::invoke_signed(
+            &ix,
+            &[
+                ctx.program.clone(),
+                ctx.accounts.source_liquidity_pubkey.to_account_info(),
+                ctx.accounts.user_collateral_pubkey.to_account_info(),
+                ctx.accounts.reserve_pubkey.to_account_info(),
+                ctx.accounts.reserve_liquidity_supply_pubkey.to_account_info(),
+                ctx.accounts.reserve_collateral_mint_pubkey.to_account_info(),
+                ctx.accounts.lending_market_pubkey.to_account_info(),
+                ctx.accounts.lending_market_authority_pubkey.to_account_info(),
+                ctx.accounts.destination_deposit_collateral_pubkey.to_account_info(),
+                ctx.accounts.obligation_pubkey.to_account_info(),
+                ctx.accounts.obligation_owner_pubkey.to_account_info(),
+                ctx.accounts.reserve_liquidity_pyth_oracle_pubkey.to_account_info(),
+                ctx.accounts
+                    .reserve_liquidity_switchboard_oracle_pubkey
+                    .to_account_info(),
+                ctx.accounts.user_transfer_authority_pubkey.to_account_info(),
+                ctx.accounts.clock_sysvar.to_account_info(),
+                ctx.accounts.token_program.to_account_info()
+            ],
+            ctx.signer_seeds,
+        ),
+        &ctx.accounts.source_liquidity_pubkey,
+        &ctx.accounts.destination_deposit_collateral_pubkey,
+        |source_delta, destination_delta| {
+            events::DepositReserveLiquidityAndObligationCollateralEvent {
+                reserve: ctx.accounts.reserve_pubkey.key(),
+                obligation: ctx.accounts.obligation_pubkey.key(),
+                liquidity_amount,
+                source_delta,
+                destination_delta,
+            }
+        },
+    );
+
+    Ok(())
 }
 
 #[derive(Accounts)]
@@ -763,7 +992,7 @@ pub struct UpdateReserveConfigAccounts<'info> {
     pub reserve_pubkey: AccountInfo<'info>,
     pub lending_market_pubkey: AccountInfo<'info>,
     pub lending_market_authority_pubkey: AccountInfo<'info>,
-    pub lending_market_owner_pubkey: Signer<'info>,
+    pub lending_market_owner_pubkey: AccountInfo<'info>,
     pub pyth_product_pubkey: AccountInfo<'info>,
     pub pyth_price_pubkey: AccountInfo<'info>,
     pub switchboard_feed_pubkey: AccountInfo<'info>,
@@ -773,8 +1002,9 @@ pub fn update_reserve_config<'a, 'b, 'c, 'info>(
     ctx: CpiContext<'a, 'b, 'c, 'info, UpdateReserveConfigAccounts<'info>>,
     config: ReserveConfig,
 ) -> ProgramResult {
+    check_program(&ctx.program)?;
     let ix = solend_token_lending::instruction::update_reserve_config(
-        solend_token_lending::id(),
+        ctx.program.key(),
         config,
         ctx.accounts.reserve_pubkey.key(),
         ctx.accounts.lending_market_pubkey.key(),
@@ -787,6 +1017,7 @@ pub fn update_reserve_config<'a, 'b, 'c, 'info>(
     solana_program::program::invoke_signed(
         &ix,
         &[
+            ctx.program.clone(),
             ctx.accounts.reserve_pubkey.to_account_info(),
             ctx.accounts.lending_market_pubkey.to_account_info(),
             ctx.accounts.lending_market_authority_pubkey.to_account_info(),
@@ -800,12 +1031,39 @@ pub fn update_reserve_config<'a, 'b, 'c, 'info>(
     .map_err(Into::into)
 }
 
+/// The Solend program, for use as `Program<'info, Solend>` and as the program of the
+/// `CpiContext` passed to the wrappers. Set `SOLEND_PROGRAM_ID` when building to target
+/// a fork deployed under another address; a malformed id fails the build.
 #[derive(Clone)]
 pub struct Solend;
 
+impl anchor_lang::AccountDeserialize for Solend {
+    fn try_deserialize(buf: &mut &[u8]) -> Result<Solend, anchor_lang::error::Error> {
+        Solend::try_deserialize_unchecked(buf)
+    }
+
+    fn try_deserialize_unchecked(_buf: &mut &[u8]) -> Result<Solend, anchor_lang::error::Error> {
+        Ok(Solend)
+    }
+}
+
+// `SOLEND_PROGRAM_ID` as decoded and checked by build.rs
+include!(concat!(env!("OUT_DIR"), "/solend_program_id.rs"));
+
 impl anchor_lang::Id for Solend {
     fn id() -> Pubkey {
-        solend_token_lending::id()
+        match SOLEND_PROGRAM_ID {
+            Some(program_id) => Pubkey::new_from_array(program_id),
+            None => solend_token_lending::id(),
+        }
+    }
+}
+
+/// Fails unless `program` is the Solend program the crate was built for
+pub fn check_program(program: &AccountInfo) -> ProgramResult {
+    if program.key() != Solend::id() {
+        return Err(anchor_lang::error!(error::SolendAnchorError::InvalidSolendProgram).into());
     }
+    Ok(())
 }
 
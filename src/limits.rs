@@ -0,0 +1,218 @@
+use anchor_lang::{
+    error,
+    prelude::CpiContext,
+    solana_program::{program_error::ProgramError, program_pack::Pack},
+};
+use solend_token_lending::{
+    math::{Decimal, TryAdd, TryDiv, TrySub},
+    state::Reserve,
+};
+
+use crate::{
+    borrow_obligation_liquidity, deposit_reserve_liquidity,
+    deposit_reserve_liquidity_and_obligation_collateral, error::SolendAnchorError,
+    host_fee::borrow_fee_split,
+    BorrowObligationLiquidityAccounts, DepositReserveLiquidityAccounts,
+    DepositReserveLiquidityAndObligationCollateralAccounts,
+};
+
+/// What the checked wrappers do with an amount over the reserve's limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimitCheck {
+    /// Fail before the CPI
+    Reject,
+    /// Lower the amount to what still fits
+    Clamp,
+}
+
+/// Liquidity sitting in the reserve supply, available to borrow or redeem
+pub fn available_liquidity(reserve: &Reserve) -> u64 {
+    reserve.liquidity.available_amount
+}
+
+/// Liquidity that can still be deposited before `deposit_limit` is reached
+pub fn remaining_deposit_capacity(reserve: &Reserve) -> Result<u64, ProgramError> {
+    let total_supply = reserve.liquidity.total_supply()?;
+    Ok(Decimal::from(reserve.config.deposit_limit)
+        .try_sub(total_supply)
+        .unwrap_or_else(|_| Decimal::zero())
+        .try_floor_u64()?)
+}
+
+/// Debt that can still be taken on, the smaller of the room left under `borrow_limit`
+/// and the available liquidity. Solend counts the borrow fee against both.
+pub fn remaining_borrow_capacity(reserve: &Reserve) -> Result<u64, ProgramError> {
+    let under_limit = Decimal::from(reserve.config.borrow_limit)
+        .try_sub(reserve.liquidity.borrowed_amount_wads)
+        .unwrap_or_else(|_| Decimal::zero())
+        .try_floor_u64()?;
+    Ok(under_limit.min(available_liquidity(reserve)))
+}
+
+/// Largest amount that can still be requested from `borrow_obligation_liquidity`, so
+/// that it plus its borrow fee fits in `remaining_borrow_capacity`
+pub fn max_borrow_amount(reserve: &Reserve) -> Result<u64, ProgramError> {
+    let capacity = remaining_borrow_capacity(reserve)?;
+    let fee_rate = Decimal::from_scaled_val(reserve.config.fees.borrow_fee_wad as u128);
+    let mut amount = Decimal::from(capacity)
+        .try_div(Decimal::one().try_add(fee_rate)?)?
+        .try_floor_u64()?;
+    while amount > 0 && !borrow_fits(reserve, amount, capacity) {
+        amount -= 1;
+    }
+    Ok(amount)
+}
+
+/// Whether borrowing `amount` adds at most `capacity` of debt. Amounts Solend
+/// rejects as too small for their fee never fit.
+fn borrow_fits(reserve: &Reserve, amount: u64, capacity: u64) -> bool {
+    borrow_fee_split(reserve, amount)
+        .ok()
+        .and_then(|fee_split| amount.checked_add(fee_split.total_fee))
+        .map_or(false, |debt| debt <= capacity)
+}
+
+fn apply_limit(
+    amount: u64,
+    capacity: u64,
+    check: LimitCheck,
+    limit_error: SolendAnchorError,
+) -> Result<u64, ProgramError> {
+    if capacity == 0 || (amount > capacity && check == LimitCheck::Reject) {
+        return Err(error!(limit_error).into());
+    }
+    Ok(amount.min(capacity))
+}
+
+/// Deposits after checking `deposit_limit`, returning the amount deposited
+pub fn deposit_reserve_liquidity_checked<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, DepositReserveLiquidityAccounts<'info>>,
+    liquidity_amount: u64,
+    check: LimitCheck,
+) -> Result<u64, ProgramError> {
+    let reserve = Reserve::unpack(&ctx.accounts.reserve_pubkey.try_borrow_data()?)?;
+    let liquidity_amount = apply_limit(
+        liquidity_amount,
+        remaining_deposit_capacity(&reserve)?,
+        check,
+        SolendAnchorError::DepositLimitExceeded,
+    )?;
+    deposit_reserve_liquidity(ctx, liquidity_amount)?;
+    Ok(liquidity_amount)
+}
+
+/// Deposits into the obligation after checking `deposit_limit`, returning the amount deposited
+pub fn deposit_reserve_liquidity_and_obligation_collateral_checked<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<
+        'a,
+        'b,
+        'c,
+        'info,
+        DepositReserveLiquidityAndObligationCollateralAccounts<'info>,
+    >,
+    liquidity_amount: u64,
+    check: LimitCheck,
+) -> Result<u64, ProgramError> {
+    let reserve = Reserve::unpack(&ctx.accounts.reserve_pubkey.try_borrow_data()?)?;
+    let liquidity_amount = apply_limit(
+        liquidity_amount,
+        remaining_deposit_capacity(&reserve)?,
+        check,
+        SolendAnchorError::DepositLimitExceeded,
+    )?;
+    deposit_reserve_liquidity_and_obligation_collateral(ctx, liquidity_amount)?;
+    Ok(liquidity_amount)
+}
+
+/// Borrows after checking `borrow_limit` and the available liquidity against the amount
+/// plus its borrow fee, returning the amount requested from Solend. Solend's `u64::MAX`
+/// borrow-the-maximum sentinel is passed through unchecked.
+pub fn borrow_obligation_liquidity_checked<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, BorrowObligationLiquidityAccounts<'info>>,
+    liquidity_amount: u64,
+    check: LimitCheck,
+) -> Result<u64, ProgramError> {
+    let liquidity_amount = if liquidity_amount == u64::MAX {
+        liquidity_amount
+    } else {
+        let reserve = Reserve::unpack(&ctx.accounts.borrow_reserve_pubkey.try_borrow_data()?)?;
+        apply_limit(
+            liquidity_amount,
+            max_borrow_amount(&reserve)?,
+            check,
+            SolendAnchorError::BorrowLimitExceeded,
+        )?
+    };
+    borrow_obligation_liquidity(ctx, liquidity_amount)?;
+    Ok(liquidity_amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reserve with `available` liquidity, `borrowed` debt and a 1% borrow fee
+    fn reserve(available: u64, borrowed: u64, borrow_limit: u64, deposit_limit: u64) -> Reserve {
+        let mut reserve = Reserve::default();
+        reserve.liquidity.available_amount = available;
+        reserve.liquidity.borrowed_amount_wads = Decimal::from(borrowed);
+        reserve.config.borrow_limit = borrow_limit;
+        reserve.config.deposit_limit = deposit_limit;
+        reserve.config.fees.borrow_fee_wad = 10_000_000_000_000_000;
+        reserve
+    }
+
+    fn limit_error() -> ProgramError {
+        error!(SolendAnchorError::BorrowLimitExceeded).into()
+    }
+
+    #[test]
+    fn apply_limit_passes_amounts_that_fit() {
+        for check in [LimitCheck::Reject, LimitCheck::Clamp] {
+            let amount = apply_limit(100, 100, check, SolendAnchorError::BorrowLimitExceeded);
+            assert_eq!(amount, Ok(100));
+        }
+    }
+
+    #[test]
+    fn apply_limit_rejects_or_clamps_amounts_over_capacity() {
+        let limit = |check| apply_limit(101, 100, check, SolendAnchorError::BorrowLimitExceeded);
+        assert_eq!(limit(LimitCheck::Reject), Err(limit_error()));
+        assert_eq!(limit(LimitCheck::Clamp), Ok(100));
+    }
+
+    #[test]
+    fn apply_limit_fails_without_capacity() {
+        for check in [LimitCheck::Reject, LimitCheck::Clamp] {
+            let amount = apply_limit(1, 0, check, SolendAnchorError::BorrowLimitExceeded);
+            assert_eq!(amount, Err(limit_error()));
+        }
+    }
+
+    #[test]
+    fn deposit_capacity_is_room_under_deposit_limit() {
+        assert_eq!(remaining_deposit_capacity(&reserve(600, 300, 0, 1_000)), Ok(100));
+        assert_eq!(remaining_deposit_capacity(&reserve(600, 500, 0, 1_000)), Ok(0));
+    }
+
+    #[test]
+    fn borrow_capacity_is_bounded_by_limit_and_available_liquidity() {
+        assert_eq!(remaining_borrow_capacity(&reserve(5_000, 1_000, 3_000, 0)), Ok(2_000));
+        assert_eq!(remaining_borrow_capacity(&reserve(1_500, 1_000, 3_000, 0)), Ok(1_500));
+        assert_eq!(remaining_borrow_capacity(&reserve(5_000, 4_000, 3_000, 0)), Ok(0));
+    }
+
+    #[test]
+    fn max_borrow_amount_leaves_room_for_the_borrow_fee() {
+        let reserve = reserve(1_010, 0, u64::MAX, 0);
+        let amount = max_borrow_amount(&reserve).unwrap();
+        assert_eq!(amount, 1_000);
+        assert_eq!(borrow_fee_split(&reserve, amount).unwrap().total_fee, 10);
+        assert!(!borrow_fits(&reserve, amount + 1, 1_010));
+    }
+
+    #[test]
+    fn max_borrow_amount_is_zero_when_only_the_fee_would_fit() {
+        assert_eq!(max_borrow_amount(&reserve(1, 0, u64::MAX, 0)), Ok(0));
+    }
+}
@@ -0,0 +1,328 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use anchor_lang::{
+    error,
+    prelude::Pubkey,
+    solana_program::{instruction::Instruction, program_error::ProgramError},
+};
+use solend_token_lending::{
+    math::{Decimal, TryAdd, TryDiv, TryMul, TrySub},
+    state::{Obligation, Reserve},
+};
+
+use crate::{
+    builder::{refresh_obligation_instructions, refresh_reserve_instruction},
+    error::SolendAnchorError,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LiquidationOpportunity {
+    pub obligation: Pubkey,
+    pub repay_reserve: Pubkey,
+    pub withdraw_reserve: Pubkey,
+    /// Liquidity to pass to `liquidate_obligation`
+    pub repay_amount: u64,
+    /// Collateral tokens seized, liquidation bonus included
+    pub withdraw_collateral_amount: u64,
+    /// Liquidity the seized collateral redeems for
+    pub withdraw_liquidity_amount: u64,
+    pub repay_value: Decimal,
+    pub withdraw_value: Decimal,
+    /// `withdraw_value - repay_value`, in the market's quote currency
+    pub profit: Decimal,
+}
+
+pub(crate) fn find_reserve<'r>(
+    reserves: &'r HashMap<Pubkey, Reserve>,
+    key: &Pubkey,
+) -> Result<&'r Reserve, ProgramError> {
+    reserves
+        .get(key)
+        .ok_or_else(|| error!(SolendAnchorError::MissingReserve).into())
+}
+
+/// Market value of `amount` liquidity at the reserve's last refreshed price
+pub fn liquidity_market_value(reserve: &Reserve, amount: Decimal) -> Result<Decimal, ProgramError> {
+    let decimals = 10u64
+        .checked_pow(reserve.liquidity.mint_decimals as u32)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    amount
+        .try_mul(reserve.liquidity.market_price)?
+        .try_div(decimals)
+}
+
+/// Recomputes an obligation's market values from the given reserves, the same way
+/// `refresh_obligation` does on chain. Reserve interest is taken as of their last refresh.
+pub fn refreshed_obligation(
+    obligation: &Obligation,
+    reserves: &HashMap<Pubkey, Reserve>,
+) -> Result<Obligation, ProgramError> {
+    let mut obligation = obligation.clone();
+    let mut deposited_value = Decimal::zero();
+    let mut borrowed_value = Decimal::zero();
+    let mut allowed_borrow_value = Decimal::zero();
+    let mut unhealthy_borrow_value = Decimal::zero();
+
+    for collateral in obligation.deposits.iter_mut() {
+        let reserve = find_reserve(reserves, &collateral.deposit_reserve)?;
+        let liquidity_amount = reserve
+            .collateral_exchange_rate()?
+            .decimal_collateral_to_liquidity(collateral.deposited_amount.into())?;
+        let market_value = liquidity_market_value(reserve, liquidity_amount)?;
+        collateral.market_value = market_value;
+
+        deposited_value = deposited_value.try_add(market_value)?;
+        allowed_borrow_value = allowed_borrow_value.try_add(
+            market_value.try_mul(Decimal::from_percent(reserve.config.loan_to_value_ratio))?,
+        )?;
+        unhealthy_borrow_value = unhealthy_borrow_value.try_add(
+            market_value.try_mul(Decimal::from_percent(reserve.config.liquidation_threshold))?,
+        )?;
+    }
+
+    for liquidity in obligation.borrows.iter_mut() {
+        let reserve = find_reserve(reserves, &liquidity.borrow_reserve)?;
+        liquidity.accrue_interest(reserve.liquidity.cumulative_borrow_rate_wads)?;
+        let market_value = liquidity_market_value(reserve, liquidity.borrowed_amount_wads)?;
+        liquidity.market_value = market_value;
+        borrowed_value = borrowed_value.try_add(market_value)?;
+    }
+
+    obligation.deposited_value = deposited_value;
+    obligation.borrowed_value = borrowed_value;
+    obligation.allowed_borrow_value = allowed_borrow_value;
+    obligation.unhealthy_borrow_value = unhealthy_borrow_value;
+    Ok(obligation)
+}
+
+/// Whether a refreshed obligation can be liquidated
+pub fn is_unhealthy(obligation: &Obligation) -> bool {
+    !obligation.borrows.is_empty() && obligation.borrowed_value > obligation.unhealthy_borrow_value
+}
+
+/// Evaluates every repay/withdraw reserve pair of an unhealthy obligation and returns
+/// them ranked by profit. Healthy obligations yield no opportunities.
+pub fn obligation_opportunities(
+    obligation_pubkey: Pubkey,
+    obligation: &Obligation,
+    reserves: &HashMap<Pubkey, Reserve>,
+) -> Result<Vec<LiquidationOpportunity>, ProgramError> {
+    let obligation = refreshed_obligation(obligation, reserves)?;
+    if !is_unhealthy(&obligation) {
+        return Ok(vec![]);
+    }
+
+    let mut opportunities = vec![];
+    for liquidity in &obligation.borrows {
+        let repay_reserve = find_reserve(reserves, &liquidity.borrow_reserve)?;
+        for collateral in &obligation.deposits {
+            let withdraw_reserve = find_reserve(reserves, &collateral.deposit_reserve)?;
+            // Solend caps the amount itself when asked to liquidate u64::MAX. The bonus is
+            // the collateral's, so the calculation runs on the withdraw reserve as on chain.
+            let result = withdraw_reserve.calculate_liquidation(
+                u64::MAX,
+                &obligation,
+                liquidity,
+                collateral,
+            )?;
+            if result.repay_amount == 0 || result.withdraw_amount == 0 {
+                continue;
+            }
+
+            let withdraw_liquidity_amount = withdraw_reserve
+                .collateral_exchange_rate()?
+                .collateral_to_liquidity(result.withdraw_amount)?;
+            let repay_value = liquidity_market_value(repay_reserve, result.repay_amount.into())?;
+            let withdraw_value =
+                liquidity_market_value(withdraw_reserve, withdraw_liquidity_amount.into())?;
+            let profit = withdraw_value
+                .try_sub(repay_value)
+                .unwrap_or_else(|_| Decimal::zero());
+
+            opportunities.push(LiquidationOpportunity {
+                obligation: obligation_pubkey,
+                repay_reserve: liquidity.borrow_reserve,
+                withdraw_reserve: collateral.deposit_reserve,
+                repay_amount: result.repay_amount,
+                withdraw_collateral_amount: result.withdraw_amount,
+                withdraw_liquidity_amount,
+                repay_value,
+                withdraw_value,
+                profit,
+            });
+        }
+    }
+
+    sort_by_profit(&mut opportunities);
+    Ok(opportunities)
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScanResult {
+    /// The most profitable reserve pair of each unhealthy obligation, best first
+    pub opportunities: Vec<LiquidationOpportunity>,
+    /// Obligations that could not be evaluated, such as those referencing reserves
+    /// missing from `reserves`
+    pub failures: Vec<(Pubkey, ProgramError)>,
+}
+
+/// Scans a set of decoded obligations for liquidation opportunities
+pub fn scan_obligations(
+    obligations: &[(Pubkey, Obligation)],
+    reserves: &HashMap<Pubkey, Reserve>,
+) -> ScanResult {
+    let mut result = ScanResult::default();
+    for (pubkey, obligation) in obligations {
+        match obligation_opportunities(*pubkey, obligation, reserves) {
+            Ok(opportunities) => result.opportunities.extend(opportunities.into_iter().next()),
+            Err(err) => result.failures.push((*pubkey, err)),
+        }
+    }
+    sort_by_profit(&mut result.opportunities);
+    result
+}
+
+fn sort_by_profit(opportunities: &mut [LiquidationOpportunity]) {
+    opportunities.sort_by(|a, b| b.profit.partial_cmp(&a.profit).unwrap_or(Ordering::Equal));
+}
+
+/// Token accounts owned by the liquidator
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LiquidatorAccounts {
+    pub transfer_authority: Pubkey,
+    /// Holds the repay reserve's liquidity
+    pub source_liquidity: Pubkey,
+    /// Receives the withdraw reserve's collateral
+    pub destination_collateral: Pubkey,
+    /// Receives the withdraw reserve's liquidity once the collateral is redeemed
+    pub destination_liquidity: Pubkey,
+}
+
+/// Refreshes the obligation, liquidates it and redeems the seized collateral.
+pub fn liquidation_instructions(
+    program_id: Pubkey,
+    opportunity: &LiquidationOpportunity,
+    obligation: &Obligation,
+    reserves: &HashMap<Pubkey, Reserve>,
+    liquidator: &LiquidatorAccounts,
+) -> Result<Vec<Instruction>, ProgramError> {
+    let repay_reserve = find_reserve(reserves, &opportunity.repay_reserve)?;
+    let withdraw_reserve = find_reserve(reserves, &opportunity.withdraw_reserve)?;
+
+    let mut instructions = refresh_obligation_instructions(
+        program_id,
+        opportunity.obligation,
+        obligation,
+        reserves,
+    )?;
+    instructions.push(solend_token_lending::instruction::liquidate_obligation(
+        program_id,
+        opportunity.repay_amount,
+        liquidator.source_liquidity,
+        liquidator.destination_collateral,
+        opportunity.repay_reserve,
+        repay_reserve.liquidity.supply_pubkey,
+        opportunity.withdraw_reserve,
+        withdraw_reserve.collateral.supply_pubkey,
+        opportunity.obligation,
+        obligation.lending_market,
+        liquidator.transfer_authority,
+    ));
+
+    // Liquidating marks the withdraw reserve stale, and redeeming needs it fresh
+    instructions.push(refresh_reserve_instruction(
+        program_id,
+        opportunity.withdraw_reserve,
+        withdraw_reserve,
+    ));
+    instructions.push(solend_token_lending::instruction::redeem_reserve_collateral(
+        program_id,
+        opportunity.withdraw_collateral_amount,
+        liquidator.destination_collateral,
+        liquidator.destination_liquidity,
+        opportunity.withdraw_reserve,
+        withdraw_reserve.collateral.mint_pubkey,
+        withdraw_reserve.liquidity.supply_pubkey,
+        obligation.lending_market,
+        liquidator.transfer_authority,
+    ));
+    Ok(instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use solend_token_lending::state::{ObligationCollateral, ObligationLiquidity};
+
+    use super::*;
+
+    const ONE: u64 = 1_000_000;
+
+    fn reserve(price: u64, liquidation_bonus: u8) -> Reserve {
+        let mut reserve = Reserve::default();
+        reserve.liquidity.mint_decimals = 6;
+        reserve.liquidity.market_price = Decimal::from(price);
+        reserve.liquidity.cumulative_borrow_rate_wads = Decimal::one();
+        reserve.config.loan_to_value_ratio = 50;
+        reserve.config.liquidation_threshold = 55;
+        reserve.config.liquidation_bonus = liquidation_bonus;
+        reserve
+    }
+
+    /// 100 tokens of $10 collateral with a 5% bonus against `borrowed` $1 tokens of
+    /// debt, whose reserve has no bonus of its own
+    fn market(borrowed: u64) -> (Pubkey, Obligation, HashMap<Pubkey, Reserve>) {
+        let (withdraw_reserve, repay_reserve) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut collateral = ObligationCollateral::new(withdraw_reserve);
+        collateral.deposited_amount = 100 * ONE;
+        let mut liquidity = ObligationLiquidity::new(repay_reserve);
+        liquidity.borrowed_amount_wads = Decimal::from(borrowed);
+        let obligation = Obligation {
+            deposits: vec![collateral],
+            borrows: vec![liquidity],
+            ..Obligation::default()
+        };
+
+        let mut reserves = HashMap::new();
+        reserves.insert(withdraw_reserve, reserve(10, 5));
+        reserves.insert(repay_reserve, reserve(1, 0));
+        (Pubkey::new_unique(), obligation, reserves)
+    }
+
+    #[test]
+    fn profit_uses_the_collateral_bonus() {
+        let (pubkey, obligation, reserves) = market(600 * ONE);
+        let opportunities = obligation_opportunities(pubkey, &obligation, &reserves).unwrap();
+        assert_eq!(opportunities.len(), 1);
+
+        // 20% of the $600 debt is closed, seizing $126 of collateral with the 5% bonus
+        let opportunity = &opportunities[0];
+        assert_eq!(opportunity.repay_amount, 120 * ONE);
+        assert_eq!(opportunity.withdraw_collateral_amount, 12_600_000);
+        assert_eq!(opportunity.withdraw_liquidity_amount, 12_600_000);
+        assert_eq!(opportunity.repay_value, Decimal::from(120u64));
+        assert_eq!(opportunity.withdraw_value, Decimal::from(126u64));
+        assert_eq!(opportunity.profit, Decimal::from(6u64));
+    }
+
+    #[test]
+    fn healthy_obligation_has_no_opportunities() {
+        let (pubkey, obligation, reserves) = market(500 * ONE);
+        assert!(obligation_opportunities(pubkey, &obligation, &reserves)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn scan_reports_obligations_it_cannot_evaluate() {
+        let (pubkey, obligation, reserves) = market(600 * ONE);
+        let (orphan, orphan_obligation, _) = market(600 * ONE);
+        let result = scan_obligations(
+            &[(pubkey, obligation), (orphan, orphan_obligation)],
+            &reserves,
+        );
+        assert_eq!(result.opportunities.len(), 1);
+        assert_eq!(result.opportunities[0].obligation, pubkey);
+        assert_eq!(result.failures.len(), 1);
+        assert_eq!(result.failures[0].0, orphan);
+    }
+}
@@ -0,0 +1,165 @@
+use anchor_lang::{
+    prelude::{AccountInfo, CpiContext},
+    solana_program::{entrypoint::ProgramResult, program_error::ProgramError},
+};
+
+use crate::{
+    borrow_obligation_liquidity, deposit_reserve_liquidity,
+    deposit_reserve_liquidity_and_obligation_collateral, liquidate_obligation,
+    redeem_reserve_collateral, repay_obligation_liquidity, token::TokenBalances,
+    withdraw_obligation_collateral, BorrowObligationLiquidityAccounts,
+    DepositReserveLiquidityAccounts, DepositReserveLiquidityAndObligationCollateralAccounts,
+    LiquidateObligationAccounts, RedeemReserveCollateralAccounts,
+    RepayObligationLiquidityAccounts, WithdrawObligationCollateralAccounts,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DepositResult {
+    pub liquidity_spent: u64,
+    pub collateral_received: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RedeemResult {
+    pub collateral_spent: u64,
+    pub liquidity_received: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WithdrawResult {
+    pub collateral_received: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BorrowResult {
+    pub liquidity_received: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RepayResult {
+    pub liquidity_repaid: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LiquidateResult {
+    pub liquidity_repaid: u64,
+    pub collateral_received: u64,
+}
+
+/// Runs `cpi` and returns what left `source` and arrived in `destination`
+fn measure<'info>(
+    source: AccountInfo<'info>,
+    destination: AccountInfo<'info>,
+    cpi: impl FnOnce() -> ProgramResult,
+) -> Result<(u64, u64), ProgramError> {
+    let balances = TokenBalances::measure(&source, &destination)?;
+    cpi()?;
+    balances.deltas(&source, &destination)
+}
+
+pub fn deposit_reserve_liquidity_measured<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, DepositReserveLiquidityAccounts<'info>>,
+    liquidity_amount: u64,
+) -> Result<DepositResult, ProgramError> {
+    let (liquidity_spent, collateral_received) = measure(
+        ctx.accounts.source_liquidity_pubkey.clone(),
+        ctx.accounts.destination_collateral_pubkey.clone(),
+        || deposit_reserve_liquidity(ctx, liquidity_amount),
+    )?;
+    Ok(DepositResult {
+        liquidity_spent,
+        collateral_received,
+    })
+}
+
+/// The collateral lands in the reserve's collateral supply, so `collateral_received`
+/// is what the obligation was credited with
+pub fn deposit_reserve_liquidity_and_obligation_collateral_measured<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<
+        'a,
+        'b,
+        'c,
+        'info,
+        DepositReserveLiquidityAndObligationCollateralAccounts<'info>,
+    >,
+    liquidity_amount: u64,
+) -> Result<DepositResult, ProgramError> {
+    let (liquidity_spent, collateral_received) = measure(
+        ctx.accounts.source_liquidity_pubkey.clone(),
+        ctx.accounts.destination_deposit_collateral_pubkey.clone(),
+        || deposit_reserve_liquidity_and_obligation_collateral(ctx, liquidity_amount),
+    )?;
+    Ok(DepositResult {
+        liquidity_spent,
+        collateral_received,
+    })
+}
+
+pub fn redeem_reserve_collateral_measured<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, RedeemReserveCollateralAccounts<'info>>,
+    collateral_amount: u64,
+) -> Result<RedeemResult, ProgramError> {
+    let (collateral_spent, liquidity_received) = measure(
+        ctx.accounts.source_collateral_pubkey.clone(),
+        ctx.accounts.destination_liquidity_pubkey.clone(),
+        || redeem_reserve_collateral(ctx, collateral_amount),
+    )?;
+    Ok(RedeemResult {
+        collateral_spent,
+        liquidity_received,
+    })
+}
+
+pub fn withdraw_obligation_collateral_measured<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, WithdrawObligationCollateralAccounts<'info>>,
+    collateral_amount: u64,
+) -> Result<WithdrawResult, ProgramError> {
+    let (_, collateral_received) = measure(
+        ctx.accounts.source_collateral_pubkey.clone(),
+        ctx.accounts.destination_collateral_pubkey.clone(),
+        || withdraw_obligation_collateral(ctx, collateral_amount),
+    )?;
+    Ok(WithdrawResult {
+        collateral_received,
+    })
+}
+
+pub fn borrow_obligation_liquidity_measured<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, BorrowObligationLiquidityAccounts<'info>>,
+    liquidity_amount: u64,
+) -> Result<BorrowResult, ProgramError> {
+    let (_, liquidity_received) = measure(
+        ctx.accounts.source_liquidity_pubkey.clone(),
+        ctx.accounts.destination_liquidity_pubkey.clone(),
+        || borrow_obligation_liquidity(ctx, liquidity_amount),
+    )?;
+    Ok(BorrowResult { liquidity_received })
+}
+
+/// Reports the real amount repaid when `liquidity_amount` is `u64::MAX`
+pub fn repay_obligation_liquidity_measured<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, RepayObligationLiquidityAccounts<'info>>,
+    liquidity_amount: u64,
+) -> Result<RepayResult, ProgramError> {
+    let (liquidity_repaid, _) = measure(
+        ctx.accounts.source_liquidity_pubkey.clone(),
+        ctx.accounts.destination_liquidity_pubkey.clone(),
+        || repay_obligation_liquidity(ctx, liquidity_amount),
+    )?;
+    Ok(RepayResult { liquidity_repaid })
+}
+
+pub fn liquidate_obligation_measured<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, LiquidateObligationAccounts<'info>>,
+    liquidity_amount: u64,
+) -> Result<LiquidateResult, ProgramError> {
+    let (liquidity_repaid, collateral_received) = measure(
+        ctx.accounts.source_liquidity_pubkey.clone(),
+        ctx.accounts.destination_collateral_pubkey.clone(),
+        || liquidate_obligation(ctx, liquidity_amount),
+    )?;
+    Ok(LiquidateResult {
+        liquidity_repaid,
+        collateral_received,
+    })
+}
@@ -0,0 +1,142 @@
+use anchor_lang::{
+    error,
+    prelude::{AccountInfo, CpiContext, Program, Rent, System, Sysvar},
+    solana_program::{self, entrypoint::ProgramResult, program_pack::Pack, system_instruction},
+    Accounts, Key, ToAccountInfo,
+};
+
+use crate::{
+    borrow_obligation_liquidity, deposit_reserve_liquidity, error::SolendAnchorError,
+    repay_obligation_liquidity, BorrowObligationLiquidityAccounts, DepositReserveLiquidityAccounts,
+    RepayObligationLiquidityAccounts, TokenProgram,
+};
+
+/// Accounts for a temporary wrapped SOL token account. `user` funds it, owns it and
+/// gets every lamport back when it is closed, so it must also be the Solend
+/// transfer authority when the wrapped SOL is spent.
+#[derive(Accounts)]
+pub struct NativeSolAccounts<'info> {
+    pub user: AccountInfo<'info>,
+    /// Uninitialized; a new keypair or a PDA of the calling program signing via `ctx`
+    pub wsol_account: AccountInfo<'info>,
+    /// Must be `spl_token::native_mint::id()`
+    pub native_mint: AccountInfo<'info>,
+    pub rent_sysvar: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Creates `wsol_account` holding `lamports` of wrapped SOL on top of its rent
+pub fn wrap_sol<'info>(
+    native: &NativeSolAccounts<'info>,
+    token_program: &AccountInfo<'info>,
+    lamports: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    if native.native_mint.key() != spl_token::native_mint::id() {
+        return Err(error!(SolendAnchorError::InvalidNativeMint).into());
+    }
+
+    let space = spl_token::state::Account::LEN;
+    let ix = system_instruction::create_account(
+        &native.user.key(),
+        &native.wsol_account.key(),
+        native.rent_sysvar.minimum_balance(space) + lamports,
+        space as u64,
+        &spl_token::id(),
+    );
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            native.user.clone(),
+            native.wsol_account.clone(),
+            native.system_program.to_account_info(),
+        ],
+        signer_seeds,
+    )?;
+
+    // Initializing a native account sets its token amount from the lamports above rent
+    let ix = spl_token::instruction::initialize_account(
+        &spl_token::id(),
+        &native.wsol_account.key(),
+        &spl_token::native_mint::id(),
+        &native.user.key(),
+    )?;
+    solana_program::program::invoke(
+        &ix,
+        &[
+            native.wsol_account.clone(),
+            native.native_mint.clone(),
+            native.user.clone(),
+            native.rent_sysvar.to_account_info(),
+            token_program.clone(),
+        ],
+    )
+}
+
+/// Closes `wsol_account`, returning its wrapped SOL and rent to `user` as lamports
+pub fn unwrap_sol<'info>(
+    native: &NativeSolAccounts<'info>,
+    token_program: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let ix = spl_token::instruction::close_account(
+        &spl_token::id(),
+        &native.wsol_account.key(),
+        &native.user.key(),
+        &native.user.key(),
+        &[],
+    )?;
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            native.wsol_account.clone(),
+            native.user.clone(),
+            native.user.clone(),
+            token_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+/// Deposits `lamports` of SOL through a temporary wrapped SOL account, which must be
+/// the context's `source_liquidity_pubkey`
+pub fn deposit_reserve_liquidity_sol<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, DepositReserveLiquidityAccounts<'info>>,
+    native: &NativeSolAccounts<'info>,
+    lamports: u64,
+) -> ProgramResult {
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let signer_seeds = ctx.signer_seeds;
+    wrap_sol(native, &token_program, lamports, signer_seeds)?;
+    deposit_reserve_liquidity(ctx, lamports)?;
+    unwrap_sol(native, &token_program, signer_seeds)
+}
+
+/// Borrows SOL into a temporary wrapped SOL account, which must be the context's
+/// `destination_liquidity_pubkey`, and pays it out to `user` as lamports
+pub fn borrow_obligation_liquidity_sol<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, BorrowObligationLiquidityAccounts<'info>>,
+    native: &NativeSolAccounts<'info>,
+    liquidity_amount: u64,
+) -> ProgramResult {
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let signer_seeds = ctx.signer_seeds;
+    wrap_sol(native, &token_program, 0, signer_seeds)?;
+    borrow_obligation_liquidity(ctx, liquidity_amount)?;
+    unwrap_sol(native, &token_program, signer_seeds)
+}
+
+/// Repays with up to `lamports` of SOL wrapped into a temporary account, which must be
+/// the context's `source_liquidity_pubkey`. Whatever is not repaid goes back to `user`.
+pub fn repay_obligation_liquidity_sol<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, RepayObligationLiquidityAccounts<'info>>,
+    native: &NativeSolAccounts<'info>,
+    lamports: u64,
+    liquidity_amount: u64,
+) -> ProgramResult {
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let signer_seeds = ctx.signer_seeds;
+    wrap_sol(native, &token_program, lamports, signer_seeds)?;
+    repay_obligation_liquidity(ctx, liquidity_amount)?;
+    unwrap_sol(native, &token_program, signer_seeds)
+}
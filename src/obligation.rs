@@ -0,0 +1,202 @@
+use anchor_lang::{
+    error,
+    prelude::{AccountInfo, Clock, CpiContext, Program, Pubkey, Rent, System, Sysvar},
+    solana_program::{self, entrypoint::ProgramResult, program_pack::Pack, system_instruction},
+    Accounts, Id, Key, ToAccountInfo, ToAccountInfos, ToAccountMetas,
+};
+use solend_token_lending::state::{Obligation, MAX_OBLIGATION_RESERVES};
+
+use crate::{
+    borrow_obligation_liquidity, check_program, deposit_obligation_collateral,
+    error::SolendAnchorError, init_obligation, BorrowObligationLiquidityAccounts,
+    DepositObligationCollateralAccounts, InitObligationAccounts, Solend, TokenProgram,
+};
+
+/// Seed Solend's UI uses for an owner's obligation: the first 32 characters of the
+/// lending market address, the longest seed `create_with_seed` accepts
+pub fn obligation_seed(lending_market: &Pubkey) -> String {
+    lending_market.to_string()[..32].to_string()
+}
+
+/// Address of `owner`'s obligation in `lending_market`, as created by Solend's UI
+/// and by `create_obligation_with_seed`
+pub fn obligation_address(owner: &Pubkey, lending_market: &Pubkey) -> Pubkey {
+    Pubkey::create_with_seed(owner, &obligation_seed(lending_market), &Solend::id())
+        .expect("obligation seed is at most 32 bytes")
+}
+
+#[derive(Accounts)]
+pub struct CreateObligationAccounts<'info> {
+    pub payer: AccountInfo<'info>,
+    /// Must be `obligation_address(obligation_owner_pubkey, lending_market_pubkey)`
+    pub obligation_pubkey: AccountInfo<'info>,
+    pub lending_market_pubkey: AccountInfo<'info>,
+    /// Base of the seeded address, so it signs the allocation as well as `init_obligation`
+    pub obligation_owner_pubkey: AccountInfo<'info>,
+    pub clock_sysvar: Sysvar<'info, Clock>,
+    pub rent_sysvar: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+/// Allocates the owner's obligation at its seed-derived address and initializes it
+pub fn create_obligation_with_seed<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, CreateObligationAccounts<'info>>,
+) -> ProgramResult {
+    check_program(&ctx.program)?;
+    let seed = obligation_seed(&ctx.accounts.lending_market_pubkey.key());
+    let ix = system_instruction::create_account_with_seed(
+        &ctx.accounts.payer.key(),
+        &ctx.accounts.obligation_pubkey.key(),
+        &ctx.accounts.obligation_owner_pubkey.key(),
+        &seed,
+        ctx.accounts.rent_sysvar.minimum_balance(Obligation::LEN),
+        Obligation::LEN as u64,
+        &ctx.program.key(),
+    );
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            ctx.accounts.payer.to_account_info(),
+            ctx.accounts.obligation_pubkey.to_account_info(),
+            ctx.accounts.obligation_owner_pubkey.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+        ],
+        ctx.signer_seeds,
+    )?;
+
+    init_obligation(CpiContext::new_with_signer(
+        ctx.program.clone(),
+        InitObligationAccounts {
+            obligation_pubkey: ctx.accounts.obligation_pubkey.clone(),
+            lending_market_pubkey: ctx.accounts.lending_market_pubkey.clone(),
+            obligation_owner_pubkey: ctx.accounts.obligation_owner_pubkey.clone(),
+            clock_sysvar: ctx.accounts.clock_sysvar.clone(),
+            rent_sysvar: ctx.accounts.rent_sysvar.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+        },
+        ctx.signer_seeds,
+    ))
+}
+
+/// Deposit and borrow positions still available to the obligation. Solend caps the
+/// deposits and borrows together at `MAX_OBLIGATION_RESERVES`.
+pub fn remaining_reserve_slots(obligation: &Obligation) -> usize {
+    MAX_OBLIGATION_RESERVES.saturating_sub(obligation.deposits.len() + obligation.borrows.len())
+}
+
+pub fn has_deposit_slot(obligation: &Obligation, reserve: &Pubkey) -> bool {
+    obligation
+        .deposits
+        .iter()
+        .any(|collateral| collateral.deposit_reserve == *reserve)
+}
+
+pub fn has_borrow_slot(obligation: &Obligation, reserve: &Pubkey) -> bool {
+    obligation
+        .borrows
+        .iter()
+        .any(|liquidity| liquidity.borrow_reserve == *reserve)
+}
+
+/// Fails unless depositing into `reserve` reuses a position or a slot is free
+pub fn check_deposit_slot(obligation: &Obligation, reserve: &Pubkey) -> ProgramResult {
+    if !has_deposit_slot(obligation, reserve) && remaining_reserve_slots(obligation) == 0 {
+        return Err(error!(SolendAnchorError::ObligationReserveLimit).into());
+    }
+    Ok(())
+}
+
+/// Fails unless borrowing from `reserve` reuses a position or a slot is free
+pub fn check_borrow_slot(obligation: &Obligation, reserve: &Pubkey) -> ProgramResult {
+    if !has_borrow_slot(obligation, reserve) && remaining_reserve_slots(obligation) == 0 {
+        return Err(error!(SolendAnchorError::ObligationReserveLimit).into());
+    }
+    Ok(())
+}
+
+/// `deposit_obligation_collateral`, failing fast when the obligation has no slot
+/// left for a new deposit reserve
+pub fn deposit_obligation_collateral_slot_checked<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, DepositObligationCollateralAccounts<'info>>,
+    collateral_amount: u64,
+) -> ProgramResult {
+    let obligation = Obligation::unpack(&ctx.accounts.obligation_pubkey.try_borrow_data()?)?;
+    check_deposit_slot(&obligation, &ctx.accounts.deposit_reserve_pubkey.key())?;
+    deposit_obligation_collateral(ctx, collateral_amount)
+}
+
+/// `borrow_obligation_liquidity`, failing fast when the obligation has no slot left
+/// for a new borrow reserve
+pub fn borrow_obligation_liquidity_slot_checked<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, BorrowObligationLiquidityAccounts<'info>>,
+    liquidity_amount: u64,
+) -> ProgramResult {
+    let obligation = Obligation::unpack(&ctx.accounts.obligation_pubkey.try_borrow_data()?)?;
+    check_borrow_slot(&obligation, &ctx.accounts.borrow_reserve_pubkey.key())?;
+    borrow_obligation_liquidity(ctx, liquidity_amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use solend_token_lending::state::{ObligationCollateral, ObligationLiquidity};
+
+    use super::*;
+
+    /// Obligation depositing into `deposits` reserves and borrowing from `borrows` reserves
+    fn obligation(deposits: usize, borrows: usize) -> (Vec<Pubkey>, Vec<Pubkey>, Obligation) {
+        let deposit_reserves: Vec<Pubkey> = (0..deposits).map(|_| Pubkey::new_unique()).collect();
+        let borrow_reserves: Vec<Pubkey> = (0..borrows).map(|_| Pubkey::new_unique()).collect();
+        let obligation = Obligation {
+            deposits: deposit_reserves
+                .iter()
+                .map(|reserve| ObligationCollateral::new(*reserve))
+                .collect(),
+            borrows: borrow_reserves
+                .iter()
+                .map(|reserve| ObligationLiquidity::new(*reserve))
+                .collect(),
+            ..Obligation::default()
+        };
+        (deposit_reserves, borrow_reserves, obligation)
+    }
+
+    #[test]
+    fn slots_are_found_by_side() {
+        let (deposit_reserves, borrow_reserves, obligation) = obligation(2, 1);
+        assert!(has_deposit_slot(&obligation, &deposit_reserves[1]));
+        assert!(!has_deposit_slot(&obligation, &borrow_reserves[0]));
+        assert!(has_borrow_slot(&obligation, &borrow_reserves[0]));
+        assert!(!has_borrow_slot(&obligation, &deposit_reserves[0]));
+        assert!(!has_deposit_slot(&obligation, &Pubkey::new_unique()));
+    }
+
+    #[test]
+    fn deposits_and_borrows_share_the_reserve_slots() {
+        assert_eq!(remaining_reserve_slots(&obligation(0, 0).2), MAX_OBLIGATION_RESERVES);
+        assert_eq!(remaining_reserve_slots(&obligation(2, 1).2), MAX_OBLIGATION_RESERVES - 3);
+    }
+
+    #[test]
+    fn new_reserves_fit_while_slots_remain() {
+        let (_, _, obligation) = obligation(2, 1);
+        assert_eq!(check_deposit_slot(&obligation, &Pubkey::new_unique()), Ok(()));
+        assert_eq!(check_borrow_slot(&obligation, &Pubkey::new_unique()), Ok(()));
+    }
+
+    #[test]
+    fn full_obligation_only_reuses_its_positions() {
+        let (deposit_reserves, borrow_reserves, obligation) =
+            obligation(MAX_OBLIGATION_RESERVES - 1, 1);
+        assert_eq!(remaining_reserve_slots(&obligation), 0);
+        assert_eq!(check_deposit_slot(&obligation, &deposit_reserves[0]), Ok(()));
+        assert_eq!(check_borrow_slot(&obligation, &borrow_reserves[0]), Ok(()));
+
+        let limit: ProgramResult = Err(error!(SolendAnchorError::ObligationReserveLimit).into());
+        assert_eq!(check_deposit_slot(&obligation, &Pubkey::new_unique()), limit);
+        assert_eq!(check_borrow_slot(&obligation, &Pubkey::new_unique()), limit);
+        // A deposit reserve is a new borrow position, and the other way around
+        assert_eq!(check_borrow_slot(&obligation, &deposit_reserves[0]), limit);
+        assert_eq!(check_deposit_slot(&obligation, &borrow_reserves[0]), limit);
+    }
+}
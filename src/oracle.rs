@@ -0,0 +1,662 @@
+use anchor_lang::{
+    error,
+    prelude::{msg, AccountInfo, Clock, CpiContext, Pubkey},
+    solana_program::{entrypoint::ProgramResult, program_error::ProgramError, program_pack::Pack},
+    Key,
+};
+use solend_token_lending::{
+    math::{Decimal, TryDiv, TryMul},
+    pyth,
+    state::{LendingMarket, Reserve},
+    NULL_PUBKEY,
+};
+use switchboard_program::{FastRoundResultAccountData, SwitchboardAccountType};
+
+use crate::{
+    deposit_reserve_liquidity_and_obligation_collateral, error::SolendAnchorError,
+    refresh_reserve, DepositReserveLiquidityAndObligationCollateralAccounts,
+    RefreshReserveAccounts,
+};
+
+/// Staleness and confidence limits applied when reading a price
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OracleLimits {
+    pub max_staleness_slots: u64,
+    /// Widest accepted Pyth confidence interval, as a percentage of the price
+    pub max_confidence_pct: u8,
+}
+
+impl Default for OracleLimits {
+    /// The limits Solend applies in `refresh_reserve`
+    fn default() -> Self {
+        Self {
+            max_staleness_slots: 240,
+            max_confidence_pct: 10,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OracleSource {
+    Pyth,
+    Switchboard,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OraclePrice {
+    /// Price of one whole token in the market's quote currency
+    pub price: Decimal,
+    pub source: OracleSource,
+    /// Slot the price was published in
+    pub slot: u64,
+}
+
+/// Solend uses this address in place of an oracle a reserve does not have
+pub fn is_null_oracle(key: &Pubkey) -> bool {
+    *key == NULL_PUBKEY
+}
+
+fn check_staleness(slot: u64, clock: &Clock, limits: &OracleLimits) -> Result<(), ProgramError> {
+    if clock.slot.saturating_sub(slot) > limits.max_staleness_slots {
+        return Err(error!(SolendAnchorError::StaleOracle).into());
+    }
+    Ok(())
+}
+
+impl OraclePrice {
+    pub fn pyth(
+        pyth_price_info: &AccountInfo,
+        clock: &Clock,
+        limits: &OracleLimits,
+    ) -> Result<Self, ProgramError> {
+        if is_null_oracle(&pyth_price_info.key()) {
+            return Err(error!(SolendAnchorError::NullOracle).into());
+        }
+
+        let data = pyth_price_info.try_borrow_data()?;
+        let pyth_price = pyth::load::<pyth::Price>(&data)
+            .map_err(|_| ProgramError::from(error!(SolendAnchorError::InvalidOracle)))?;
+        if pyth_price.magic != pyth::MAGIC || pyth_price.ptype != pyth::PriceType::Price {
+            return Err(error!(SolendAnchorError::InvalidOracle).into());
+        }
+        check_staleness(pyth_price.valid_slot, clock, limits)?;
+
+        let price: u64 = pyth_price
+            .agg
+            .price
+            .try_into()
+            .map_err(|_| ProgramError::from(error!(SolendAnchorError::InvalidOracle)))?;
+        if price == 0 {
+            return Err(error!(SolendAnchorError::InvalidOracle).into());
+        }
+        let max_confidence = (price as u128) * (limits.max_confidence_pct as u128) / 100;
+        if pyth_price.agg.conf as u128 > max_confidence {
+            return Err(error!(SolendAnchorError::OracleConfidenceTooWide).into());
+        }
+
+        let exponent = 10u64
+            .checked_pow(pyth_price.expo.unsigned_abs())
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let price = if pyth_price.expo >= 0 {
+            Decimal::from(price).try_mul(exponent)?
+        } else {
+            Decimal::from(price).try_div(exponent)?
+        };
+
+        Ok(Self {
+            price,
+            source: OracleSource::Pyth,
+            slot: pyth_price.valid_slot,
+        })
+    }
+
+    pub fn switchboard(
+        switchboard_feed_info: &AccountInfo,
+        clock: &Clock,
+        limits: &OracleLimits,
+    ) -> Result<Self, ProgramError> {
+        if is_null_oracle(&switchboard_feed_info.key()) {
+            return Err(error!(SolendAnchorError::NullOracle).into());
+        }
+
+        let data = switchboard_feed_info.try_borrow_data()?;
+        if data.first()
+            != Some(&(SwitchboardAccountType::TYPE_AGGREGATOR_RESULT_PARSE_OPTIMIZED as u8))
+        {
+            return Err(error!(SolendAnchorError::InvalidOracle).into());
+        }
+        let feed = FastRoundResultAccountData::deserialize(&data)
+            .map_err(|_| ProgramError::from(error!(SolendAnchorError::InvalidOracle)))?;
+        check_staleness(feed.result.round_open_slot, clock, limits)?;
+
+        // Same fixed point conversion Solend applies to the feed's f64 result
+        let price_quotient = 10u64.pow(9);
+        let price = ((price_quotient as f64) * feed.result.result) as u64;
+        if price == 0 {
+            return Err(error!(SolendAnchorError::InvalidOracle).into());
+        }
+
+        Ok(Self {
+            price: Decimal::from(price).try_div(price_quotient)?,
+            source: OracleSource::Switchboard,
+            slot: feed.result.round_open_slot,
+        })
+    }
+
+    /// Reads the price the way Solend's `refresh_reserve` does: Pyth first, falling
+    /// back to Switchboard when Pyth is the null oracle or fails the limits.
+    pub fn read(
+        pyth_price_info: &AccountInfo,
+        switchboard_feed_info: &AccountInfo,
+        clock: &Clock,
+        limits: &OracleLimits,
+    ) -> Result<Self, ProgramError> {
+        match Self::pyth(pyth_price_info, clock, limits) {
+            Ok(price) => Ok(price),
+            Err(pyth_err) => {
+                if is_null_oracle(&switchboard_feed_info.key()) {
+                    return Err(pyth_err);
+                }
+                Self::switchboard(switchboard_feed_info, clock, limits)
+            }
+        }
+    }
+}
+
+/// Checks the oracles passed alongside a reserve against the ones stored in its
+/// liquidity state, and their owners against the market's oracle programs. A null
+/// oracle only has to match, since Solend never reads it.
+pub fn check_reserve_oracles(
+    reserve_pubkey: &Pubkey,
+    reserve: &Reserve,
+    lending_market_pubkey: &Pubkey,
+    lending_market: &LendingMarket,
+    pyth_oracle_info: &AccountInfo,
+    switchboard_oracle_info: &AccountInfo,
+) -> ProgramResult {
+    if reserve.lending_market != *lending_market_pubkey {
+        return Err(error!(SolendAnchorError::LendingMarketMismatch).into());
+    }
+
+    if pyth_oracle_info.key() != reserve.liquidity.pyth_oracle_pubkey {
+        msg!(
+            "Reserve {} expects Pyth oracle {}",
+            reserve_pubkey,
+            reserve.liquidity.pyth_oracle_pubkey
+        );
+        return Err(error!(SolendAnchorError::PythOracleMismatch).into());
+    }
+    if !is_null_oracle(pyth_oracle_info.key)
+        && *pyth_oracle_info.owner != lending_market.oracle_program_id
+    {
+        msg!(
+            "Pyth oracle {} must be owned by {}",
+            pyth_oracle_info.key,
+            lending_market.oracle_program_id
+        );
+        return Err(error!(SolendAnchorError::PythOracleOwnerMismatch).into());
+    }
+
+    if switchboard_oracle_info.key() != reserve.liquidity.switchboard_oracle_pubkey {
+        msg!(
+            "Reserve {} expects Switchboard oracle {}",
+            reserve_pubkey,
+            reserve.liquidity.switchboard_oracle_pubkey
+        );
+        return Err(error!(SolendAnchorError::SwitchboardOracleMismatch).into());
+    }
+    if !is_null_oracle(switchboard_oracle_info.key)
+        && *switchboard_oracle_info.owner != lending_market.switchboard_oracle_program_id
+    {
+        msg!(
+            "Switchboard oracle {} must be owned by {}",
+            switchboard_oracle_info.key,
+            lending_market.switchboard_oracle_program_id
+        );
+        return Err(error!(SolendAnchorError::SwitchboardOracleOwnerMismatch).into());
+    }
+
+    Ok(())
+}
+
+/// Unpacking variant of `check_reserve_oracles` for callers holding account infos
+pub fn validate_reserve_oracles(
+    reserve_info: &AccountInfo,
+    lending_market_info: &AccountInfo,
+    pyth_oracle_info: &AccountInfo,
+    switchboard_oracle_info: &AccountInfo,
+) -> ProgramResult {
+    let reserve = Reserve::unpack(&reserve_info.try_borrow_data()?)?;
+    let lending_market = LendingMarket::unpack(&lending_market_info.try_borrow_data()?)?;
+    check_reserve_oracles(
+        reserve_info.key,
+        &reserve,
+        lending_market_info.key,
+        &lending_market,
+        pyth_oracle_info,
+        switchboard_oracle_info,
+    )
+}
+
+/// `refresh_reserve` does not take the lending market, so it is passed here to
+/// check the oracle owners before the CPI
+pub fn refresh_reserve_checked<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, RefreshReserveAccounts<'info>>,
+    lending_market_info: &AccountInfo<'info>,
+) -> ProgramResult {
+    validate_reserve_oracles(
+        &ctx.accounts.reserve_pubkey,
+        lending_market_info,
+        &ctx.accounts.reserve_liquidity_pyth_oracle_pubkey,
+        &ctx.accounts.reserve_liquidity_switchboard_oracle_pubkey,
+    )?;
+    refresh_reserve(ctx)
+}
+
+/// Deposits into the obligation after checking the oracles passed for the reserve
+pub fn deposit_reserve_liquidity_and_obligation_collateral_checked<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<
+        'a,
+        'b,
+        'c,
+        'info,
+        DepositReserveLiquidityAndObligationCollateralAccounts<'info>,
+    >,
+    liquidity_amount: u64,
+) -> ProgramResult {
+    validate_reserve_oracles(
+        &ctx.accounts.reserve_pubkey,
+        &ctx.accounts.lending_market_pubkey,
+        &ctx.accounts.reserve_liquidity_pyth_oracle_pubkey,
+        &ctx.accounts.reserve_liquidity_switchboard_oracle_pubkey,
+    )?;
+    deposit_reserve_liquidity_and_obligation_collateral(ctx, liquidity_amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::size_of;
+
+    use bytemuck::Zeroable;
+
+    use super::*;
+
+    const SLOT: u64 = 1_000;
+
+    /// Owns the fields an `AccountInfo` borrows
+    struct TestAccount {
+        key: Pubkey,
+        owner: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+    }
+
+    impl TestAccount {
+        fn new(key: Pubkey, owner: Pubkey, data: Vec<u8>) -> Self {
+            Self {
+                key,
+                owner,
+                lamports: 0,
+                data,
+            }
+        }
+
+        fn info(&mut self) -> AccountInfo {
+            AccountInfo::new(
+                &self.key,
+                false,
+                false,
+                &mut self.lamports,
+                &mut self.data,
+                &self.owner,
+                false,
+                0,
+            )
+        }
+    }
+
+    /// `price * 10^expo` with confidence `conf`, published in `slot`
+    fn pyth_price(price: i64, expo: i32, conf: u64, slot: u64) -> Vec<u8> {
+        let mut pyth_price: pyth::Price = Zeroable::zeroed();
+        pyth_price.magic = pyth::MAGIC;
+        pyth_price.ver = pyth::VERSION_2;
+        pyth_price.atype = pyth::AccountType::Price as u32;
+        pyth_price.ptype = pyth::PriceType::Price;
+        pyth_price.expo = expo;
+        pyth_price.valid_slot = slot;
+        pyth_price.agg.price = price;
+        pyth_price.agg.conf = conf;
+        pyth_price.agg.status = pyth::PriceStatus::Trading;
+        bytemuck::bytes_of(&pyth_price).to_vec()
+    }
+
+    fn switchboard_feed(result: f64, slot: u64) -> Vec<u8> {
+        // SAFETY: the feed is plain old data, for which all zeroes is a valid value
+        let mut feed: FastRoundResultAccountData = unsafe { std::mem::zeroed() };
+        feed.result.result = result;
+        feed.result.round_open_slot = slot;
+        // `deserialize` copies the bytes after the account type tag into the struct
+        let mut data = vec![SwitchboardAccountType::TYPE_AGGREGATOR_RESULT_PARSE_OPTIMIZED as u8];
+        data.extend_from_slice(unsafe {
+            std::slice::from_raw_parts(
+                &feed as *const FastRoundResultAccountData as *const u8,
+                size_of::<FastRoundResultAccountData>(),
+            )
+        });
+        data
+    }
+
+    fn oracle_account(data: Vec<u8>) -> TestAccount {
+        TestAccount::new(Pubkey::new_unique(), Pubkey::new_unique(), data)
+    }
+
+    fn null_account() -> TestAccount {
+        TestAccount::new(NULL_PUBKEY, Pubkey::default(), vec![])
+    }
+
+    fn clock(slot: u64) -> Clock {
+        Clock {
+            slot,
+            ..Clock::default()
+        }
+    }
+
+    fn read_pyth(data: Vec<u8>, slot: u64) -> Result<OraclePrice, ProgramError> {
+        let limits = OracleLimits::default();
+        OraclePrice::pyth(&oracle_account(data).info(), &clock(slot), &limits)
+    }
+
+    fn read_switchboard(data: Vec<u8>, slot: u64) -> Result<OraclePrice, ProgramError> {
+        let limits = OracleLimits::default();
+        OraclePrice::switchboard(&oracle_account(data).info(), &clock(slot), &limits)
+    }
+
+    #[test]
+    fn pyth_price_is_scaled_down_by_a_negative_exponent() {
+        assert_eq!(
+            read_pyth(pyth_price(1_234_500_000, -8, 0, SLOT), SLOT),
+            Ok(OraclePrice {
+                price: Decimal::from(12_345u64).try_div(1_000u64).unwrap(),
+                source: OracleSource::Pyth,
+                slot: SLOT,
+            })
+        );
+    }
+
+    #[test]
+    fn pyth_price_is_scaled_up_by_a_positive_exponent() {
+        let price = read_pyth(pyth_price(12, 2, 0, SLOT), SLOT).unwrap();
+        assert_eq!(price.price, Decimal::from(1_200u64));
+    }
+
+    #[test]
+    fn pyth_price_is_stale_past_the_staleness_limit() {
+        let max_staleness = OracleLimits::default().max_staleness_slots;
+        assert!(read_pyth(pyth_price(100, 0, 0, SLOT), SLOT + max_staleness).is_ok());
+        assert_eq!(
+            read_pyth(pyth_price(100, 0, 0, SLOT), SLOT + max_staleness + 1),
+            Err(error!(SolendAnchorError::StaleOracle).into())
+        );
+    }
+
+    #[test]
+    fn pyth_confidence_may_reach_the_limit() {
+        // 10% of the price
+        assert!(read_pyth(pyth_price(100, 0, 10, SLOT), SLOT).is_ok());
+        assert_eq!(
+            read_pyth(pyth_price(100, 0, 11, SLOT), SLOT),
+            Err(error!(SolendAnchorError::OracleConfidenceTooWide).into())
+        );
+    }
+
+    #[test]
+    fn pyth_price_must_be_positive() {
+        for price in [0, -100] {
+            assert_eq!(
+                read_pyth(pyth_price(price, 0, 0, SLOT), SLOT),
+                Err(error!(SolendAnchorError::InvalidOracle).into())
+            );
+        }
+    }
+
+    #[test]
+    fn pyth_account_must_hold_a_price() {
+        let mut data = pyth_price(100, 0, 0, SLOT);
+        data[0] ^= 0xff;
+        assert_eq!(
+            read_pyth(data, SLOT),
+            Err(error!(SolendAnchorError::InvalidOracle).into())
+        );
+    }
+
+    #[test]
+    fn null_oracle_has_no_price() {
+        let limits = OracleLimits::default();
+        assert_eq!(
+            OraclePrice::pyth(&null_account().info(), &clock(SLOT), &limits),
+            Err(error!(SolendAnchorError::NullOracle).into())
+        );
+        assert_eq!(
+            OraclePrice::switchboard(&null_account().info(), &clock(SLOT), &limits),
+            Err(error!(SolendAnchorError::NullOracle).into())
+        );
+    }
+
+    #[test]
+    fn switchboard_result_is_converted_to_a_decimal() {
+        assert_eq!(
+            read_switchboard(switchboard_feed(1.5, SLOT), SLOT),
+            Ok(OraclePrice {
+                price: Decimal::from(3u64).try_div(2u64).unwrap(),
+                source: OracleSource::Switchboard,
+                slot: SLOT,
+            })
+        );
+    }
+
+    #[test]
+    fn switchboard_feed_is_stale_past_the_staleness_limit() {
+        let max_staleness = OracleLimits::default().max_staleness_slots;
+        assert_eq!(
+            read_switchboard(switchboard_feed(1.5, SLOT), SLOT + max_staleness + 1),
+            Err(error!(SolendAnchorError::StaleOracle).into())
+        );
+    }
+
+    #[test]
+    fn switchboard_result_must_be_positive() {
+        assert_eq!(
+            read_switchboard(switchboard_feed(0.0, SLOT), SLOT),
+            Err(error!(SolendAnchorError::InvalidOracle).into())
+        );
+    }
+
+    #[test]
+    fn switchboard_account_must_be_a_result_feed() {
+        let mut data = switchboard_feed(1.5, SLOT);
+        data[0] = SwitchboardAccountType::TYPE_AGGREGATOR as u8;
+        assert_eq!(
+            read_switchboard(data, SLOT),
+            Err(error!(SolendAnchorError::InvalidOracle).into())
+        );
+    }
+
+    fn read(
+        pyth: &mut TestAccount,
+        switchboard: &mut TestAccount,
+    ) -> Result<OraclePrice, ProgramError> {
+        OraclePrice::read(
+            &pyth.info(),
+            &switchboard.info(),
+            &clock(SLOT + 1),
+            &OracleLimits::default(),
+        )
+    }
+
+    #[test]
+    fn read_prefers_pyth() {
+        let mut pyth = oracle_account(pyth_price(2, 0, 0, SLOT));
+        let mut switchboard = oracle_account(switchboard_feed(1.5, SLOT));
+        let price = read(&mut pyth, &mut switchboard).unwrap();
+        assert_eq!(price.source, OracleSource::Pyth);
+        assert_eq!(price.price, Decimal::from(2u64));
+    }
+
+    #[test]
+    fn read_falls_back_to_switchboard_when_pyth_fails() {
+        let mut switchboard = oracle_account(switchboard_feed(1.5, SLOT));
+        for mut pyth in [
+            null_account(),
+            oracle_account(pyth_price(2, 0, 0, 0)),
+            oracle_account(pyth_price(2, 0, 1, SLOT)),
+        ] {
+            let price = read(&mut pyth, &mut switchboard).unwrap();
+            assert_eq!(price.source, OracleSource::Switchboard);
+        }
+    }
+
+    #[test]
+    fn read_reports_the_pyth_error_without_switchboard() {
+        let mut pyth = oracle_account(pyth_price(2, 0, 0, 0));
+        assert_eq!(
+            read(&mut pyth, &mut null_account()),
+            Err(error!(SolendAnchorError::StaleOracle).into())
+        );
+    }
+
+    #[test]
+    fn read_reports_the_switchboard_error_when_both_fail() {
+        let mut pyth = oracle_account(pyth_price(2, 0, 0, 0));
+        let mut switchboard = oracle_account(switchboard_feed(0.0, SLOT));
+        assert_eq!(
+            read(&mut pyth, &mut switchboard),
+            Err(error!(SolendAnchorError::InvalidOracle).into())
+        );
+    }
+
+    struct Oracles {
+        reserve_pubkey: Pubkey,
+        reserve: Reserve,
+        lending_market_pubkey: Pubkey,
+        lending_market: LendingMarket,
+        pyth: TestAccount,
+        switchboard: TestAccount,
+    }
+
+    impl Oracles {
+        /// A reserve whose oracles are both set and owned by the market's programs
+        fn new() -> Self {
+            let lending_market_pubkey = Pubkey::new_unique();
+            let lending_market = LendingMarket {
+                oracle_program_id: Pubkey::new_unique(),
+                switchboard_oracle_program_id: Pubkey::new_unique(),
+                ..LendingMarket::default()
+            };
+            let pyth = TestAccount::new(
+                Pubkey::new_unique(),
+                lending_market.oracle_program_id,
+                vec![],
+            );
+            let switchboard = TestAccount::new(
+                Pubkey::new_unique(),
+                lending_market.switchboard_oracle_program_id,
+                vec![],
+            );
+            let mut reserve = Reserve::default();
+            reserve.lending_market = lending_market_pubkey;
+            reserve.liquidity.pyth_oracle_pubkey = pyth.key;
+            reserve.liquidity.switchboard_oracle_pubkey = switchboard.key;
+            Self {
+                reserve_pubkey: Pubkey::new_unique(),
+                reserve,
+                lending_market_pubkey,
+                lending_market,
+                pyth,
+                switchboard,
+            }
+        }
+
+        fn check(&mut self) -> ProgramResult {
+            check_reserve_oracles(
+                &self.reserve_pubkey,
+                &self.reserve,
+                &self.lending_market_pubkey,
+                &self.lending_market,
+                &self.pyth.info(),
+                &self.switchboard.info(),
+            )
+        }
+    }
+
+    #[test]
+    fn reserve_oracles_pass_when_they_match() {
+        assert_eq!(Oracles::new().check(), Ok(()));
+    }
+
+    #[test]
+    fn reserve_must_belong_to_the_market() {
+        let mut oracles = Oracles::new();
+        oracles.lending_market_pubkey = Pubkey::new_unique();
+        assert_eq!(
+            oracles.check(),
+            Err(error!(SolendAnchorError::LendingMarketMismatch).into())
+        );
+    }
+
+    #[test]
+    fn pyth_oracle_must_be_the_reserve_oracle() {
+        let mut oracles = Oracles::new();
+        oracles.pyth.key = Pubkey::new_unique();
+        assert_eq!(
+            oracles.check(),
+            Err(error!(SolendAnchorError::PythOracleMismatch).into())
+        );
+    }
+
+    #[test]
+    fn pyth_oracle_must_be_owned_by_the_market_oracle_program() {
+        let mut oracles = Oracles::new();
+        oracles.pyth.owner = Pubkey::new_unique();
+        assert_eq!(
+            oracles.check(),
+            Err(error!(SolendAnchorError::PythOracleOwnerMismatch).into())
+        );
+    }
+
+    #[test]
+    fn switchboard_oracle_must_be_the_reserve_oracle() {
+        let mut oracles = Oracles::new();
+        oracles.switchboard.key = Pubkey::new_unique();
+        assert_eq!(
+            oracles.check(),
+            Err(error!(SolendAnchorError::SwitchboardOracleMismatch).into())
+        );
+    }
+
+    #[test]
+    fn switchboard_oracle_must_be_owned_by_the_market_switchboard_program() {
+        let mut oracles = Oracles::new();
+        oracles.switchboard.owner = Pubkey::new_unique();
+        assert_eq!(
+            oracles.check(),
+            Err(error!(SolendAnchorError::SwitchboardOracleOwnerMismatch).into())
+        );
+    }
+
+    #[test]
+    fn null_oracles_only_have_to_match() {
+        let mut oracles = Oracles::new();
+        oracles.reserve.liquidity.pyth_oracle_pubkey = NULL_PUBKEY;
+        oracles.reserve.liquidity.switchboard_oracle_pubkey = NULL_PUBKEY;
+        oracles.pyth = null_account();
+        oracles.switchboard = null_account();
+        assert_eq!(oracles.check(), Ok(()));
+
+        // A null oracle in the reserve still rejects a real account in its place
+        oracles.switchboard = Oracles::new().switchboard;
+        assert_eq!(
+            oracles.check(),
+            Err(error!(SolendAnchorError::SwitchboardOracleMismatch).into())
+        );
+    }
+}
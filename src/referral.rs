@@ -0,0 +1,505 @@
+//! Host fee accounts for front-end partners. Each partner gets one token account per
+//! liquidity mint at a PDA of the calling program, under a per-partner PDA authority,
+//! so Solend can pay the host share of borrow and flash loan fees into it and the
+//! calling program can later settle it to the partner.
+
+use anchor_lang::{
+    error,
+    prelude::{AccountInfo, CpiContext, Program, Pubkey, Rent, System, Sysvar},
+    solana_program::{
+        self, entrypoint::ProgramResult, program_error::ProgramError, program_pack::Pack,
+        system_instruction,
+    },
+    Accounts, Key, ToAccountInfo, ToAccountInfos, ToAccountMetas,
+};
+use solend_token_lending::state::Reserve;
+
+use crate::{
+    error::SolendAnchorError,
+    flash_loan,
+    host_fee::{
+        borrow_obligation_liquidity_with_host_fee, check_host_fee_receiver, flash_loan_fee_split,
+        BorrowWithHostFeeAccounts, FeeSplit,
+    },
+    token::{token_balance, transfer},
+    FlashLoanAccounts, TokenProgram,
+};
+
+pub const HOST_FEE_AUTHORITY_SEED: &[u8] = b"host-fee-authority";
+pub const HOST_FEE_ACCOUNT_SEED: &[u8] = b"host-fee-account";
+
+/// PDA of `program_id` owning all of `partner`'s host fee accounts
+pub fn host_fee_authority_address(program_id: &Pubkey, partner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[HOST_FEE_AUTHORITY_SEED, partner.as_ref()], program_id)
+}
+
+/// PDA of `program_id` holding `partner`'s host fees in `mint`
+pub fn host_fee_account_address(
+    program_id: &Pubkey,
+    partner: &Pubkey,
+    mint: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[HOST_FEE_ACCOUNT_SEED, partner.as_ref(), mint.as_ref()],
+        program_id,
+    )
+}
+
+/// Fails unless `host_fee_account` is `partner`'s host fee account of `program_id` for
+/// the reserve's liquidity mint
+pub fn check_partner_host_fee_account(
+    program_id: &Pubkey,
+    partner: &Pubkey,
+    reserve: &Reserve,
+    host_fee_account: &AccountInfo,
+) -> ProgramResult {
+    check_host_fee_receiver(reserve, host_fee_account)?;
+    let (expected, _) =
+        host_fee_account_address(program_id, partner, &reserve.liquidity.mint_pubkey);
+    if host_fee_account.key() != expected {
+        return Err(error!(SolendAnchorError::InvalidHostFeeAccount).into());
+    }
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CreateHostFeeAccountAccounts<'info> {
+    pub payer: AccountInfo<'info>,
+    /// `host_fee_account_address(program_id, partner, mint)`
+    pub host_fee_account: AccountInfo<'info>,
+    /// `host_fee_authority_address(program_id, partner)`
+    pub host_fee_authority: AccountInfo<'info>,
+    pub mint: AccountInfo<'info>,
+    pub rent_sysvar: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+/// Allocates and initializes `partner`'s host fee account for `mint`. `program_id` is
+/// the calling program, which signs for the account's address.
+pub fn create_host_fee_account<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, CreateHostFeeAccountAccounts<'info>>,
+    program_id: &Pubkey,
+    partner: &Pubkey,
+) -> ProgramResult {
+    let mint = ctx.accounts.mint.key();
+    let (host_fee_account, bump) = host_fee_account_address(program_id, partner, &mint);
+    let (host_fee_authority, _) = host_fee_authority_address(program_id, partner);
+    if ctx.accounts.host_fee_account.key() != host_fee_account
+        || ctx.accounts.host_fee_authority.key() != host_fee_authority
+    {
+        return Err(error!(SolendAnchorError::InvalidHostFeeAccount).into());
+    }
+
+    let space = spl_token::state::Account::LEN;
+    let ix = system_instruction::create_account(
+        &ctx.accounts.payer.key(),
+        &host_fee_account,
+        ctx.accounts.rent_sysvar.minimum_balance(space),
+        space as u64,
+        &spl_token::id(),
+    );
+    let bump = [bump];
+    let account_seeds: &[&[u8]] = &[HOST_FEE_ACCOUNT_SEED, partner.as_ref(), mint.as_ref(), &bump];
+    // The payer may itself be a PDA signing through `ctx`
+    let mut signer_seeds: Vec<&[&[u8]]> = ctx.signer_seeds.to_vec();
+    signer_seeds.push(account_seeds);
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            ctx.accounts.payer.clone(),
+            ctx.accounts.host_fee_account.clone(),
+            ctx.accounts.system_program.to_account_info(),
+        ],
+        &signer_seeds,
+    )?;
+
+    let ix = spl_token::instruction::initialize_account(
+        &spl_token::id(),
+        &host_fee_account,
+        &mint,
+        &host_fee_authority,
+    )?;
+    solana_program::program::invoke(
+        &ix,
+        &[
+            ctx.accounts.host_fee_account.clone(),
+            ctx.accounts.mint.clone(),
+            ctx.accounts.host_fee_authority.clone(),
+            ctx.accounts.rent_sysvar.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+        ],
+    )
+}
+
+/// Borrows with the host fee paid to `partner`'s host fee account
+pub fn borrow_with_referral<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, BorrowWithHostFeeAccounts<'info>>,
+    program_id: &Pubkey,
+    partner: &Pubkey,
+    liquidity_amount: u64,
+) -> Result<FeeSplit, ProgramError> {
+    let reserve = Reserve::unpack(&ctx.accounts.borrow_reserve_pubkey.try_borrow_data()?)?;
+    check_partner_host_fee_account(
+        program_id,
+        partner,
+        &reserve,
+        &ctx.accounts.host_fee_receiver_pubkey,
+    )?;
+    borrow_obligation_liquidity_with_host_fee(ctx, liquidity_amount)
+}
+
+/// Flash loans with the host fee paid to `partner`'s host fee account
+pub fn flash_loan_with_referral<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, FlashLoanAccounts<'info>>,
+    program_id: &Pubkey,
+    partner: &Pubkey,
+    amount: u64,
+) -> Result<FeeSplit, ProgramError> {
+    let reserve = Reserve::unpack(&ctx.accounts.reserve_pubkey.try_borrow_data()?)?;
+    check_partner_host_fee_account(
+        program_id,
+        partner,
+        &reserve,
+        &ctx.accounts.host_fee_receiver_pubkey,
+    )?;
+    let fee_split = flash_loan_fee_split(&reserve, amount)?;
+    flash_loan(ctx, amount)?;
+    Ok(fee_split)
+}
+
+#[derive(Accounts)]
+pub struct SettleHostFeesAccounts<'info> {
+    pub host_fee_account: AccountInfo<'info>,
+    pub host_fee_authority: AccountInfo<'info>,
+    /// Token account of the partner in the host fee account's mint
+    pub partner_destination: AccountInfo<'info>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+/// Transfers everything accumulated in `partner`'s host fee account to a token account
+/// the partner owns, returning the amount settled
+pub fn settle_host_fees<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, SettleHostFeesAccounts<'info>>,
+    program_id: &Pubkey,
+    partner: &Pubkey,
+) -> Result<u64, ProgramError> {
+    let host_fee_account =
+        spl_token::state::Account::unpack(&ctx.accounts.host_fee_account.try_borrow_data()?)?;
+    let (expected_account, _) =
+        host_fee_account_address(program_id, partner, &host_fee_account.mint);
+    let (host_fee_authority, bump) = host_fee_authority_address(program_id, partner);
+    if ctx.accounts.host_fee_account.key() != expected_account
+        || ctx.accounts.host_fee_authority.key() != host_fee_authority
+    {
+        return Err(error!(SolendAnchorError::InvalidHostFeeAccount).into());
+    }
+
+    let destination =
+        spl_token::state::Account::unpack(&ctx.accounts.partner_destination.try_borrow_data()?)?;
+    if destination.owner != *partner || destination.mint != host_fee_account.mint {
+        return Err(error!(SolendAnchorError::InvalidSettlementDestination).into());
+    }
+
+    let amount = token_balance(&ctx.accounts.host_fee_account)?;
+    if amount == 0 {
+        return Ok(0);
+    }
+    let bump = [bump];
+    transfer(
+        &ctx.accounts.token_program.to_account_info(),
+        &ctx.accounts.host_fee_account,
+        &ctx.accounts.partner_destination,
+        &ctx.accounts.host_fee_authority,
+        amount,
+        &[&[HOST_FEE_AUTHORITY_SEED, partner.as_ref(), &bump]],
+    )?;
+    Ok(amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::solana_program::{bpf_loader, program_option::COption};
+    use spl_token::state::{Account, AccountState};
+
+    use super::*;
+
+    /// Owns the fields an `AccountInfo` borrows
+    struct TestAccount {
+        key: Pubkey,
+        owner: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+        executable: bool,
+    }
+
+    impl TestAccount {
+        fn new(key: Pubkey, owner: Pubkey) -> Self {
+            Self {
+                key,
+                owner,
+                lamports: 0,
+                data: vec![],
+                executable: false,
+            }
+        }
+
+        fn token_account(key: Pubkey, mint: Pubkey, owner: Pubkey, amount: u64) -> Self {
+            let mut data = vec![0; Account::LEN];
+            Account::pack(
+                Account {
+                    mint,
+                    owner,
+                    amount,
+                    delegate: COption::None,
+                    state: AccountState::Initialized,
+                    ..Account::default()
+                },
+                &mut data,
+            )
+            .unwrap();
+            Self {
+                data,
+                ..Self::new(key, spl_token::id())
+            }
+        }
+
+        fn info(&mut self) -> AccountInfo {
+            AccountInfo::new(
+                &self.key,
+                false,
+                true,
+                &mut self.lamports,
+                &mut self.data,
+                &self.owner,
+                self.executable,
+                0,
+            )
+        }
+    }
+
+    #[test]
+    fn host_fee_authority_is_a_pda_per_partner() {
+        let (program_id, partner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (authority, bump) = host_fee_authority_address(&program_id, &partner);
+        assert_eq!(
+            Pubkey::create_program_address(
+                &[HOST_FEE_AUTHORITY_SEED, partner.as_ref(), &[bump]],
+                &program_id
+            ),
+            Ok(authority)
+        );
+        assert_ne!(
+            host_fee_authority_address(&program_id, &Pubkey::new_unique()).0,
+            authority
+        );
+        assert_ne!(
+            host_fee_authority_address(&Pubkey::new_unique(), &partner).0,
+            authority
+        );
+    }
+
+    #[test]
+    fn host_fee_account_is_a_pda_per_partner_and_mint() {
+        let (program_id, partner, mint) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (account, bump) = host_fee_account_address(&program_id, &partner, &mint);
+        assert_eq!(
+            Pubkey::create_program_address(
+                &[HOST_FEE_ACCOUNT_SEED, partner.as_ref(), mint.as_ref(), &[bump]],
+                &program_id
+            ),
+            Ok(account)
+        );
+        assert_ne!(
+            host_fee_account_address(&program_id, &partner, &Pubkey::new_unique()).0,
+            account
+        );
+        assert_ne!(
+            host_fee_account_address(&program_id, &Pubkey::new_unique(), &mint).0,
+            account
+        );
+        assert_ne!(host_fee_authority_address(&program_id, &partner).0, account);
+    }
+
+    struct Partner {
+        program_id: Pubkey,
+        partner: Pubkey,
+        mint: Pubkey,
+    }
+
+    impl Partner {
+        fn new() -> Self {
+            Self {
+                program_id: Pubkey::new_unique(),
+                partner: Pubkey::new_unique(),
+                mint: Pubkey::new_unique(),
+            }
+        }
+
+        fn host_fee_account(&self) -> Pubkey {
+            host_fee_account_address(&self.program_id, &self.partner, &self.mint).0
+        }
+
+        fn host_fee_authority(&self) -> Pubkey {
+            host_fee_authority_address(&self.program_id, &self.partner).0
+        }
+
+        fn check(&self, host_fee_account: &mut TestAccount) -> ProgramResult {
+            let mut reserve = Reserve::default();
+            reserve.liquidity.mint_pubkey = self.mint;
+            check_partner_host_fee_account(
+                &self.program_id,
+                &self.partner,
+                &reserve,
+                &host_fee_account.info(),
+            )
+        }
+    }
+
+    #[test]
+    fn partner_host_fee_account_passes() {
+        let partner = Partner::new();
+        let mut account = TestAccount::token_account(
+            partner.host_fee_account(),
+            partner.mint,
+            partner.host_fee_authority(),
+            0,
+        );
+        assert_eq!(partner.check(&mut account), Ok(()));
+    }
+
+    #[test]
+    fn partner_host_fee_account_must_be_in_the_reserve_mint() {
+        let partner = Partner::new();
+        let mut account = TestAccount::token_account(
+            partner.host_fee_account(),
+            Pubkey::new_unique(),
+            partner.host_fee_authority(),
+            0,
+        );
+        assert_eq!(
+            partner.check(&mut account),
+            Err(error!(SolendAnchorError::HostFeeReceiverMintMismatch).into())
+        );
+    }
+
+    #[test]
+    fn partner_host_fee_account_must_be_at_the_partner_pda() {
+        let partner = Partner::new();
+        let mut account = TestAccount::token_account(
+            Pubkey::new_unique(),
+            partner.mint,
+            partner.host_fee_authority(),
+            0,
+        );
+        assert_eq!(
+            partner.check(&mut account),
+            Err(error!(SolendAnchorError::InvalidHostFeeAccount).into())
+        );
+    }
+
+    struct Settlement {
+        partner: Partner,
+        host_fee_account: TestAccount,
+        host_fee_authority: TestAccount,
+        partner_destination: TestAccount,
+        token_program: TestAccount,
+    }
+
+    impl Settlement {
+        /// A valid settlement of `amount` accumulated host fees
+        fn new(amount: u64) -> Self {
+            let partner = Partner::new();
+            let host_fee_account = TestAccount::token_account(
+                partner.host_fee_account(),
+                partner.mint,
+                partner.host_fee_authority(),
+                amount,
+            );
+            let host_fee_authority =
+                TestAccount::new(partner.host_fee_authority(), Pubkey::default());
+            let partner_destination = TestAccount::token_account(
+                Pubkey::new_unique(),
+                partner.mint,
+                partner.partner,
+                0,
+            );
+            let token_program = TestAccount {
+                executable: true,
+                ..TestAccount::new(spl_token::id(), bpf_loader::id())
+            };
+            Self {
+                partner,
+                host_fee_account,
+                host_fee_authority,
+                partner_destination,
+                token_program,
+            }
+        }
+
+        fn settle(&mut self) -> Result<u64, ProgramError> {
+            let token_program = self.token_program.info();
+            let accounts = SettleHostFeesAccounts {
+                host_fee_account: self.host_fee_account.info(),
+                host_fee_authority: self.host_fee_authority.info(),
+                partner_destination: self.partner_destination.info(),
+                token_program: Program::try_from(&token_program).unwrap(),
+            };
+            settle_host_fees(
+                CpiContext::new(token_program.clone(), accounts),
+                &self.partner.program_id,
+                &self.partner.partner,
+            )
+        }
+    }
+
+    #[test]
+    fn settling_an_empty_host_fee_account_transfers_nothing() {
+        assert_eq!(Settlement::new(0).settle(), Ok(0));
+    }
+
+    #[test]
+    fn settlement_rejects_a_host_fee_account_outside_the_partner_pda() {
+        let mut settlement = Settlement::new(100);
+        settlement.host_fee_account.key = Pubkey::new_unique();
+        assert_eq!(
+            settlement.settle(),
+            Err(error!(SolendAnchorError::InvalidHostFeeAccount).into())
+        );
+    }
+
+    #[test]
+    fn settlement_rejects_another_authority() {
+        let mut settlement = Settlement::new(100);
+        settlement.host_fee_authority.key = Pubkey::new_unique();
+        assert_eq!(
+            settlement.settle(),
+            Err(error!(SolendAnchorError::InvalidHostFeeAccount).into())
+        );
+    }
+
+    #[test]
+    fn settlement_rejects_a_destination_the_partner_does_not_own() {
+        let mut settlement = Settlement::new(100);
+        let mint = settlement.partner.mint;
+        settlement.partner_destination =
+            TestAccount::token_account(Pubkey::new_unique(), mint, Pubkey::new_unique(), 0);
+        assert_eq!(
+            settlement.settle(),
+            Err(error!(SolendAnchorError::InvalidSettlementDestination).into())
+        );
+    }
+
+    #[test]
+    fn settlement_rejects_a_destination_in_another_mint() {
+        let mut settlement = Settlement::new(100);
+        let partner = settlement.partner.partner;
+        settlement.partner_destination =
+            TestAccount::token_account(Pubkey::new_unique(), Pubkey::new_unique(), partner, 0);
+        assert_eq!(
+            settlement.settle(),
+            Err(error!(SolendAnchorError::InvalidSettlementDestination).into())
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use anchor_lang::{
+    prelude::{AccountInfo, Clock, CpiContext, Program, Pubkey, Rent, System, Sysvar},
+    solana_program::{self, entrypoint::ProgramResult, program_pack::Pack, system_instruction},
+    Accounts, Key, ToAccountInfo, ToAccountInfos, ToAccountMetas,
+};
+use solend_token_lending::state::Reserve;
+
+use crate::{check_program, init_reserve, InitReserveAccounts, ReserveConfigData, TokenProgram};
+
+#[derive(Accounts)]
+pub struct CreateReserveAccounts<'info> {
+    pub payer: AccountInfo<'info>,
+    pub source_liquidity_pubkey: AccountInfo<'info>,
+    /// Allocated here, initialized by Solend as a collateral account owned by
+    /// `user_transfer_authority_pubkey`
+    pub destination_collateral_pubkey: AccountInfo<'info>,
+    /// Allocated here and owned by Solend
+    pub reserve_pubkey: AccountInfo<'info>,
+    pub reserve_liquidity_mint_pubkey: AccountInfo<'info>,
+    /// Allocated here, initialized by Solend under the lending market authority
+    pub reserve_liquidity_supply_pubkey: AccountInfo<'info>,
+    /// Allocated here, initialized by Solend under the lending market authority
+    pub reserve_collateral_mint_pubkey: AccountInfo<'info>,
+    /// Allocated here, initialized by Solend under the lending market authority
+    pub reserve_collateral_supply_pubkey: AccountInfo<'info>,
+    pub pyth_product_pubkey: AccountInfo<'info>,
+    pub pyth_price_pubkey: AccountInfo<'info>,
+    pub switchboard_feed_pubkey: AccountInfo<'info>,
+    pub lending_market_pubkey: AccountInfo<'info>,
+    pub lending_market_owner_pubkey: AccountInfo<'info>,
+    pub user_transfer_authority_pubkey: AccountInfo<'info>,
+    /// Allocated here, initialized by Solend under the lending market authority
+    pub fee_receiver_pubkey: AccountInfo<'info>,
+    pub lending_market_authority_pubkey: AccountInfo<'info>,
+    pub rent_sysvar: Sysvar<'info, Rent>,
+    pub clock_sysvar: Sysvar<'info, Clock>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, TokenProgram>,
+}
+
+fn allocate<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    space: usize,
+    owner: &Pubkey,
+    rent: &Rent,
+    system_program: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let ix = system_instruction::create_account(
+        &payer.key(),
+        &account.key(),
+        rent.minimum_balance(space),
+        space as u64,
+        owner,
+    );
+    solana_program::program::invoke_signed(
+        &ix,
+        &[payer.clone(), account.clone(), system_program.clone()],
+        signer_seeds,
+    )
+}
+
+/// Allocates the reserve and the token accounts and mint Solend initializes in
+/// `init_reserve`, then calls it. The new accounts are usually PDAs of the calling
+/// program, so `signer_seeds` must hold the seeds of every one of them along with
+/// those of any PDA payer or authority.
+pub fn create_reserve<'a, 'b, 'c, 'info>(
+    ctx: CpiContext<'a, 'b, 'c, 'info, CreateReserveAccounts<'info>>,
+    liquidity_amount: u64,
+    config_data: ReserveConfigData,
+) -> ProgramResult {
+    check_program(&ctx.program)?;
+    let accounts = &ctx.accounts;
+    let system_program = accounts.system_program.to_account_info();
+    let token_program_id = spl_token::id();
+
+    allocate(
+        &accounts.payer,
+        &accounts.reserve_pubkey,
+        Reserve::LEN,
+        &ctx.program.key(),
+        &accounts.rent_sysvar,
+        &system_program,
+        ctx.signer_seeds,
+    )?;
+    allocate(
+        &accounts.payer,
+        &accounts.reserve_collateral_mint_pubkey,
+        spl_token::state::Mint::LEN,
+        &token_program_id,
+        &accounts.rent_sysvar,
+        &system_program,
+        ctx.signer_seeds,
+    )?;
+    for token_account in [
+        &accounts.reserve_liquidity_supply_pubkey,
+        &accounts.reserve_collateral_supply_pubkey,
+        &accounts.fee_receiver_pubkey,
+        &accounts.destination_collateral_pubkey,
+    ] {
+        allocate(
+            &accounts.payer,
+            token_account,
+            spl_token::state::Account::LEN,
+            &token_program_id,
+            &accounts.rent_sysvar,
+            &system_program,
+            ctx.signer_seeds,
+        )?;
+    }
+
+    init_reserve(
+        CpiContext::new_with_signer(
+            ctx.program.clone(),
+            InitReserveAccounts {
+                source_liquidity_pubkey: accounts.source_liquidity_pubkey.clone(),
+                destination_collateral_pubkey: accounts.destination_collateral_pubkey.clone(),
+                reserve_pubkey: accounts.reserve_pubkey.clone(),
+                reserve_liquidity_mint_pubkey: accounts.reserve_liquidity_mint_pubkey.clone(),
+                reserve_liquidity_supply_pubkey: accounts.reserve_liquidity_supply_pubkey.clone(),
+                reserve_collateral_mint_pubkey: accounts.reserve_collateral_mint_pubkey.clone(),
+                reserve_collateral_supply_pubkey: accounts
+                    .reserve_collateral_supply_pubkey
+                    .clone(),
+                pyth_product_pubkey: accounts.pyth_product_pubkey.clone(),
+                pyth_price_pubkey: accounts.pyth_price_pubkey.clone(),
+                switchboard_feed_pubkey: accounts.switchboard_feed_pubkey.clone(),
+                lending_market_pubkey: accounts.lending_market_pubkey.clone(),
+                lending_market_owner_pubkey: accounts.lending_market_owner_pubkey.clone(),
+                user_transfer_authority_pubkey: accounts.user_transfer_authority_pubkey.clone(),
+                fee_receiver_pubkey: accounts.fee_receiver_pubkey.clone(),
+                lending_market_authority_pubkey: accounts.lending_market_authority_pubkey.clone(),
+                rent_sysvar: accounts.rent_sysvar.clone(),
+                clock_sysvar: accounts.clock_sysvar.clone(),
+                token_program: accounts.token_program.clone(),
+            },
+            ctx.signer_seeds,
+        ),
+        liquidity_amount,
+        config_data,
+    )
+}
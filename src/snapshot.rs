@@ -0,0 +1,374 @@
+use std::{collections::HashMap, fmt};
+
+use anchor_lang::{prelude::Pubkey, solana_program::program_error::ProgramError};
+use solend_token_lending::{
+    math::{Decimal, WAD},
+    state::{Obligation, Reserve},
+};
+
+use crate::liquidation::{find_reserve, refreshed_obligation};
+
+fn decimal_to_f64(value: Decimal) -> Result<f64, ProgramError> {
+    Ok(value.to_scaled_val()? as f64 / WAD as f64)
+}
+
+/// Width of the asset column in the rendered table
+const SYMBOL_WIDTH: usize = 12;
+
+/// A mint address shortened to fit the asset column, such as `So11..1112`
+fn short_address(mint: &Pubkey) -> String {
+    let address = mint.to_string();
+    format!("{}..{}", &address[..4], &address[address.len() - 4..])
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PositionEntry {
+    pub reserve: Pubkey,
+    pub mint: Pubkey,
+    pub symbol: String,
+    /// Liquidity amount in whole tokens; deposits are converted from collateral
+    pub amount: f64,
+    /// Collateral tokens for deposits, borrowed liquidity rounded up for borrows
+    pub raw_amount: u64,
+    pub usd_value: f64,
+}
+
+/// A user's position in human units, for support tooling and tests
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObligationSnapshot {
+    pub obligation: Pubkey,
+    pub owner: Pubkey,
+    pub lending_market: Pubkey,
+    pub deposits: Vec<PositionEntry>,
+    pub borrows: Vec<PositionEntry>,
+    pub deposited_value: f64,
+    pub borrowed_value: f64,
+    pub allowed_borrow_value: f64,
+    pub unhealthy_borrow_value: f64,
+    /// Borrowed value over deposited value
+    pub loan_to_value: f64,
+    /// Weighted liquidation threshold, unhealthy borrow value over deposited value
+    pub liquidation_threshold: f64,
+    /// Fraction collateral can lose before the obligation becomes liquidatable,
+    /// zero once it already is
+    pub distance_to_liquidation: f64,
+}
+
+impl ObligationSnapshot {
+    /// Values the obligation at the reserves' last refreshed prices. `symbols` maps
+    /// liquidity mints to display names; unknown mints are shown by shortened address.
+    pub fn new(
+        obligation_pubkey: Pubkey,
+        obligation: &Obligation,
+        reserves: &HashMap<Pubkey, Reserve>,
+        symbols: &HashMap<Pubkey, String>,
+    ) -> Result<Self, ProgramError> {
+        let obligation = refreshed_obligation(obligation, reserves)?;
+        let entry = |reserve_pubkey: Pubkey,
+                     liquidity_amount: Decimal,
+                     raw_amount: u64,
+                     market_value: Decimal|
+         -> Result<PositionEntry, ProgramError> {
+            let reserve = find_reserve(reserves, &reserve_pubkey)?;
+            let mint = reserve.liquidity.mint_pubkey;
+            let decimals = 10u64
+                .checked_pow(reserve.liquidity.mint_decimals as u32)
+                .ok_or(ProgramError::InvalidAccountData)?;
+            Ok(PositionEntry {
+                reserve: reserve_pubkey,
+                mint,
+                symbol: symbols
+                    .get(&mint)
+                    .cloned()
+                    .unwrap_or_else(|| short_address(&mint)),
+                amount: decimal_to_f64(liquidity_amount)? / decimals as f64,
+                raw_amount,
+                usd_value: decimal_to_f64(market_value)?,
+            })
+        };
+
+        let deposits = obligation
+            .deposits
+            .iter()
+            .map(|collateral| {
+                let reserve = find_reserve(reserves, &collateral.deposit_reserve)?;
+                let liquidity_amount = reserve
+                    .collateral_exchange_rate()?
+                    .decimal_collateral_to_liquidity(collateral.deposited_amount.into())?;
+                entry(
+                    collateral.deposit_reserve,
+                    liquidity_amount,
+                    collateral.deposited_amount,
+                    collateral.market_value,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let borrows = obligation
+            .borrows
+            .iter()
+            .map(|liquidity| {
+                entry(
+                    liquidity.borrow_reserve,
+                    liquidity.borrowed_amount_wads,
+                    liquidity.borrowed_amount_wads.try_ceil_u64()?,
+                    liquidity.market_value,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let deposited_value = decimal_to_f64(obligation.deposited_value)?;
+        let borrowed_value = decimal_to_f64(obligation.borrowed_value)?;
+        let unhealthy_borrow_value = decimal_to_f64(obligation.unhealthy_borrow_value)?;
+        let ratio = |value: f64| {
+            if deposited_value > 0.0 {
+                value / deposited_value
+            } else {
+                0.0
+            }
+        };
+        let distance_to_liquidation = if unhealthy_borrow_value > 0.0 {
+            (1.0 - borrowed_value / unhealthy_borrow_value).max(0.0)
+        } else if borrowed_value > 0.0 {
+            0.0
+        } else {
+            1.0
+        };
+
+        Ok(Self {
+            obligation: obligation_pubkey,
+            owner: obligation.owner,
+            lending_market: obligation.lending_market,
+            deposits,
+            borrows,
+            deposited_value,
+            borrowed_value,
+            allowed_borrow_value: decimal_to_f64(obligation.allowed_borrow_value)?,
+            unhealthy_borrow_value,
+            loan_to_value: ratio(borrowed_value),
+            liquidation_threshold: ratio(unhealthy_borrow_value),
+            distance_to_liquidation,
+        })
+    }
+
+    #[cfg(feature = "client")]
+    pub fn to_json(&self) -> serde_json::Value {
+        let entries = |entries: &[PositionEntry]| {
+            entries
+                .iter()
+                .map(|entry| {
+                    serde_json::json!({
+                        "reserve": entry.reserve.to_string(),
+                        "mint": entry.mint.to_string(),
+                        "symbol": entry.symbol,
+                        "amount": entry.amount,
+                        "raw_amount": entry.raw_amount,
+                        "usd_value": entry.usd_value,
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+        serde_json::json!({
+            "obligation": self.obligation.to_string(),
+            "owner": self.owner.to_string(),
+            "lending_market": self.lending_market.to_string(),
+            "deposits": entries(&self.deposits),
+            "borrows": entries(&self.borrows),
+            "deposited_value": self.deposited_value,
+            "borrowed_value": self.borrowed_value,
+            "allowed_borrow_value": self.allowed_borrow_value,
+            "unhealthy_borrow_value": self.unhealthy_borrow_value,
+            "loan_to_value": self.loan_to_value,
+            "liquidation_threshold": self.liquidation_threshold,
+            "distance_to_liquidation": self.distance_to_liquidation,
+        })
+    }
+}
+
+impl fmt::Display for ObligationSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Obligation {}", self.obligation)?;
+        writeln!(f, "Owner      {}", self.owner)?;
+        writeln!(f, "Market     {}", self.lending_market)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<8} {:<width$} {:>20} {:>16}  {}",
+            "Side",
+            "Asset",
+            "Amount",
+            "Value (USD)",
+            "Reserve",
+            width = SYMBOL_WIDTH
+        )?;
+        for (side, entries) in [("Deposit", &self.deposits), ("Borrow", &self.borrows)] {
+            for entry in entries.iter() {
+                // Longer symbols are cut so the columns stay aligned
+                writeln!(
+                    f,
+                    "{:<8} {:<width$.width$} {:>20.6} {:>16.2}  {}",
+                    side,
+                    entry.symbol,
+                    entry.amount,
+                    entry.usd_value,
+                    entry.reserve,
+                    width = SYMBOL_WIDTH
+                )?;
+            }
+        }
+        writeln!(f)?;
+        writeln!(f, "Deposited value          {:>16.2}", self.deposited_value)?;
+        writeln!(f, "Borrowed value           {:>16.2}", self.borrowed_value)?;
+        writeln!(f, "Allowed borrow value     {:>16.2}", self.allowed_borrow_value)?;
+        writeln!(f, "Unhealthy borrow value   {:>16.2}", self.unhealthy_borrow_value)?;
+        writeln!(f, "LTV                      {:>15.2}%", self.loan_to_value * 100.0)?;
+        writeln!(
+            f,
+            "Liquidation threshold    {:>15.2}%",
+            self.liquidation_threshold * 100.0
+        )?;
+        write!(
+            f,
+            "Distance to liquidation  {:>15.2}%",
+            self.distance_to_liquidation * 100.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solend_token_lending::state::{ObligationCollateral, ObligationLiquidity};
+
+    use super::*;
+
+    const ONE: u64 = 1_000_000;
+
+    fn reserve(mint: Pubkey, price: u64) -> Reserve {
+        let mut reserve = Reserve::default();
+        reserve.liquidity.mint_pubkey = mint;
+        reserve.liquidity.mint_decimals = 6;
+        reserve.liquidity.market_price = Decimal::from(price);
+        reserve.liquidity.cumulative_borrow_rate_wads = Decimal::one();
+        reserve.config.loan_to_value_ratio = 50;
+        reserve.config.liquidation_threshold = 55;
+        reserve
+    }
+
+    struct Position {
+        deposit_reserve: Pubkey,
+        borrow_reserve: Pubkey,
+        borrow_mint: Pubkey,
+        snapshot: ObligationSnapshot,
+    }
+
+    /// 100 SOL at $10 against 400 tokens of $1 debt whose mint has no symbol
+    fn position() -> Position {
+        let deposit_reserve = Pubkey::new_unique();
+        let borrow_reserve = Pubkey::new_unique();
+        let deposit_mint = Pubkey::new_unique();
+        let borrow_mint = Pubkey::new_unique();
+        let mut collateral = ObligationCollateral::new(deposit_reserve);
+        collateral.deposited_amount = 100 * ONE;
+        let mut liquidity = ObligationLiquidity::new(borrow_reserve);
+        liquidity.borrowed_amount_wads = Decimal::from(400 * ONE);
+        let obligation = Obligation {
+            deposits: vec![collateral],
+            borrows: vec![liquidity],
+            ..Obligation::default()
+        };
+
+        let mut reserves = HashMap::new();
+        reserves.insert(deposit_reserve, reserve(deposit_mint, 10));
+        reserves.insert(borrow_reserve, reserve(borrow_mint, 1));
+        let mut symbols = HashMap::new();
+        symbols.insert(deposit_mint, "SOL".to_string());
+
+        let snapshot =
+            ObligationSnapshot::new(Pubkey::new_unique(), &obligation, &reserves, &symbols)
+                .unwrap();
+        Position {
+            deposit_reserve,
+            borrow_reserve,
+            borrow_mint,
+            snapshot,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn snapshot_values_the_position_in_human_units() {
+        let Position {
+            deposit_reserve,
+            borrow_reserve,
+            borrow_mint,
+            snapshot,
+        } = position();
+
+        assert_eq!(snapshot.deposits.len(), 1);
+        let deposit = &snapshot.deposits[0];
+        assert_eq!(deposit.reserve, deposit_reserve);
+        assert_eq!(deposit.symbol, "SOL");
+        assert_eq!(deposit.raw_amount, 100 * ONE);
+        assert_close(deposit.amount, 100.0);
+        assert_close(deposit.usd_value, 1_000.0);
+
+        assert_eq!(snapshot.borrows.len(), 1);
+        let borrow = &snapshot.borrows[0];
+        assert_eq!(borrow.reserve, borrow_reserve);
+        assert_eq!(borrow.symbol, short_address(&borrow_mint));
+        assert_eq!(borrow.raw_amount, 400 * ONE);
+        assert_close(borrow.amount, 400.0);
+        assert_close(borrow.usd_value, 400.0);
+
+        assert_close(snapshot.deposited_value, 1_000.0);
+        assert_close(snapshot.borrowed_value, 400.0);
+        assert_close(snapshot.allowed_borrow_value, 500.0);
+        assert_close(snapshot.unhealthy_borrow_value, 550.0);
+        assert_close(snapshot.loan_to_value, 0.4);
+        assert_close(snapshot.liquidation_threshold, 0.55);
+        assert_close(snapshot.distance_to_liquidation, 1.0 - 400.0 / 550.0);
+    }
+
+    #[test]
+    fn empty_obligation_is_far_from_liquidation() {
+        let snapshot = ObligationSnapshot::new(
+            Pubkey::new_unique(),
+            &Obligation::default(),
+            &HashMap::new(),
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_close(snapshot.loan_to_value, 0.0);
+        assert_close(snapshot.distance_to_liquidation, 1.0);
+    }
+
+    #[test]
+    fn table_columns_stay_aligned_for_unknown_mints() {
+        let Position {
+            deposit_reserve,
+            borrow_reserve,
+            snapshot,
+            ..
+        } = position();
+        let table = snapshot.to_string();
+        let lines: Vec<&str> = table.lines().collect();
+
+        let header = lines.iter().find(|line| line.starts_with("Side")).unwrap();
+        let reserve_column = header.find("Reserve").unwrap();
+        for reserve in [deposit_reserve, borrow_reserve] {
+            let row = lines
+                .iter()
+                .find(|line| line.ends_with(&reserve.to_string()))
+                .unwrap();
+            assert_eq!(row.find(&reserve.to_string()), Some(reserve_column));
+        }
+
+        assert!(lines.iter().any(|line| line.starts_with("Deposit  SOL ")
+            && line.contains("100.000000")
+            && line.contains("1000.00")));
+        assert!(table.contains("LTV                                40.00%"));
+        assert!(table.ends_with("Distance to liquidation            27.27%"));
+    }
+}
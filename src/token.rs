@@ -0,0 +1,111 @@
+use anchor_lang::{
+    prelude::AccountInfo,
+    solana_program::{self, entrypoint::ProgramResult, program_error::ProgramError, program_pack::Pack},
+    Key,
+};
+
+/// Amount held by an SPL token account
+pub fn token_balance(account: &AccountInfo) -> Result<u64, ProgramError> {
+    Ok(spl_token::state::Account::unpack(&account.try_borrow_data()?)?.amount)
+}
+
+pub fn transfer<'info>(
+    token_program: &AccountInfo<'info>,
+    source: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let ix = spl_token::instruction::transfer(
+        &token_program.key(),
+        &source.key(),
+        &destination.key(),
+        &authority.key(),
+        &[],
+        amount,
+    )?;
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            source.clone(),
+            destination.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+/// Lets `delegate` transfer up to `amount` out of `source`
+pub fn approve<'info>(
+    token_program: &AccountInfo<'info>,
+    source: &AccountInfo<'info>,
+    delegate: &AccountInfo<'info>,
+    owner: &AccountInfo<'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let ix = spl_token::instruction::approve(
+        &token_program.key(),
+        &source.key(),
+        &delegate.key(),
+        &owner.key(),
+        &[],
+        amount,
+    )?;
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            source.clone(),
+            delegate.clone(),
+            owner.clone(),
+            token_program.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+/// Removes whatever delegation `source` has
+pub fn revoke<'info>(
+    token_program: &AccountInfo<'info>,
+    source: &AccountInfo<'info>,
+    owner: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let ix =
+        spl_token::instruction::revoke(&token_program.key(), &source.key(), &owner.key(), &[])?;
+    solana_program::program::invoke_signed(
+        &ix,
+        &[source.clone(), owner.clone(), token_program.clone()],
+        signer_seeds,
+    )
+}
+
+/// Balances of the two token accounts a CPI moves tokens between
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TokenBalances {
+    pub source: u64,
+    pub destination: u64,
+}
+
+impl TokenBalances {
+    pub fn measure(source: &AccountInfo, destination: &AccountInfo) -> Result<Self, ProgramError> {
+        Ok(Self {
+            source: token_balance(source)?,
+            destination: token_balance(destination)?,
+        })
+    }
+
+    /// Amounts that left `source` and arrived in `destination` since `self` was measured
+    pub fn deltas(
+        &self,
+        source: &AccountInfo,
+        destination: &AccountInfo,
+    ) -> Result<(u64, u64), ProgramError> {
+        Ok((
+            self.source.saturating_sub(token_balance(source)?),
+            token_balance(destination)?.saturating_sub(self.destination),
+        ))
+    }
+}